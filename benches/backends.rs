@@ -1,6 +1,6 @@
 use criterion::{criterion_group, criterion_main, Criterion};
 
-use wallust::backends::{self, Backend};
+use wallust::backends::{Backend, Loader, Params, Source};
 
 use strum::IntoEnumIterator;
 
@@ -8,9 +8,12 @@ include!("util.in");
 
 fn backends(c: &mut Criterion) {
     let root = get_dir();
+    let params = Params::default();
+    let loader = Loader::default();
 
     for curr in Backend::iter() {
         let mut group = c.benchmark_group(curr.to_string());
+        let parser = curr.parser(&params);
 
         //iterate over all images
         for image in IMAGES {
@@ -19,7 +22,7 @@ fn backends(c: &mut Criterion) {
             //with all possible backends
             group.bench_function(
                 image,
-                |b| b.iter(|| backends::main(&curr)(&path).expect("Download the images"))
+                |b| b.iter(|| parser.parse(&Source::from(&path), &loader).expect("Download the images"))
 
             );
         }
@@ -1,7 +1,7 @@
 use criterion::{criterion_group, criterion_main, Criterion, BenchmarkId};
 
-use wallust::backends::{self, Backend};
-use wallust::colorspaces::{self, ColorSpace};
+use wallust::backends::{Full, ImageParser, Loader, Source};
+use wallust::colorspaces::{Bounds, ColorOrder, ColorSpace, FallbackGenerator, Scoring, Threshold, GRID_BITS};
 
 use strum::IntoEnumIterator;
 
@@ -12,7 +12,12 @@ fn colorspaces(c: &mut Criterion) {
     let path = root.join("target").join("benchimg");
     let threshold = 20;
 
-    let read_images = IMAGES.map(|x| backends::main(&Backend::Full)(&path.join(x)).unwrap());
+    if !path.exists() {
+        eprintln!("Skipping the images, download them with benches/dl.sh");
+        return;
+    }
+
+    let read_images = IMAGES.map(|x| Full.parse(&Source::from(path.join(x)), &Loader::default()).unwrap());
 
     for curr in ColorSpace::iter() {
         let mut group = c.benchmark_group(curr.to_string());
@@ -21,8 +26,92 @@ fn colorspaces(c: &mut Criterion) {
             group.bench_with_input(
                 BenchmarkId::new(IMAGES[idx], image.len()),
                 &image,
-                |b, i| b.iter(|| colorspaces::main(curr, i, threshold, &colorspaces::Generate::default()))
+                |b, i| b.iter(|| curr.run(Threshold::Fixed(threshold), i, &FallbackGenerator::default(), &ColorOrder::DarkFirst, &Bounds::default(), &Scoring::default()))
+
+            );
+        }
+    }
+}
+
+/// A 4K "photo" made up on the spot, so it runs without downloading the images: smooth gradients
+/// with some grain, which gives plenty of unique colors, like the `full` backend does.
+fn synthetic() -> Vec<u8> {
+    let (w, h) = (3840_u32, 2160_u32);
+    let mut seed = 0x2545_f491_u32;
+    let mut grain = move || {
+        seed ^= seed << 13;
+        seed ^= seed >> 17;
+        seed ^= seed << 5;
+        (seed % 7) as f32 - 3.0
+    };
 
+    let mut ret = Vec::with_capacity((w * h * 3) as usize);
+    for y in 0..h {
+        for x in 0..w {
+            let (u, v) = (x as f32 / w as f32, y as f32 / h as f32);
+            let px = [
+                120.0 + 100.0 * (u * 5.0).sin() * v,
+                90.0 + 80.0 * (v * 3.0 + u).cos(),
+                140.0 - 110.0 * (u * v * 7.0).sin(),
+            ];
+            ret.extend(px.map(|c| (c + grain()).clamp(0.0, 255.0) as u8));
+        }
+    }
+    ret
+}
+
+/// Fixed and automatic thresholds over the same 4K image, the automatic one tries many. With
+/// every color apart (the default) and with `grid = true`.
+fn thresholds(c: &mut Criterion) {
+    let image = synthetic();
+    let grid = Bounds { grid: true, ..Bounds::default() };
+
+    for curr in [ColorSpace::Lab, ColorSpace::Lch, ColorSpace::OkLab] {
+        let mut group = c.benchmark_group(format!("{curr} 4K"));
+
+        for (name, th, bounds) in [
+            ("fixed", Threshold::Fixed(20), Bounds::default()),
+            ("auto", Threshold::Auto(None), Bounds::default()),
+            ("fixed grid", Threshold::Fixed(20), grid),
+            ("auto grid", Threshold::Auto(None), grid),
+        ] {
+            group.bench_with_input(
+                BenchmarkId::new(name, image.len()),
+                &image,
+                |b, i| b.iter(|| curr.run(th, i, &FallbackGenerator::default(), &ColorOrder::DarkFirst, &bounds, &Scoring::default()))
+            );
+        }
+    }
+}
+
+/// The OKLab based colorspaces, over the same 4K image, next to `lab` to compare them.
+fn oklab(c: &mut Criterion) {
+    let image = synthetic();
+    let mut group = c.benchmark_group("oklab 4K");
+
+    for curr in [ColorSpace::Lab, ColorSpace::OkLab, ColorSpace::OkLabMixed, ColorSpace::OkLch, ColorSpace::OkLchMixed] {
+        group.bench_with_input(
+            BenchmarkId::new(curr.to_string(), image.len()),
+            &image,
+            |b, i| b.iter(|| curr.run(Threshold::Fixed(20), i, &FallbackGenerator::default(), &ColorOrder::DarkFirst, &Bounds::default(), &Scoring::default()))
+        );
+    }
+}
+
+/// Gathering the histogram of the same 4K image: every pixel on its own (how it worked before
+/// counting each color once), every color apart (the default) and the near ones put together on
+/// the grid (`grid = true`).
+fn grid(c: &mut Criterion) {
+    let image = synthetic();
+
+    for curr in [ColorSpace::Lab, ColorSpace::Lch, ColorSpace::LchAnsi, ColorSpace::OkLab] {
+        let mut group = c.benchmark_group(format!("{curr} 4K histogram"));
+
+        for (name, bits) in [("per pixel", None), ("exact", Some(8)), ("grid", Some(GRID_BITS))] {
+            group.bench_with_input(
+                BenchmarkId::new(name, image.len()),
+                &image,
+                |b, i| b.iter(|| curr.histogram(i, 20, bits, &Bounds::default()))
             );
         }
     }
@@ -31,6 +120,6 @@ fn colorspaces(c: &mut Criterion) {
 criterion_group! {
   name = benches;
   config = Criterion::default().sample_size(10);
-  targets = colorspaces
+  targets = colorspaces, thresholds, oklab, grid
 }
 criterion_main!(benches);
@@ -0,0 +1,22 @@
+//! Records what image magick gives for `tests/fixtures/wal/sample.png` with the `wal` backend,
+//! into `tests/fixtures/wal/sample.txt`, which `octree_matches_wal` (tests/backends.rs) compares
+//! the native `octree` backend against. Needs `magick` or `convert` installed:
+//! ```sh
+//! cargo run --example record_wal
+//! ```
+use std::path::Path;
+
+use wallust::backends::{Backend, Loader, Params, Source};
+use wallust::backends::wal::has_im;
+
+fn main() -> anyhow::Result<()> {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/wal");
+
+    has_im()?;
+    let wal = Backend::Wal.parser(&Params::default()).parse(&Source::from(dir.join("sample.png")), &Loader::default())?;
+    let lines: String = wal.chunks(3).map(|c| format!("#{:02X}{:02X}{:02X}\n", c[0], c[1], c[2])).collect();
+
+    std::fs::write(dir.join("sample.txt"), lines)?;
+    println!("{} colors written to {}", wal.len() / 3, dir.join("sample.txt").display());
+    Ok(())
+}
@@ -1,11 +1,12 @@
 //! Type declarations for working with clap `derive`, subcommands, flags, value parsers ...
 
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
 
 use crate::{
-    backends::Backend,
+    backends::{Backend, Source},
     colorspaces::ColorSpace,
-    palettes::Palette,
+    palettes::{Palette, PaletteName},
     themes::Schemes,
 };
 
@@ -113,13 +114,53 @@ pub enum Subcmds {
 /// No subcommands, global arguments
 #[derive(Parser, Debug, Clone, Default)]
 pub struct WallustArgs {
-    /// Path to the image to use
-    pub file: PathBuf,
+    /// Path to the image to use, `-` reads it from stdin. More than one are combined into a single
+    /// palette, each one can have a weight, `image.png:2`, to count more (or less, `image.png:0.5`)
+    /// than the others
+    #[arg(required_unless_present = "outputs", num_args = 1.., value_name = "FILE")]
+    pub files: Vec<Input>,
+
+    /// Also generate a palette for a monitor from its own image, `NAME=image.png`, available to
+    /// templates as `outputs["NAME"]`. Without any FILE, the main palette combines these images
+    #[arg(long = "output", value_name = "NAME=FILE")]
+    pub outputs: Vec<Output>,
 
     /// Alpha *template variable* value, used only for templating (default is 100)
     #[arg(short, long, value_parser = 0..=100)]
     pub alpha: Option<i64>,
 
+    /// Ignore pixels with an alpha below this percentage, 0 keeps every pixel (overwrites config)
+    #[arg(long, value_parser = 0..=100)]
+    pub alpha_cutoff: Option<i64>,
+
+    /// Only read this part of the image, `x,y,w,h` or `gravity:WxH` in fractions, e.g. `0.5,0,0.5,1` (overwrites config)
+    #[arg(long, visible_alias = "crop")]
+    pub region: Option<crate::backends::Region>,
+
+    /// Read this many frames, evenly spaced in time, from animated images (overwrites config)
+    #[arg(long, value_parser = 1..=i64::from(crate::backends::MAX_FRAMES))]
+    pub frames: Option<i64>,
+
+    /// How to map 16 bit and HDR images to 8 bits (overwrites config)
+    #[arg(long, value_enum)]
+    pub tone_map: Option<crate::backends::ToneMap>,
+
+    /// Read the image as sRGB, ignoring its embedded ICC profile (overwrites config)
+    #[arg(long)]
+    pub ignore_icc: bool,
+
+    /// Weight pixels by their importance before counting colors (overwrites config)
+    #[arg(long, value_enum)]
+    pub weighting: Option<crate::colorspaces::Weighting>,
+
+    /// Rank the colors by count, chroma or vibrancy, keeping the best ones (overwrites config)
+    #[arg(long, value_enum)]
+    pub scoring: Option<crate::colorspaces::Scoring>,
+
+    /// Leave out uniform bars on the sides of the image, like letterboxes (overwrites config)
+    #[arg(long)]
+    pub trim_borders: bool,
+
     /// Choose which backend to use (overwrites config)
     #[arg(short, long, value_enum)]
     pub backend: Option<Backend>,
@@ -128,6 +169,22 @@ pub struct WallustArgs {
     #[arg(short, long, value_enum)]
     pub colorspace: Option<ColorSpace>,
 
+    /// Leave out colors darker than this lightness, from 0 to 100 (overwrites config)
+    #[arg(long, value_parser = bound)]
+    pub darkest: Option<f32>,
+
+    /// Leave out colors lighter than this lightness, from 0 to 100 (overwrites config)
+    #[arg(long, value_parser = bound)]
+    pub lightest: Option<f32>,
+
+    /// Leave out colors with less chroma than this, from 0 to 100 (overwrites config)
+    #[arg(long, value_parser = bound)]
+    pub min_chroma: Option<f32>,
+
+    /// Average near colors on a grid before comparing them, faster with big images (overwrites config)
+    #[arg(long)]
+    pub grid: bool,
+
     /// Choose which fallback generation method to use (overwrites config)
     #[arg(short, long, value_enum)]
     pub fallback_generator: Option<crate::colorspaces::FallbackGenerator>,
@@ -140,18 +197,26 @@ pub struct WallustArgs {
     #[arg(short, long)]
     pub no_cache: bool,
 
-    /// Choose which palette to use (overwrites config)
-    #[arg(short, long, value_enum)]
-    pub palette: Option<Palette>,
+    /// Choose which palette to use, or one of the `[palettes]` of the config file (overwrites config)
+    #[arg(short, long, value_parser = PaletteParser)]
+    pub palette: Option<PaletteName>,
+
+    /// Also generate the dark and light palettes of the `[palette]` table, for templates (overwrites config)
+    #[arg(long)]
+    pub variants: bool,
 
     /// Add saturation from 1% to 100% (overwrites config)
     #[arg(long, value_parser = 1..=100)]
     pub saturation: Option<i64>,
 
-    /// Choose a custom threshold, between 1 and 100 (overwrites config)
+    /// Choose a custom threshold, between 1 and 100, the kmeans colorspace ignores it (overwrites config)
     #[arg(short, long, value_parser = 1..=100)]
     pub threshold: Option<i64>,
 
+    /// How many colors to look for, between 6 and 16, instead of a threshold (overwrites config)
+    #[arg(long, value_parser = 6..=16, conflicts_with = "threshold")]
+    pub color_count: Option<i64>,
+
     /// Dynamically changes the threshold to be best fit
     #[arg(long, conflicts_with = "threshold")]
     pub dynamic_threshold: bool,
@@ -162,6 +227,121 @@ pub struct WallustArgs {
     pub overwrite_cache: bool,
 }
 
+impl WallustArgs {
+    /// Images of the main palette, the ones of the `outputs` if no FILE was given.
+    pub fn inputs(&self) -> Vec<Input> {
+        if !self.files.is_empty() { return self.files.clone(); }
+        self.outputs.iter().map(|o| o.input.clone()).collect()
+    }
+
+    /// The first image, used as the wallpaper
+    pub fn file(&self) -> &Source {
+        match self.files.first() {
+            Some(i) => &i.source,
+            None => &self.outputs[0].input.source,
+        }
+    }
+
+    /// Read the image given as `-` from stdin, it can only be given once.
+    pub fn read_stdin(mut self) -> anyhow::Result<Self> {
+        let mut stdin = None;
+        for i in self.files.iter_mut().chain(self.outputs.iter_mut().map(|o| &mut o.input)) {
+            if i.source.path() != Some(Path::new("-")) { continue; }
+            if stdin.is_some() { anyhow::bail!("Only one image can be read from stdin."); }
+            i.source = Source::stdin()?;
+            stdin = Some(());
+        }
+        Ok(self)
+    }
+}
+
+/// A lightness or chroma bound of the colorspace, see [`crate::colorspaces::Bounds`]
+fn bound(s: &str) -> Result<f32, String> {
+    match s.parse::<f32>() {
+        Ok(v) if (0.0..=100.0).contains(&v) => Ok(v),
+        _ => Err(format!("expected a number between 0 and 100, not '{s}'")),
+    }
+}
+
+/// A built in [`Palette`], which are the ones completed and shown in `--help`, or the name of a
+/// custom one, see [`PaletteName`].
+#[derive(Clone)]
+struct PaletteParser;
+
+impl clap::builder::TypedValueParser for PaletteParser {
+    type Value = PaletteName;
+
+    fn parse_ref(&self, cmd: &clap::Command, arg: Option<&clap::Arg>, value: &std::ffi::OsStr) -> Result<Self::Value, clap::Error> {
+        let s = clap::builder::StringValueParser::new().parse_ref(cmd, arg, value)?;
+        Ok(s.parse().unwrap_or_else(|e| match e {}))
+    }
+
+    fn possible_values(&self) -> Option<Box<dyn Iterator<Item = clap::builder::PossibleValue> + '_>> {
+        use clap::ValueEnum;
+        Some(Box::new(Palette::value_variants().iter().filter_map(|p| p.to_possible_value())))
+    }
+}
+
+/// A monitor (output) with its own palette, `NAME=FILE`, see [`Input`] for the FILE part.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Output {
+    /// Name of the output, like `DP-1` or `HDMI-A-1`
+    pub name: String,
+    pub input: Input,
+}
+
+impl FromStr for Output {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        match s.split_once('=') {
+            Some((name, file)) if !name.is_empty() && !file.is_empty() => Ok(Self { name: name.to_string(), input: file.parse()? }),
+            _ => anyhow::bail!("Expected NAME=FILE, like DP-1=image.png, not '{s}'."),
+        }
+    }
+}
+
+/// An image given to `wallust run` and how much it counts when combined with others, see
+/// [`crate::combine`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Input {
+    pub source: Source,
+    /// Share of the pixels, relative to the other images
+    pub weight: f32,
+}
+
+impl From<Source> for Input {
+    fn from(source: Source) -> Self {
+        Self { source, weight: 1.0 }
+    }
+}
+
+impl From<PathBuf> for Input {
+    fn from(path: PathBuf) -> Self {
+        Source::Path(path).into()
+    }
+}
+
+/// `path` or `path:weight`, an existing file is always a path (even with a `:` in its name)
+impl FromStr for Input {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        if !Path::new(s).exists() {
+            if let Some((path, w)) = s.rsplit_once(':').filter(|(p, _)| !p.is_empty()) {
+                if let Ok(weight) = w.parse::<f32>() {
+                    if !(weight.is_finite() && weight > 0.0) {
+                        anyhow::bail!("The weight of '{path}' should be a positive number, not {w}.");
+                    }
+                    return Ok(Self { source: PathBuf::from(path).into(), weight });
+                }
+            }
+        }
+
+        Ok(PathBuf::from(s).into())
+    }
+}
+
 /// Pywal cli flags arguments. This is to create a drop in replacement, since many apps rely on the
 /// `pywal` command. However, cli flags are ignored, as of now.
 #[derive(Parser, Debug, Clone, Default)]
@@ -256,17 +436,32 @@ impl From<PywalArgs> for WallustArgs {
         Self {
             // All empty so wallust prioritizes the config file
             alpha: None,
+            alpha_cutoff: None,
+            region: None,
+            frames: None,
+            tone_map: None,
+            ignore_icc: false,
+            weighting: None,
+            scoring: None,
+            trim_borders: false,
             backend: None,
             colorspace: None,
+            darkest: None,
+            lightest: None,
+            min_chroma: None,
+            grid: false,
             check_contrast: false,
             dynamic_threshold: true,
             fallback_generator: None,
             no_cache: false,
             overwrite_cache: false,
             palette: None,
+            variants: false,
             saturation: None,
             threshold: None,
-            file: p.file.expect("ALWAYS SOME, CHECKED ON MAIN"),
+            color_count: None,
+            files: vec![p.file.expect("ALWAYS SOME, CHECKED ON MAIN").into()],
+            outputs: vec![],
         }
     }
 }
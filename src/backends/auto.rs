@@ -0,0 +1,97 @@
+//! # Auto
+//! Pick the backend by looking at the image header (and the [`Loader`] options), without
+//! decoding it:
+//! * Small images are read whole, with [`Full`], resizing them only loses colors.
+//! * GIFs have a limited palette, so [`Full`] is used (resize filters invent in between colors),
+//!   unless they are big.
+//! * Really big images (more than 4K), animations read with many frames, and 16 bit or HDR images
+//!   use [`Resized`], since [`FastResize`] produces glitches at large sizes.
+//! * Everything else uses [`FastResize`].
+//!
+//! The rest of [`FastResize`], [`Resized`], [`Full`] and [`Thumb`] are kept, in that order of
+//! preference, as fallbacks in case the chosen one fails or doesn't give enough colors. Backends
+//! that need external programs ([`Wal`]) or change the look of the palette ([`Kmeans`],
+//! [`Octree`]) are never picked.
+use crate::backends::*;
+use image::{ColorType, ImageFormat};
+
+/// Up to this many pixels, the image is read whole.
+const SMALL: u64 = 512 * 512;
+/// More than this many pixels (4K) is considered big.
+const BIG: u64 = 3840 * 2160;
+
+/// Every backend that can be picked, in order of preference as a fallback.
+const CANDIDATES: [Backend; 4] = [B::FastResize, B::Resized, B::Full, B::Thumb];
+
+/// Inspects the image every time it's parsed, trying the backends from [`candidates`] until one
+/// works. Used when calling [`Backend::parser`] directly, the cli resolves the backend beforehand
+/// (see [`crate::config::Config::auto_backend`]), so the choice is printed and cached.
+#[derive(Debug, Clone, Copy)]
+pub struct Auto(pub Params);
+
+impl ImageParser for Auto {
+    fn read(&self, f: &Source, l: &Loader) -> Result<Pixels> {
+        let mut err = None;
+
+        for b in candidates(f, l)? {
+            match b.parser(&self.0).read(f, l) {
+                Ok(p) if enough_colors(&p.rgb) => return Ok(p),
+                Ok(_) => err = Some(anyhow::anyhow!("The {b} backend didn't find enough colors.")),
+                Err(e) => err = Some(e),
+            }
+        }
+
+        Err(err.expect("there is always a candidate"))
+    }
+}
+
+/// Backends to try on the image `f`, best first.
+pub fn candidates(f: &Source, l: &Loader) -> Result<Vec<Backend>> {
+    let (format, (w, h), color) = match f {
+        Source::Image(img) => (None, img.dimensions(), img.color()),
+        _ => {
+            let reader = f.image_reader()?;
+            let format = reader.format();
+            let decoder = reader.into_decoder()?;
+            (format, decoder.dimensions(), decoder.color_type())
+        },
+    };
+
+    let (mut w, mut h) = (f64::from(w), f64::from(h));
+    if let Some(r) = l.region {
+        let (_, _, rw, rh) = r.fractions();
+        w *= f64::from(rw);
+        h *= f64::from(rh);
+    }
+
+    // the frames get stacked into one image
+    let frames = if l.frames > 1 && frames::animation(f)?.is_some() { u64::from(l.frames) } else { 1 };
+    let pixels = (w * h) as u64 * frames;
+
+    let deep = matches!(color,
+        ColorType::L16 | ColorType::La16 | ColorType::Rgb16 | ColorType::Rgba16
+        | ColorType::Rgb32F | ColorType::Rgba32F);
+
+    let first = if pixels <= SMALL {
+        B::Full
+    } else if pixels > BIG || frames > 1 || deep {
+        B::Resized
+    } else if format == Some(ImageFormat::Gif) {
+        B::Full
+    } else {
+        B::FastResize
+    };
+
+    let mut ret = vec![first];
+    ret.extend(CANDIDATES.into_iter().filter(|&b| b != first));
+    Ok(ret)
+}
+
+/// Whether the backend output has at least [`crate::colorspaces::MIN_COLS`] different colors.
+pub fn enough_colors(rgb: &[u8]) -> bool {
+    let mut seen = std::collections::HashSet::new();
+    rgb.chunks_exact(3).any(|c| {
+        seen.insert(c);
+        seen.len() >= crate::colorspaces::MIN_COLS.into()
+    })
+}
@@ -0,0 +1,66 @@
+//! # Borders
+//! Screenshots and film stills often have black (or white) bars, letterbox on the top and bottom
+//! or pillarbox on the sides, that dominate the palette. Each side is walked inwards while the
+//! lines are uniform and of the same color as the outermost one, allowing a few different pixels
+//! (compression noise, subtitles). Only neutral colors (black, gray, white) are bars, so a clear
+//! sky at the top of a photo isn't trimmed.
+use image::RgbaImage;
+
+/// Maximum difference, in any channel, to be the same color as the bar.
+const TOLERANCE: u8 = 24;
+
+/// Fraction of a line that has to be of the bar color.
+const UNIFORM: f32 = 0.98;
+
+/// Bars can't cover more than this fraction of each side, so a flat image isn't trimmed away.
+const MAX_BAR: f32 = 0.4;
+
+/// The image without the bars, `(x, y, w, h)` in pixels. `None` if there aren't any bars.
+pub fn content(img: &RgbaImage, visible: impl Fn(u8) -> bool) -> Option<(u32, u32, u32, u32)> {
+    let (w, h) = img.dimensions();
+    if w < 3 || h < 3 { return None; }
+
+    // a line, as the pixel at every step `i`
+    let bar = |len: u32, px: &dyn Fn(u32) -> [u8; 4]| -> Option<[u8; 3]> {
+        let (mut sum, mut n) = ([0u32; 3], 0);
+        for i in 0..len {
+            let p = px(i);
+            if !visible(p[3]) { continue; }
+            for c in 0..3 { sum[c] += u32::from(p[c]); }
+            n += 1;
+        }
+        // transparent lines are already ignored, no need to trim them
+        if n == 0 { return None; }
+        Some(sum.map(|s| (s / n) as u8))
+    };
+
+    let same = |len: u32, px: &dyn Fn(u32) -> [u8; 4], col: [u8; 3]| -> bool {
+        let matching = (0..len)
+            .filter(|&i| {
+                let p = px(i);
+                !visible(p[3]) || (0..3).all(|c| p[c].abs_diff(col[c]) <= TOLERANCE)
+            })
+            .count();
+        matching as f32 >= len as f32 * UNIFORM
+    };
+
+    // how many lines, from the outside, are part of the bar
+    let side = |lines: u32, len: u32, px: &dyn Fn(u32, u32) -> [u8; 4]| -> u32 {
+        let Some(col) = bar(len, &|i| px(0, i)) else { return 0 };
+        let neutral = col.iter().max().unwrap_or(&0) - col.iter().min().unwrap_or(&0) <= TOLERANCE;
+        if !neutral { return 0; }
+        let max = (lines as f32 * MAX_BAR) as u32;
+        (0..max).take_while(|&l| same(len, &|i| px(l, i), col)).count() as u32
+    };
+
+    let at = |x: u32, y: u32| img.get_pixel(x, y).0;
+
+    let top    = side(h, w, &|l, i| at(i, l));
+    let bottom = side(h, w, &|l, i| at(i, h - 1 - l));
+    let left   = side(w, h, &|l, i| at(l, i));
+    let right  = side(w, h, &|l, i| at(w - 1 - l, i));
+
+    if top + bottom + left + right == 0 { return None; }
+
+    Some((left, top, w - left - right, h - top - bottom))
+}
@@ -1,50 +1,69 @@
 use crate::backends::*;
 use fast_image_resize::images::{Image, ImageRef};
-use fast_image_resize::Resizer;
+use fast_image_resize::{Resizer, ResizeOptions};
 use fast_image_resize::PixelType;
 use image::GenericImageView;
 
 /// Resize it, then get read the image, with an optimized algorithm that uses SIMD operations.
 /// TODO for some reason this method likes really small sizes. Working with 512 or more creates
 /// `green` "glitched" colors, that's why we don't use `shrink()` from `resized` module in here
-pub fn fast_resize(f: &Path) -> Result<Vec<u8>> {
-    //read the image and guess format
-    let img = image::ImageReader::open(f)?
-        .with_guessed_format()?
-        .decode()?;
-
-    let (true_w, true_h) = img.dimensions();
-
-    //custom shrink
-    let s = |x| if x > 512 { x / 4 } else { x };
-
-    let pixels = img.into_rgb8().into_raw();
-
-    // source image
-    let src = ImageRef::new(
-        true_w,
-        true_h,
-        &pixels,
-        PixelType::U8x3, //u8 u8 u8 (r g b)
-    )?;
-
-    //destination (where to write new resized image)
-    let mut dst = Image::new(
-        s(true_w),
-        s(true_h),
-        src.pixel_type(),
-    );
-
-    // Create Resizer instance and resize source image
-    // into buffer of destination image.
-    let mut resizer = Resizer::new();
-    // By default, Resizer multiplies and divides by alpha channel
-    // images with U8x2, U8x4, U16x2 and U16x4 pixels.
-    resizer.resize(&src, &mut dst, None)?;
-
-    //resize
-    // fir::Resizer::new(fir::ResizeAlg::Nearest)
-    //     .resize(&src.view(), &mut dest.view_mut())?;
-
-    Ok(dst.into_vec())
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[cfg_attr(feature = "schema" , derive(schemars::JsonSchema))]
+#[serde(default, deny_unknown_fields)]
+pub struct FastResize {
+    /// Sides bigger than this many pixels get resized
+    pub threshold: u32,
+    /// Divide those sides by this value
+    pub shrink: u32,
+    /// Which filter to resize with
+    pub filter: ResizeFilter,
+}
+
+impl Default for FastResize {
+    fn default() -> Self {
+        Self { threshold: 512, shrink: 4, filter: ResizeFilter::Lanczos3 }
+    }
+}
+
+impl ImageParser for FastResize {
+    fn read(&self, f: &Source, l: &Loader) -> Result<Pixels> {
+        //read the image and guess format
+        let (img, crop, frames) = l.load(f)?;
+
+        let (true_w, true_h) = img.dimensions();
+
+        //custom shrink
+        let s = |x: u32| if x > self.threshold { (x / self.shrink.max(1)).max(1) } else { x };
+
+        let pixels = img.into_rgba8().into_raw();
+
+        // source image
+        let src = ImageRef::new(
+            true_w,
+            true_h,
+            &pixels,
+            PixelType::U8x4, //u8 u8 u8 u8 (r g b a)
+        )?;
+
+        //destination (where to write new resized image)
+        let mut dst = Image::new(
+            s(true_w),
+            s(true_h),
+            src.pixel_type(),
+        );
+
+        // Create Resizer instance and resize source image
+        // into buffer of destination image.
+        let mut resizer = Resizer::new();
+        // By default, Resizer multiplies and divides by alpha channel
+        // images with U8x2, U8x4, U16x2 and U16x4 pixels.
+        let opts = ResizeOptions::new().resize_alg(self.filter.into());
+        resizer.resize(&src, &mut dst, &opts)?;
+
+        l.pixels(dst.buffer(), (dst.width(), dst.height()), frames, crop)
+    }
+
+    fn key(&self) -> String {
+        format!("_t{}_s{}_{}", self.threshold, self.shrink, self.filter)
+    }
 }
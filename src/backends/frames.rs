@@ -0,0 +1,131 @@
+//! # Frames
+//! Animated GIF, APNG and WebP wallpapers. Instead of only using the first frame, `N` frames are
+//! picked evenly spaced *in time* across the whole loop, so a frame that is shown for longer can be
+//! picked more than once, weighting its pixels by how long it's on screen.
+use anyhow::Result;
+use image::{AnimationDecoder, Frames, ImageDecoder, ImageFormat, RgbaImage};
+use image::codecs::gif::GifDecoder;
+use image::codecs::png::PngDecoder;
+use image::codecs::webp::WebPDecoder;
+use image::metadata::Orientation;
+
+use super::Source;
+
+/// Most frames picked from an animation, only these are kept in memory.
+pub const MAX_FRAMES: u16 = 256;
+
+/// An animated image, not decoded yet, and what still images also use when decoding.
+pub(super) struct Animation {
+    pub frames: Frames<'static>,
+    /// Embedded ICC profile, if any
+    pub icc: Option<Vec<u8>>,
+    /// EXIF orientation
+    pub orientation: Orientation,
+}
+
+/// The frames picked by [`read`], each one a full image, with the metadata of the animation.
+pub(super) struct Picked {
+    pub frames: Vec<RgbaImage>,
+    pub icc: Option<Vec<u8>>,
+    pub orientation: Orientation,
+}
+
+/// The frames of the image, if it's animated. Decoded images never are.
+pub(super) fn animation(f: &Source) -> Result<Option<Animation>> {
+    if let Source::Image(_) = f { return Ok(None); }
+    let format = f.image_reader()?.format();
+    let reader = || f.reader();
+
+    // broken metadata shouldn't stop reading the image
+    fn with_meta(mut d: impl ImageDecoder + AnimationDecoder<'static>) -> Animation {
+        let icc = d.icc_profile().ok().flatten();
+        let orientation = d.orientation().unwrap_or(Orientation::NoTransforms);
+        Animation { frames: d.into_frames(), icc, orientation }
+    }
+
+    Ok(match format {
+        Some(ImageFormat::Gif) => Some(with_meta(GifDecoder::new(reader()?)?)),
+        Some(ImageFormat::Png) => {
+            let mut png = PngDecoder::new(reader()?)?;
+            if !png.is_apng()? { return Ok(None); }
+            let icc = png.icc_profile().ok().flatten();
+            let orientation = png.orientation().unwrap_or(Orientation::NoTransforms);
+            Some(Animation { frames: png.apng()?.into_frames(), icc, orientation })
+        },
+        Some(ImageFormat::WebP) => {
+            let webp = WebPDecoder::new(reader()?)?;
+            if webp.has_animation() { Some(with_meta(webp)) } else { None }
+        },
+        _ => None,
+    })
+}
+
+/// Indexes of `n` frames evenly spaced in time, sorted, maybe repeated. `None` if the image isn't
+/// animated.
+pub fn indexes(f: &Source, n: u16) -> Result<Option<Vec<usize>>> {
+    let Some(anim) = animation(f)? else { return Ok(None) };
+
+    let delays = anim.frames
+        .map(|fr| Ok(delay(&fr?)))
+        .collect::<Result<Vec<f64>>>()?;
+
+    Ok(Some(sample(&delays, n)))
+}
+
+/// The frames at [`indexes`], already composited (each one is a full image). Which ones are
+/// picked depends on every delay, so the animation is decoded twice: once for the delays and once
+/// keeping only the picked frames.
+pub(super) fn read(f: &Source, n: u16) -> Result<Option<Picked>> {
+    let Some(idx) = indexes(f, n)? else { return Ok(None) };
+    let Some(anim) = animation(f)? else { return Ok(None) };
+
+    let last = idx.last().copied().unwrap_or_default();
+    let mut frames = Vec::with_capacity(idx.len());
+    let mut picked = idx.iter().peekable();
+
+    for (i, fr) in anim.frames.enumerate().take(last + 1) {
+        let fr = fr?;
+        // earlier frames still have to be decoded, since later ones are composited over them
+        if picked.peek() != Some(&&i) { continue; }
+        let buf = fr.into_buffer();
+        while picked.next_if_eq(&&i).is_some() {
+            frames.push(buf.clone());
+        }
+    }
+
+    Ok(Some(Picked { frames, icc: anim.icc, orientation: anim.orientation }))
+}
+
+/// How long the frame is on screen, in milliseconds
+fn delay(fr: &image::Frame) -> f64 {
+    let (num, den) = fr.delay().numer_denom_ms();
+    f64::from(num) / f64::from(den.max(1))
+}
+
+/// Pick the frame on screen at the middle of each of the `n` equal slices of the loop. Frames
+/// without a delay get the same one, since they can't all be zero.
+fn sample(delays: &[f64], n: u16) -> Vec<usize> {
+    let delays: Vec<f64> = if delays.iter().all(|&d| d <= 0.0) {
+        vec![1.0; delays.len()]
+    } else {
+        delays.iter().map(|&d| d.max(0.0)).collect()
+    };
+
+    let total: f64 = delays.iter().sum();
+    let n = usize::from(n.max(1));
+
+    let mut ret = Vec::with_capacity(n);
+    let mut frame = 0;
+    let mut end = delays.first().copied().unwrap_or_default();
+
+    for k in 0..n {
+        let t = (k as f64 + 0.5) * total / n as f64;
+        while t >= end && frame + 1 < delays.len() {
+            frame += 1;
+            end += delays[frame];
+        }
+        ret.push(frame);
+    }
+
+    ret
+}
@@ -1,13 +1,14 @@
 use crate::backends::*;
 
 /// Read and return the whole image pixels rgb8 array
-pub fn full(f: &Path) -> Result<Vec<u8>> {
-    // Init image, then convert it into rgb and finally to LAB
-    Ok(
-        image::ImageReader::open(f)?
-            .with_guessed_format()?
-            .decode()?
-            .into_rgb8()
-            .into_raw()
-    )
+#[derive(Debug, Clone, Copy)]
+pub struct Full;
+
+impl ImageParser for Full {
+    fn read(&self, f: &Source, l: &Loader) -> Result<Pixels> {
+        // Init image, then convert it into rgb and finally to LAB
+        let (img, crop, frames) = l.load(f)?;
+        let img = img.into_rgba8();
+        l.pixels(&img, img.dimensions(), frames, crop)
+    }
 }
@@ -0,0 +1,42 @@
+//! # ICC
+//! Wallpapers exported in a wide gamut, like Display P3 or Adobe RGB, embed an ICC profile that
+//! says how to read their values. Without it, those values are read as sRGB and hues get skewed,
+//! so the pixels get converted into sRGB before anything else.
+//!
+//! A profile that can't be read, or that isn't for RGB data, is ignored, the image is still usable.
+use image::DynamicImage;
+use image::ColorType;
+use moxcms::{ColorProfile, DataColorSpace, Layout, TransformOptions};
+
+/// Convert the image from the `icc` profile into sRGB, as rgba8 or rgba16 (keeping the
+/// precision for [`super::ToneMap`]). Float images are returned as is, since they are linear
+/// light and are expected to already be in sRGB primaries.
+pub fn to_srgb(img: DynamicImage, icc: &[u8]) -> DynamicImage {
+    let Ok(src) = ColorProfile::new_from_slice(icc) else { return img };
+    if src.color_space != DataColorSpace::Rgb { return img; }
+
+    let dst = ColorProfile::new_srgb();
+    let opts = TransformOptions::default();
+
+    match img.color() {
+        ColorType::Rgb32F | ColorType::Rgba32F => img,
+        ColorType::L16 | ColorType::La16 | ColorType::Rgb16 | ColorType::Rgba16 => {
+            let Ok(t) = src.create_transform_16bit(Layout::Rgba, &dst, Layout::Rgba, opts) else { return img };
+            let src = img.into_rgba16();
+            let mut out = src.clone();
+            match t.transform(&src, &mut out) {
+                Ok(_) => out.into(),
+                Err(_) => src.into(),
+            }
+        },
+        _ => {
+            let Ok(t) = src.create_transform_8bit(Layout::Rgba, &dst, Layout::Rgba, opts) else { return img };
+            let src = img.into_rgba8();
+            let mut out = src.clone();
+            match t.transform(&src, &mut out) {
+                Ok(_) => out.into(),
+                Err(_) => src.into(),
+            }
+        },
+    }
+}
@@ -1,70 +1,99 @@
 use crate::backends::*;
 
 //use std::cmp::Ordering;
-use std::path::Path;
 use palette::{Lab, Srgb, FromColor, IntoColor, cast::{AsComponents, ComponentsAs}};
 use kmeans_colors::{get_kmeans_hamerly, get_kmeans, MapColor};
 
 
-const K: u8 = crate::colorspaces::MIN_COLS;
-
 /// Requires more tweaking and more in depth testing, but seems to do the work.
 /// TODO Investigate what are the better default properties that get the most average and tasteful palette.
 /// `palette` `as_components()` and `components_as()` is very interesting, since it works on primitive types, need more reading.
 /// from: https://github.com/okaneco/kmeans-colors/blob/master/src/bin/kmeans_colors/app.rs
-pub fn kmeans(f: &Path) -> Result<Vec<u8>> {
-    let img = image::ImageReader::open(f)?.with_guessed_format()?.decode()?.into_rgb8();
-    // let img = super::fast_resize::fast_resize(f)?;
-
-    // Get RGB pixels
-    let img_vec: &[Srgb<u8>] = img.components_as();
-
-    // Convert RGB -> Lab for perceptual clustering
-    let pixels: Vec<Lab> = img_vec
-        .iter()
-        .map(|px| px.into_format::<f32>().into_color())
-        .collect();
-
-    //prefer max iter over runs.
-    let max_iter = 300;
-    let converge = 1e-5;
-    //let runs = 1;
-    let verbose = false;
-
-    fastrand::seed(0xBEEF);
-
-    //let mut best_result = Kmeans::new();
-
-    let method = if K > 1 { get_kmeans_hamerly } else { get_kmeans };
-
-    //for _ in 0..runs {
-        let result = method(
-            K.into(),
-            max_iter,
-            converge,
-            verbose,
-            &pixels,
-            fastrand::u64(..),
-        );
-
-    //     println!("score: {}", run_result.score);
-    //
-    //     if best_result.score > run_result.score {
-    //         best_result = run_result;
-    //     }
-    // }
-
-    // Convert Lab → Srgb<f32> → Srgb<u8> (using your preferred method)
-    let centroids: Vec<Srgb<u8>> = result
-        .centroids
-        .iter()
-        .map(|&lab| Srgb::from_color(lab).into_format::<u8>())
-        .collect();
-
-    // map pixels to their nearest centroid
-    let rgb: Vec<Srgb<u8>> = Srgb::map_indices_to_centroids(&centroids, &result.indices);
-
-    Ok(rgb.as_components().to_vec())
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[cfg_attr(feature = "schema" , derive(schemars::JsonSchema))]
+#[serde(default, deny_unknown_fields)]
+pub struct Kmeans {
+    /// Number of clusters
+    pub k: u8,
+    /// Maximum iterations of every run
+    pub max_iter: usize,
+    /// Stop iterating once the centroids move less than this
+    pub converge: f32,
+    /// Runs, each one with a new seed, the one with the best score is used
+    pub runs: u8,
+    /// Seed of the first run, same seed gives the same output
+    pub seed: u64,
+}
+
+impl Default for Kmeans {
+    fn default() -> Self {
+        Self {
+            k: crate::colorspaces::MIN_COLS,
+            //prefer max iter over runs.
+            max_iter: 300,
+            converge: 1e-5,
+            runs: 1,
+            seed: 0xBEEF,
+        }
+    }
+}
+
+impl ImageParser for Kmeans {
+    fn read(&self, f: &Source, l: &Loader) -> Result<Pixels> {
+        let (img, crop, _) = l.load(f)?;
+        let img = l.rgb(&img.into_rgba8())?;
+        // let img = super::fast_resize::fast_resize(f)?;
+
+        // Get RGB pixels
+        let img_vec: &[Srgb<u8>] = img.components_as();
+
+        // Convert RGB -> Lab for perceptual clustering
+        let pixels: Vec<Lab> = img_vec
+            .iter()
+            .map(|px| px.into_format::<f32>().into_color())
+            .collect();
+
+        let verbose = false;
+
+        // the seed of every run comes from this one, not the global one, so nothing else moves it
+        let mut rng = fastrand::Rng::with_seed(self.seed);
+
+        let k = self.k.max(1);
+        let method = if k > 1 { get_kmeans_hamerly } else { get_kmeans };
+
+        let mut best_result = kmeans_colors::Kmeans::new();
+
+        for _ in 0..self.runs.max(1) {
+            let run_result = method(
+                k.into(),
+                self.max_iter,
+                self.converge,
+                verbose,
+                &pixels,
+                rng.u64(..),
+            );
+
+            if run_result.score < best_result.score {
+                best_result = run_result;
+            }
+        }
+
+        // Convert Lab → Srgb<f32> → Srgb<u8> (using your preferred method)
+        let centroids: Vec<Srgb<u8>> = best_result
+            .centroids
+            .iter()
+            .map(|&lab| Srgb::from_color(lab).into_format::<u8>())
+            .collect();
+
+        // map pixels to their nearest centroid
+        let rgb: Vec<Srgb<u8>> = Srgb::map_indices_to_centroids(&centroids, &best_result.indices);
+
+        Ok(Pixels { rgb: rgb.as_components().to_vec(), crop, size: None })
+    }
+
+    fn key(&self) -> String {
+        format!("_k{}_i{}_c{}_r{}_s{}", self.k, self.max_iter, self.converge, self.runs, self.seed)
+    }
 }
 
 
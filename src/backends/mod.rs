@@ -2,10 +2,15 @@
 //! A backend is the **how** to read the image, and get rgb, as a `Vec<u8>`, from that image. This
 //! is, all the colors present in the raw image file (so then it's used to find the most prominent
 //! colors).
-use std::path::Path;
+//!
+//! Every backend is an [`ImageParser`], a value carrying its own parameters, read from the
+//! `[backend]` table of the config file (see [`BackendTable`]). The image can be a file or in
+//! memory, see [`Source`].
 use std::fmt;
 
 use anyhow::Result;
+use image::{DynamicImage, GenericImageView, ImageDecoder};
+use image::metadata::Orientation;
 use serde::{Serialize, Deserialize};
 use owo_colors::AnsiColors;
 
@@ -18,6 +23,56 @@ pub mod wal;
 mod thumb;
 mod fast_resize;
 mod kmeans;
+pub mod octree;
+mod region;
+mod frames;
+mod tonemap;
+mod icc;
+mod borders;
+mod source;
+pub mod auto;
+
+pub use region::{Region, Gravity};
+pub use tonemap::ToneMap;
+pub use frames::MAX_FRAMES;
+pub use source::Source;
+pub use full::Full;
+pub use resized::Resized;
+pub use wal::Wal;
+pub use thumb::Thumb;
+pub use fast_resize::FastResize;
+pub use kmeans::Kmeans;
+pub use octree::Octree;
+pub use auto::Auto;
+
+/// How to read an image and get its rgb8 pixels, implemented by every backend.
+pub trait ImageParser: fmt::Debug {
+    /// Read the image `f`, loaded with `l`, and return the rgb8 pixels, along with the part of the
+    /// image read.
+    fn read(&self, f: &Source, l: &Loader) -> Result<Pixels>;
+
+    /// Only the rgb8 pixels of [`ImageParser::read`].
+    fn parse(&self, f: &Source, l: &Loader) -> Result<Vec<u8>> {
+        Ok(self.read(f, l)?.rgb)
+    }
+
+    /// Parameters that change the output, part of the cache file name. Empty if there are none.
+    fn key(&self) -> String { String::new() }
+}
+
+/// What a backend read from the image, see [`ImageParser::read`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Pixels {
+    /// The rgb8 pixels
+    pub rgb: Vec<u8>,
+    /// Part of the image read, `(x, y, w, h)` in pixels, without the bars and inside the
+    /// [`Region`]. `None` if it's the whole image, see [`Loader::load`].
+    pub crop: Option<(u32, u32, u32, u32)>,
+    /// `(w, h, frames)` when `rgb` are every pixel of a `w`x`h` image, row by row, with `frames`
+    /// stacked on top of each other. `None` when the backend returns new colors, or left some
+    /// pixels out, see [`Loader::pixels`].
+    pub size: Option<(u32, u32, u32)>,
+}
 
 /// This indicates what 'parser' method to use, defined in the config file. Corresponds to the
 /// modules inside this module
@@ -44,18 +99,26 @@ pub enum Backend {
     /// Kmeans is an algo that divides and picks pixels all around the image, giving a more
     /// diverse look.
     Kmeans,
+    /// Native octree quantizer that mimics `wal` (image magick) output, without requiring image
+    /// magick to be installed.
+    Octree,
+    /// Chooses one of `full`, `resized`, `fastresize` or `thumb` by looking at the image size,
+    /// format, bit depth and animation, falling back to the next one if it fails.
+    Auto,
 }
 
 impl Backend {
-    /// match and return the proper backend
-    pub fn main(&self) -> fn(&Path) -> Result<Vec<u8>> {
+    /// match and return the proper backend, with its parameters
+    pub fn parser(&self, p: &Params) -> Box<dyn ImageParser> {
         match &self {
-            B::Full    => full::full,
-            B::Resized => resized::resized,
-            B::Wal     => wal::wal,
-            B::Thumb   => thumb::thumb,
-            B::FastResize => fast_resize::fast_resize,
-            B::Kmeans => kmeans::kmeans,
+            B::Full    => Box::new(Full),
+            B::Resized => Box::new(p.resized),
+            B::Wal     => Box::new(Wal),
+            B::Thumb   => Box::new(p.thumb),
+            B::FastResize => Box::new(p.fastresize),
+            B::Kmeans => Box::new(p.kmeans),
+            B::Octree => Box::new(Octree),
+            B::Auto => Box::new(Auto(*p)),
         }
     }
     /// This assigns a colors for a backend, used when printing
@@ -67,7 +130,323 @@ impl Backend {
             B::Thumb => AnsiColors::Magenta,
             B::FastResize => AnsiColors::Green,
             B::Kmeans => AnsiColors::BrightBlue,
+            B::Octree => AnsiColors::BrightRed,
+            B::Auto => AnsiColors::Yellow,
+        }
+    }
+}
+
+/// Parameters of each backend, the ones without any aren't here.
+/// ```toml
+/// [backend]
+/// name = "kmeans"
+/// kmeans = { k = 8, seed = 42 }
+/// thumb.width = 256
+/// ```
+#[derive(Debug, Default, Clone, Copy, PartialEq, Deserialize)]
+#[cfg_attr(feature = "schema" , derive(schemars::JsonSchema))]
+#[serde(default)]
+pub struct Params {
+    pub resized: Resized,
+    pub thumb: Thumb,
+    #[serde(alias = "fast-resize")]
+    pub fastresize: FastResize,
+    pub kmeans: Kmeans,
+}
+
+/// The `backend` key of the config file, which is either only the name, `backend = "kmeans"`, or
+/// a `[backend]` table with the name and the [`Params`].
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "schema" , derive(schemars::JsonSchema))]
+pub struct BackendTable {
+    /// Which backend to use
+    pub name: Option<Backend>,
+    /// Parameters of every backend, only the ones of `name` are used
+    pub params: Params,
+}
+
+impl<'de> Deserialize<'de> for BackendTable {
+    fn deserialize<D: serde::Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        let (name, params) = crate::config::str_or_table(d, "a backend name or a [backend] table", &["name", "resized", "thumb", "fastresize", "kmeans"])?;
+        Ok(BackendTable { name, params })
+    }
+}
+
+/// Resize filters, from the fastest (and roughest) to the slowest.
+#[derive(Debug, PartialEq, Eq, Deserialize, Serialize, Clone, Copy)]
+#[cfg_attr(feature = "schema" , derive(schemars::JsonSchema))]
+#[serde(rename_all = "lowercase")]
+pub enum ResizeFilter {
+    /// Nearest neighbor
+    Nearest,
+    /// Linear
+    Triangle,
+    /// Cubic
+    CatmullRom,
+    /// Gaussian
+    Gaussian,
+    /// Lanczos with a window of 3
+    Lanczos3,
+}
+
+impl From<ResizeFilter> for image::imageops::FilterType {
+    fn from(f: ResizeFilter) -> Self {
+        use image::imageops::FilterType as F;
+        match f {
+            ResizeFilter::Nearest    => F::Nearest,
+            ResizeFilter::Triangle   => F::Triangle,
+            ResizeFilter::CatmullRom => F::CatmullRom,
+            ResizeFilter::Gaussian   => F::Gaussian,
+            ResizeFilter::Lanczos3   => F::Lanczos3,
+        }
+    }
+}
+
+impl From<ResizeFilter> for fast_image_resize::ResizeAlg {
+    fn from(f: ResizeFilter) -> Self {
+        use fast_image_resize::{ResizeAlg as R, FilterType as F};
+        match f {
+            ResizeFilter::Nearest    => R::Nearest,
+            ResizeFilter::Triangle   => R::Convolution(F::Bilinear),
+            ResizeFilter::CatmullRom => R::Convolution(F::CatmullRom),
+            ResizeFilter::Gaussian   => R::Convolution(F::Gaussian),
+            ResizeFilter::Lanczos3   => R::Convolution(F::Lanczos3),
+        }
+    }
+}
+
+impl fmt::Display for ResizeFilter {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ResizeFilter::Nearest    => write!(f, "nearest"),
+            ResizeFilter::Triangle   => write!(f, "triangle"),
+            ResizeFilter::CatmullRom => write!(f, "catmullrom"),
+            ResizeFilter::Gaussian   => write!(f, "gaussian"),
+            ResizeFilter::Lanczos3   => write!(f, "lanczos3"),
+        }
+    }
+}
+
+/// How every backend reads the image, before looking at the colors. Filled from the config file
+/// and cli flags, see [`crate::config::Config::loader`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Loader {
+    /// Pixels with an alpha (in percentage) below this value are dropped, `0` keeps every pixel.
+    pub alpha_cutoff: u8,
+    /// Only read this part of the image.
+    pub region: Option<Region>,
+    /// How many frames to read from animated images, `1` only reads the first one.
+    pub frames: u16,
+    /// How to bring 16 bit and HDR images down to 8 bits.
+    pub tone_map: ToneMap,
+    /// Convert images with an embedded ICC profile into sRGB.
+    pub icc: bool,
+    /// Detect uniform bars on the sides (letterbox, pillarbox) and leave them out.
+    pub trim_borders: bool,
+}
+
+impl Default for Loader {
+    fn default() -> Self {
+        Self { alpha_cutoff: 0, region: None, frames: 1, tone_map: ToneMap::default(), icc: true, trim_borders: false }
+    }
+}
+
+impl Loader {
+    /// Decode the image, guessing the format, rotate it as the EXIF orientation says, convert it
+    /// to sRGB if it has an ICC profile, trim its borders (if enabled), crop it to the
+    /// [`Region`], if any, and bring high bit depth images down to rgba8 (see [`ToneMap`]).
+    /// Animated images, when reading more than one frame, return every frame (cropped) stacked
+    /// vertically, see [`frames`].
+    pub fn open(&self, f: &Source) -> Result<DynamicImage> {
+        Ok(self.load(f)?.0)
+    }
+
+    /// [`Loader::open`], also returning the part of the image read (of every frame), like
+    /// [`Loader::rect`], without decoding the image twice, and how many frames are stacked.
+    pub fn load(&self, f: &Source) -> Result<(DynamicImage, Option<(u32, u32, u32, u32)>, u32)> {
+        let frames = if self.frames > 1 { frames::read(f, self.frames)? } else { None };
+
+        if let Some(picked) = frames.filter(|p| !p.frames.is_empty()) {
+            // the same steps as a still image below, for every frame
+            let icc = picked.icc.filter(|_| self.icc);
+            let frames: Vec<DynamicImage> = picked.frames
+                .into_iter()
+                .map(|fr| {
+                    let mut img = DynamicImage::from(fr);
+                    img.apply_orientation(picked.orientation);
+                    match &icc {
+                        Some(s) => icc::to_srgb(img, s),
+                        None => img,
+                    }
+                })
+                .collect();
+
+            // the same bars for every frame, so they keep the same size
+            let bars = self.bars(&frames[0].to_rgba8());
+            let rect = self.rect_of(frames[0].dimensions(), bars);
+            let frames: Vec<_> = frames
+                .into_iter()
+                .map(|fr| self.tone_map.apply(self.crop(fr, rect)).into_rgba8())
+                .collect();
+
+            // every frame has the size of the canvas, so they stack fine
+            let (w, h) = frames[0].dimensions();
+            let n = frames.len() as u32;
+            let raw = frames.into_iter().flat_map(|fr| fr.into_raw()).collect();
+            let stacked = image::RgbaImage::from_raw(w, h * n, raw).expect("frames have the same size");
+            return Ok((stacked.into(), rect, n));
+        }
+
+        let reader = match f {
+            Source::Image(img) => {
+                let bars = if self.trim_borders { self.bars(&img.to_rgba8()) } else { None };
+                let rect = self.rect_of(img.dimensions(), bars);
+                return Ok((self.tone_map.apply(self.crop(img.as_ref().clone(), rect)), rect, 1));
+            },
+            _ => f.image_reader()?,
+        };
+        let mut decoder = reader.into_decoder()?;
+
+        // broken metadata shouldn't stop reading the image
+        let icc = if self.icc { decoder.icc_profile().ok().flatten() } else { None };
+        let orientation = decoder.orientation().unwrap_or(Orientation::NoTransforms);
+
+        let mut img = DynamicImage::from_decoder(decoder)?;
+        img.apply_orientation(orientation);
+
+        let img = match icc {
+            Some(s) => icc::to_srgb(img, &s),
+            None => img,
+        };
+
+        let bars = if self.trim_borders { self.bars(&img.to_rgba8()) } else { None };
+        let rect = self.rect_of(img.dimensions(), bars);
+
+        Ok((self.tone_map.apply(self.crop(img, rect)), rect, 1))
+    }
+
+    /// The part of the image without the bars on its sides, `(x, y, w, h)` in pixels, or `None`
+    /// if there aren't any (or [`Loader::trim_borders`] is off). Only the first frame is used.
+    /// Decodes the image, backends get it from [`Loader::load`] instead.
+    pub fn content(&self, f: &Source) -> Result<Option<(u32, u32, u32, u32)>> {
+        if !self.trim_borders { return Ok(None); }
+        if let Source::Image(img) = f { return Ok(self.bars(&img.to_rgba8())); }
+
+        let mut decoder = f.image_reader()?.into_decoder()?;
+        let orientation = decoder.orientation().unwrap_or(Orientation::NoTransforms);
+
+        let mut img = DynamicImage::from_decoder(decoder)?;
+        img.apply_orientation(orientation);
+
+        Ok(self.bars(&img.to_rgba8()))
+    }
+
+    /// Part of the image to read, `(x, y, w, h)` in pixels, the [`Region`] inside the
+    /// [`Loader::content`]. `None` if it's the whole image. Only decodes the image when trimming
+    /// the borders, for backends that don't, like [`Wal`].
+    pub fn rect(&self, f: &Source) -> Result<Option<(u32, u32, u32, u32)>> {
+        let content = self.content(f)?;
+        if content.is_none() && self.region.is_none() { return Ok(None); }
+
+        Ok(self.rect_of(self.dimensions(f)?, content))
+    }
+
+    /// [`Loader::rect`] of an image of `size`, with these `bars`.
+    fn rect_of(&self, size: (u32, u32), bars: Option<(u32, u32, u32, u32)>) -> Option<(u32, u32, u32, u32)> {
+        if bars.is_none() && self.region.is_none() { return None; }
+
+        let (x, y, w, h) = bars.unwrap_or((0, 0, size.0, size.1));
+
+        Some(match self.region {
+            Some(r) => {
+                let (rx, ry, rw, rh) = r.pixels(w, h);
+                (x + rx, y + ry, rw, rh)
+            },
+            None => (x, y, w, h),
+        })
+    }
+
+    /// [`borders::content`] of the image, if trimming
+    fn bars(&self, img: &image::RgbaImage) -> Option<(u32, u32, u32, u32)> {
+        if !self.trim_borders { return None; }
+        borders::content(img, |a| self.is_visible(a))
+    }
+
+    /// Size of the image as shown, after the EXIF orientation, without decoding it.
+    pub fn dimensions(&self, f: &Source) -> Result<(u32, u32)> {
+        if let Source::Image(img) = f { return Ok(img.dimensions()); }
+
+        let mut decoder = f.image_reader()?.into_decoder()?;
+
+        let (w, h) = decoder.dimensions();
+        Ok(match decoder.orientation().unwrap_or(Orientation::NoTransforms) {
+            Orientation::Rotate90 | Orientation::Rotate270
+            | Orientation::Rotate90FlipH | Orientation::Rotate270FlipH => (h, w),
+            _ => (w, h),
+        })
+    }
+
+    /// Crop to the `rect`, see [`Loader::rect`].
+    fn crop(&self, img: DynamicImage, rect: Option<(u32, u32, u32, u32)>) -> DynamicImage {
+        match rect {
+            Some((x, y, w, h)) => img.crop_imm(x, y, w, h),
+            None => img,
+        }
+    }
+
+    /// Turn rgba8 pixels into the rgb8 bytes backends return, ignoring the (mostly) transparent
+    /// ones, otherwise they count as black (the usual color under the alpha channel).
+    pub fn rgb(&self, rgba: &[u8]) -> Result<Vec<u8>> {
+        let rgb: Vec<u8> = rgba
+            .chunks_exact(4)
+            .filter(|p| self.is_visible(p[3]))
+            .flat_map(|p| &p[..3])
+            .copied()
+            .collect();
+
+        if rgb.is_empty() {
+            anyhow::bail!("Every pixel of the image is transparent (below the {}% alpha cutoff).", self.alpha_cutoff);
+        }
+
+        Ok(rgb)
+    }
+
+    /// [`Loader::rgb`] of a `w`x`h` image with `frames`, keeping its size when no pixel was left
+    /// out, so they can be weighted by where they are, see [`Pixels::size`].
+    pub fn pixels(&self, rgba: &[u8], (w, h): (u32, u32), frames: u32, crop: Option<(u32, u32, u32, u32)>) -> Result<Pixels> {
+        let rgb = self.rgb(rgba)?;
+        let size = (rgb.len() == w as usize * h as usize * 3).then_some((w, h, frames));
+        Ok(Pixels { rgb, crop, size })
+    }
+
+    /// Whether an alpha value (0..=255) passes the cutoff.
+    pub fn is_visible(&self, alpha: u8) -> bool {
+        u16::from(alpha) * 100 >= u16::from(self.alpha_cutoff) * 255
+    }
+}
+
+/// Used as part of the cache path, since it changes the backend output.
+impl fmt::Display for Loader {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "a{}", self.alpha_cutoff)?;
+        // fractions, so both notations of the same region share the cache (and no `:` in paths)
+        if let Some(r) = self.region {
+            let (x, y, w, h) = r.fractions();
+            write!(f, "_r{x}_{y}_{w}_{h}")?;
+        }
+        if self.frames > 1 {
+            write!(f, "_f{}", self.frames)?;
+        }
+        if self.tone_map != ToneMap::default() {
+            write!(f, "_{}", self.tone_map)?;
+        }
+        if !self.icc {
+            write!(f, "_noicc")?;
+        }
+        if self.trim_borders {
+            write!(f, "_trim")?;
         }
+        Ok(())
     }
 }
 
@@ -81,6 +460,8 @@ impl fmt::Display for Backend {
             B::Thumb   => write!(f, "Thumb"),
             B::FastResize => write!(f, "FastResize"),
             B::Kmeans => write!(f, "Kmeans"),
+            B::Octree => write!(f, "Octree"),
+            B::Auto => write!(f, "Auto"),
         }
     }
 }
@@ -0,0 +1,302 @@
+//! # Octree
+//! Native reimplementation of what the [`wal`](super::wal) backend asks image magick to do, so it
+//! works without having `magick` or `convert` installed:
+//! ```txt
+//!   magick image.png -resize 25% -colors N -unique-colors txt:-
+//! ```
+//! * `-resize` uses the same filter IM prefers when downscaling (Lanczos).
+//! * `-colors` is IM's adaptive spatial subdivision, an octree: classification, reduction and
+//!   assignment, following `MagickCore/quantize.c` (Q16, no alpha).
+//! * Instead of dithering and then looking up the unique colors of the dithered image, the whole
+//!   colormap is returned, since with the default Riemersma dither every entry ends up being used.
+//! * `-unique-colors` prints colors by walking IM's histogram tree, which is the same as sorting
+//!   them by interleaving the bits of blue, green and red (most significant first).
+//!
+//! Refs:
+//! - <https://imagemagick.org/script/quantize.php>
+//! - <https://github.com/ImageMagick/ImageMagick/blob/main/MagickCore/quantize.c>
+use crate::backends::*;
+use image::GenericImageView;
+use image::imageops::FilterType;
+
+/// Maximum depth of the color tree (8 bits per channel)
+const MAX_TREE_DEPTH: usize = 8;
+
+/// Default IM builds use 16 bits per channel
+const QUANTUM_RANGE: f64 = 65535.0;
+const QUANTUM_SCALE: f64 = 1.0 / QUANTUM_RANGE;
+
+/// Index of the root node in [`Cube::nodes`]
+const ROOT: usize = 0;
+
+/// Least colors wanted, the first `-colors N` tried, like pywal
+pub const COLORS: usize = 16;
+
+/// How many times `N` is raised by one when there are less than [`COLORS`], so there are never
+/// more than `COLORS + RETRIES - 1` colors
+pub const RETRIES: usize = 20;
+
+/// Same loop as [`Wal`](super::Wal), but without spawning image magick.
+#[derive(Debug, Clone, Copy)]
+pub struct Octree;
+
+impl ImageParser for Octree {
+    fn read(&self, f: &Source, l: &Loader) -> Result<Pixels> {
+        let (img, crop, _) = l.load(f)?;
+
+        // `-resize 25%`
+        let (w, h) = img.dimensions();
+        let quarter = |x: u32| ((f64::from(x) * 0.25).round() as u32).max(1);
+        let (w, h) = (quarter(w), quarter(h));
+
+        // transparent pixels are dropped, so rows can end up shorter than `w`, which only matters for
+        // how soon the tree gets pruned.
+        let pixels: Vec<[u8; 3]> = l
+            .rgb(&img.resize_exact(w, h, FilterType::Lanczos3).into_rgba8())?
+            .chunks_exact(3)
+            .map(|p| [p[0], p[1], p[2]])
+            .collect();
+
+        for i in 0..RETRIES {
+            let cols = quantize(&pixels, w as usize, COLORS + i);
+
+            if cols.len() >= COLORS {
+                return Ok(Pixels { rgb: cols.into_iter().flatten().collect(), crop, size: None });
+            }
+        }
+
+        anyhow::bail!("Octree couldn't generate a suitable palette.");
+    }
+}
+
+/// `-colors max_colors -unique-colors`, pixels are rgb8 in row-major order.
+fn quantize(pixels: &[[u8; 3]], width: usize, max_colors: usize) -> Vec<[u8; 3]> {
+    let gray = pixels.iter().all(|p| p[0] == p[1] && p[1] == p[2]);
+
+    let depth = if gray {
+        MAX_TREE_DEPTH
+    } else {
+        // Depth of color tree is: Log4(colormap size)+2, minus one since IM dithers by default.
+        let mut colors = max_colors;
+        let mut depth = 1;
+        while colors != 0 {
+            colors >>= 2;
+            depth += 1;
+        }
+        if depth > 2 { depth - 1 } else { depth }
+    };
+
+    let mut cube = Cube::new(max_colors, depth);
+    cube.classify(pixels, width.max(1));
+    cube.reduce();
+
+    let mut cols = vec![];
+    cube.colormap(ROOT, &mut cols);
+
+    // `-unique-colors` order
+    cols.sort_by_key(histogram_key);
+    cols.dedup();
+    cols
+}
+
+/// Which child a color belongs to at a given bit `index`
+fn node_id(c: [u8; 3], index: usize) -> usize {
+    usize::from((c[0] >> index) & 1)
+        | usize::from((c[1] >> index) & 1) << 1
+        | usize::from((c[2] >> index) & 1) << 2
+}
+
+/// Position of a color when traversing IM's histogram tree.
+fn histogram_key(c: &[u8; 3]) -> u32 {
+    (0..MAX_TREE_DEPTH).rev().fold(0, |key, i| key << 3 | node_id(*c, i) as u32)
+}
+
+#[derive(Debug, Default, Clone)]
+struct Node {
+    parent: usize,
+    child: [Option<usize>; 8],
+    id: usize,
+    level: usize,
+    /// number of pixels that ended up in this node
+    number_unique: f64,
+    /// sum of the colors (scaled to 0..=1) of those pixels
+    total: [f64; 3],
+    quantize_error: f64,
+}
+
+/// IM's `CubeInfo`, nodes are never freed, only unlinked from their parent.
+struct Cube {
+    nodes: Vec<Node>,
+    /// linked nodes (`cube_info->nodes`)
+    live: usize,
+    colors: usize,
+    max_colors: usize,
+    depth: usize,
+    pruning_threshold: f64,
+    next_threshold: f64,
+}
+
+impl Cube {
+    fn new(max_colors: usize, depth: usize) -> Self {
+        Self {
+            nodes: vec![Node::default()],
+            live: 1,
+            colors: 0,
+            max_colors,
+            depth,
+            pruning_threshold: 0.0,
+            next_threshold: 0.0,
+        }
+    }
+
+    fn new_node(&mut self, parent: usize, id: usize, level: usize) -> usize {
+        let idx = self.nodes.len();
+        self.nodes.push(Node { parent, id, level, ..Node::default() });
+        self.nodes[parent].child[id] = Some(idx);
+        self.live += 1;
+        idx
+    }
+
+    /// The first colors are classified to the full depth, once there are more than `max_colors`
+    /// the tree is pruned to the cube depth and the remaining rows use that one.
+    fn classify(&mut self, pixels: &[[u8; 3]], width: usize) {
+        let mut depth = MAX_TREE_DEPTH;
+
+        for row in pixels.chunks(width) {
+            let mut x = 0;
+            while x < row.len() {
+                // runs of the same color are added at once
+                let mut count = 1;
+                while x + count < row.len() && row[x + count] == row[x] {
+                    count += 1;
+                }
+                self.add(row[x], count as f64, depth);
+                x += count;
+            }
+
+            if depth == MAX_TREE_DEPTH && depth != self.depth && self.colors > self.max_colors {
+                self.prune_to_cube_depth(ROOT);
+                depth = self.depth;
+            }
+        }
+    }
+
+    fn add(&mut self, pixel: [u8; 3], count: f64, depth: usize) {
+        let p = pixel.map(|c| f64::from(c) * 257.0);
+        let mut bisect = (QUANTUM_RANGE + 1.0) / 2.0;
+        let mut mid = [bisect; 3];
+        let mut node = ROOT;
+
+        for level in 1..=depth {
+            bisect *= 0.5;
+            let id = node_id(pixel, MAX_TREE_DEPTH - level);
+            for (ch, m) in mid.iter_mut().enumerate() {
+                *m += if (id >> ch) & 1 != 0 { bisect } else { -bisect };
+            }
+
+            node = match self.nodes[node].child[id] {
+                Some(s) => s,
+                None => {
+                    if level == depth { self.colors += 1; }
+                    self.new_node(node, id, level)
+                },
+            };
+
+            // Approximate the quantization error represented by this node.
+            let distance: f64 = (0..3)
+                .map(|ch| (QUANTUM_SCALE * (p[ch] - mid[ch])).powi(2))
+                .sum();
+            self.nodes[node].quantize_error += count * distance.sqrt();
+            self.nodes[ROOT].quantize_error += self.nodes[node].quantize_error;
+        }
+
+        // Sum RGB for this leaf for later derivation of the mean cube color.
+        let leaf = &mut self.nodes[node];
+        leaf.number_unique += count;
+        for (t, c) in leaf.total.iter_mut().zip(p) {
+            *t += count * QUANTUM_SCALE * c;
+        }
+    }
+
+    /// Merge the node (and its children) statistics into its parent.
+    fn prune_child(&mut self, node: usize) {
+        for c in self.nodes[node].child.into_iter().flatten() {
+            self.prune_child(c);
+        }
+
+        let Node { parent, id, number_unique, total, .. } = self.nodes[node].clone();
+        let p = &mut self.nodes[parent];
+        p.number_unique += number_unique;
+        for (a, b) in p.total.iter_mut().zip(total) {
+            *a += b;
+        }
+        p.child[id] = None;
+        self.live -= 1;
+    }
+
+    fn prune_to_cube_depth(&mut self, node: usize) {
+        for c in self.nodes[node].child.into_iter().flatten() {
+            self.prune_to_cube_depth(c);
+        }
+        if self.nodes[node].level > self.depth {
+            self.prune_child(node);
+        }
+    }
+
+    fn flatten_errors(&self, node: usize, errors: &mut Vec<f64>) {
+        errors.push(self.nodes[node].quantize_error);
+        for c in self.nodes[node].child.into_iter().flatten() {
+            self.flatten_errors(c, errors);
+        }
+    }
+
+    /// Prune the nodes with the smallest quantization error until there are `max_colors`.
+    fn reduce(&mut self) {
+        self.next_threshold = 0.0;
+
+        if self.colors > self.max_colors {
+            // Enable rapid reduction of the number of unique colors.
+            let mut errors = Vec::with_capacity(self.live);
+            self.flatten_errors(ROOT, &mut errors);
+            errors.sort_by(f64::total_cmp);
+
+            let keep = 110 * (self.max_colors + 1) / 100;
+            if errors.len() > keep {
+                self.next_threshold = errors[errors.len() - keep];
+            }
+        }
+
+        while self.colors > self.max_colors {
+            self.pruning_threshold = self.next_threshold;
+            self.next_threshold = self.nodes[ROOT].quantize_error - 1.0;
+            self.colors = 0;
+            self.reduce_node(ROOT);
+        }
+    }
+
+    fn reduce_node(&mut self, node: usize) {
+        for c in self.nodes[node].child.into_iter().flatten() {
+            self.reduce_node(c);
+        }
+
+        let n = &self.nodes[node];
+        if node != ROOT && n.quantize_error <= self.pruning_threshold {
+            self.prune_child(node);
+        } else {
+            if n.number_unique > 0.0 { self.colors += 1; }
+            if n.quantize_error < self.next_threshold { self.next_threshold = n.quantize_error; }
+        }
+    }
+
+    /// Mean color of every node that holds pixels.
+    fn colormap(&self, node: usize, cols: &mut Vec<[u8; 3]>) {
+        for c in self.nodes[node].child.into_iter().flatten() {
+            self.colormap(c, cols);
+        }
+
+        let n = &self.nodes[node];
+        if n.number_unique != 0.0 {
+            cols.push(n.total.map(|t| (t / n.number_unique * 255.0).round().clamp(0.0, 255.0) as u8));
+        }
+    }
+}
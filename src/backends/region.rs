@@ -0,0 +1,174 @@
+//! # Region
+//! Part of the image to read, useful for ultrawide wallpapers where only some part is visible, or
+//! to only match the colors under a bar. Two notations are accepted, both in fractions of the
+//! image size (`0.0` to `1.0`):
+//! * Rectangle, `x,y,w,h`: `0.5,0,0.5,1` is the right half.
+//! * Gravity and size, like image magick `-gravity`, `gravity:WxH`: `north:1x0.05` is a thin strip
+//!   at the top, `center:0.5x0.5` the middle of the image.
+use std::fmt;
+use std::str::FromStr;
+
+use anyhow::Result;
+use serde::Deserialize;
+
+/// Where a [`Region`] with only a size is placed inside the image
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Gravity {
+    NorthWest,
+    North,
+    NorthEast,
+    West,
+    Center,
+    East,
+    SouthWest,
+    South,
+    SouthEast,
+}
+
+/// A rectangle of the image, see the module docs.
+#[derive(Debug, PartialEq, Clone, Copy, Deserialize)]
+#[serde(try_from = "String")]
+pub enum Region {
+    Rect { x: f32, y: f32, w: f32, h: f32 },
+    Gravity { gravity: Gravity, w: f32, h: f32 },
+}
+
+impl Gravity {
+    /// Offset of the size inside the image, `0.0` start, `0.5` middle, `1.0` end
+    fn align(&self) -> (f32, f32) {
+        match self {
+            Gravity::NorthWest => (0.0, 0.0),
+            Gravity::North     => (0.5, 0.0),
+            Gravity::NorthEast => (1.0, 0.0),
+            Gravity::West      => (0.0, 0.5),
+            Gravity::Center    => (0.5, 0.5),
+            Gravity::East      => (1.0, 0.5),
+            Gravity::SouthWest => (0.0, 1.0),
+            Gravity::South     => (0.5, 1.0),
+            Gravity::SouthEast => (1.0, 1.0),
+        }
+    }
+}
+
+impl Region {
+    /// `(x, y, w, h)` as fractions of the image size, resolving the gravity.
+    pub fn fractions(&self) -> (f32, f32, f32, f32) {
+        match *self {
+            Region::Rect { x, y, w, h } => (x, y, w, h),
+            Region::Gravity { gravity, w, h } => {
+                let (ax, ay) = gravity.align();
+                ((1.0 - w) * ax, (1.0 - h) * ay, w, h)
+            },
+        }
+    }
+
+    /// `(x, y, w, h)` in pixels of an image of `width` x `height`, at least one pixel wide.
+    pub fn pixels(&self, width: u32, height: u32) -> (u32, u32, u32, u32) {
+        let (x, y, w, h) = self.fractions();
+
+        let axis = |start: f32, size: f32, total: u32| {
+            let total = total.max(1);
+            let start = ((start * total as f32).round() as u32).min(total - 1);
+            let size = ((size * total as f32).round() as u32).clamp(1, total - start);
+            (start, size)
+        };
+
+        let (x, w) = axis(x, w, width);
+        let (y, h) = axis(y, h, height);
+        (x, y, w, h)
+    }
+}
+
+impl FromStr for Gravity {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Ok(match s.to_lowercase().as_str() {
+            "northwest" => Gravity::NorthWest,
+            "north"     => Gravity::North,
+            "northeast" => Gravity::NorthEast,
+            "west"      => Gravity::West,
+            "center"    => Gravity::Center,
+            "east"      => Gravity::East,
+            "southwest" => Gravity::SouthWest,
+            "south"     => Gravity::South,
+            "southeast" => Gravity::SouthEast,
+            _ => anyhow::bail!("Unknown gravity '{s}', expected one of: northwest, north, northeast, west, center, east, southwest, south, southeast."),
+        })
+    }
+}
+
+impl FromStr for Region {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let frac = |v: &str| -> Result<f32> {
+            let v: f32 = v.trim().parse().map_err(|_| anyhow::anyhow!("'{v}' is not a number."))?;
+            if !(0.0..=1.0).contains(&v) {
+                anyhow::bail!("'{v}' should be a fraction of the image, between 0 and 1.");
+            }
+            Ok(v)
+        };
+
+        let r = match s.split_once(':') {
+            Some((gravity, size)) => {
+                let Some((w, h)) = size.split_once('x') else {
+                    anyhow::bail!("Region size should look like WxH, e.g. `center:0.5x0.5`");
+                };
+                Region::Gravity { gravity: gravity.trim().parse()?, w: frac(w)?, h: frac(h)? }
+            },
+            None => {
+                let v = s.split(',').map(frac).collect::<Result<Vec<f32>>>()?;
+                let [x, y, w, h] = v[..] else {
+                    anyhow::bail!("Region should be either `x,y,w,h` or `gravity:WxH`, e.g. `0.5,0,0.5,1`");
+                };
+                Region::Rect { x, y, w, h }
+            },
+        };
+
+        let (x, y, w, h) = r.fractions();
+        if w == 0.0 || h == 0.0 {
+            anyhow::bail!("Region '{s}' is empty.");
+        }
+        if x + w > 1.0 + f32::EPSILON || y + h > 1.0 + f32::EPSILON {
+            anyhow::bail!("Region '{s}' goes outside the image.");
+        }
+
+        Ok(r)
+    }
+}
+
+impl TryFrom<String> for Region {
+    type Error = anyhow::Error;
+
+    fn try_from(s: String) -> Result<Self> {
+        s.parse()
+    }
+}
+
+impl fmt::Display for Gravity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = match self {
+            Gravity::NorthWest => "northwest",
+            Gravity::North     => "north",
+            Gravity::NorthEast => "northeast",
+            Gravity::West      => "west",
+            Gravity::Center    => "center",
+            Gravity::East      => "east",
+            Gravity::SouthWest => "southwest",
+            Gravity::South     => "south",
+            Gravity::SouthEast => "southeast",
+        };
+        write!(f, "{s}")
+    }
+}
+
+/// Same notation it was written with
+impl fmt::Display for Region {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Region::Rect { x, y, w, h } => write!(f, "{x},{y},{w},{h}"),
+            Region::Gravity { gravity, w, h } => write!(f, "{gravity}:{w}x{h}"),
+        }
+    }
+}
@@ -3,30 +3,54 @@ use image::GenericImageView;
 use crate::backends::*;
 
 /// Resize it, then get read the image
-pub fn resized(f: &Path) -> Result<Vec<u8>> {
-    let img = image::ImageReader::open(f)?
-        .with_guessed_format()?
-        .decode()?;
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[cfg_attr(feature = "schema" , derive(schemars::JsonSchema))]
+#[serde(default, deny_unknown_fields)]
+pub struct Resized {
+    /// Images with a side of at least this many pixels get resized
+    pub threshold: u32,
+    /// Divide both sides by this value
+    pub shrink: u32,
+    /// Which filter to resize with
+    pub filter: ResizeFilter,
+}
+
+impl Default for Resized {
+    fn default() -> Self {
+        Self { threshold: 1024, shrink: 2, filter: ResizeFilter::Gaussian }
+    }
+}
+
+impl ImageParser for Resized {
+    fn read(&self, f: &Source, l: &Loader) -> Result<Pixels> {
+        let (img, crop, frames) = l.load(f)?;
 
-    let (true_w, true_h) = img.dimensions();
+        let (true_w, true_h) = img.dimensions();
 
-    let (w, h) = shrink(true_w, true_h);
+        let (w, h) = self.shrink(true_w, true_h);
 
-    Ok(
-        img
-        .resize(w, h, image::imageops::Gaussian)
-        .into_rgb8()
-        .into_raw()
-    )
+        let img = img
+            .resize(w, h, self.filter.into())
+            .into_rgba8();
+
+        l.pixels(&img, img.dimensions(), frames, crop)
+    }
+
+    fn key(&self) -> String {
+        format!("_t{}_s{}_{}", self.threshold, self.shrink, self.filter)
+    }
 }
 
-/// Calculates the new resized sizes, **keeping the aspect ratio**.
-fn shrink(w: u32, h: u32) -> (u32, u32) {
-    let resized = w >= 1024 || h >= 1024;
+impl Resized {
+    /// Calculates the new resized sizes, **keeping the aspect ratio**.
+    fn shrink(&self, w: u32, h: u32) -> (u32, u32) {
+        let resized = w >= self.threshold || h >= self.threshold;
+        let s = self.shrink.max(1);
 
-    if resized {
-        (w / 2, h / 2)
-    } else {
-        (w, h)
+        if resized {
+            ((w / s).max(1), (h / s).max(1))
+        } else {
+            (w, h)
+        }
     }
 }
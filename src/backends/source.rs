@@ -0,0 +1,136 @@
+//! # Source
+//! Where the image is read from. Usually a file, but it can also be in memory: the bytes piped
+//! through stdin (`grim - | wallust run -`), or an image a program using wallust as a library
+//! already has, encoded or decoded, so there is no need for a temporary file.
+use std::fmt;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Cursor, Read, Seek};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use anyhow::Result;
+use image::DynamicImage;
+
+/// The image to read, cheap to clone.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Source {
+    /// An image file
+    Path(PathBuf),
+    /// An image file already read, see [`Source::read`]
+    File(PathBuf, Arc<[u8]>),
+    /// The contents of an image file, in any format that can be guessed
+    Bytes(Arc<[u8]>),
+    /// An already decoded image, read as is, since it doesn't have an EXIF orientation nor an
+    /// ICC profile (and can't be animated)
+    Image(Arc<DynamicImage>),
+}
+
+/// A reader of the encoded image, see [`Source::reader`].
+pub trait BufReadSeek: BufRead + Seek {}
+impl<T: BufRead + Seek> BufReadSeek for T {}
+
+impl Source {
+    /// Read the whole image from stdin.
+    pub fn stdin() -> Result<Self> {
+        let mut buf = vec![];
+        std::io::stdin().lock().read_to_end(&mut buf)?;
+        if buf.is_empty() { anyhow::bail!("Nothing was read from stdin."); }
+        Ok(Self::Bytes(buf.into()))
+    }
+
+    /// Read a file into memory, so hashing it for the cache and decoding it (maybe with more than
+    /// one backend) don't read it again. Images already in memory are kept as they are.
+    pub fn read(&self) -> Result<Self> {
+        Ok(match self {
+            Source::Path(p) => Source::File(p.clone(), std::fs::read(p)?.into()),
+            s => s.clone(),
+        })
+    }
+
+    /// The path of the image, if it's a file.
+    pub fn path(&self) -> Option<&Path> {
+        match self {
+            Source::Path(p) | Source::File(p, _) => Some(p),
+            _ => None,
+        }
+    }
+
+    /// A reader of the encoded image, fails with [`Source::Image`].
+    pub fn reader(&self) -> Result<Box<dyn BufReadSeek>> {
+        Ok(match self {
+            Source::Path(p) => Box::new(BufReader::new(File::open(p)?)),
+            Source::File(_, b) | Source::Bytes(b) => Box::new(Cursor::new(Arc::clone(b))),
+            Source::Image(_) => anyhow::bail!("The image is already decoded."),
+        })
+    }
+
+    /// [`image::ImageReader`] of the encoded image, with its format guessed.
+    pub fn image_reader(&self) -> Result<image::ImageReader<Box<dyn BufReadSeek>>> {
+        Ok(image::ImageReader::new(self.reader()?).with_guessed_format()?)
+    }
+
+    /// The encoded image, as PNG for a [`Source::Image`], for programs that read a file (or stdin).
+    pub fn encoded(&self) -> Result<std::borrow::Cow<'_, [u8]>> {
+        use std::borrow::Cow;
+        Ok(match self {
+            Source::Path(p) => Cow::Owned(std::fs::read(p)?),
+            Source::File(_, b) | Source::Bytes(b) => Cow::Borrowed(b),
+            Source::Image(img) => {
+                // PNG doesn't do floats
+                let img = match img.color() {
+                    image::ColorType::Rgb32F | image::ColorType::Rgba32F => Cow::Owned(img.to_rgba16().into()),
+                    _ => Cow::Borrowed(img.as_ref()),
+                };
+                let mut buf = Cursor::new(vec![]);
+                img.write_to(&mut buf, image::ImageFormat::Png)?;
+                Cow::Owned(buf.into_inner())
+            },
+        })
+    }
+}
+
+impl From<PathBuf> for Source {
+    fn from(p: PathBuf) -> Self {
+        Self::Path(p)
+    }
+}
+
+impl From<&Path> for Source {
+    fn from(p: &Path) -> Self {
+        Self::Path(p.to_path_buf())
+    }
+}
+
+impl From<&PathBuf> for Source {
+    fn from(p: &PathBuf) -> Self {
+        Self::Path(p.clone())
+    }
+}
+
+impl From<Vec<u8>> for Source {
+    fn from(b: Vec<u8>) -> Self {
+        Self::Bytes(b.into())
+    }
+}
+
+impl From<&[u8]> for Source {
+    fn from(b: &[u8]) -> Self {
+        Self::Bytes(b.into())
+    }
+}
+
+impl From<DynamicImage> for Source {
+    fn from(img: DynamicImage) -> Self {
+        Self::Image(Arc::new(img))
+    }
+}
+
+/// The path, or `-` when it's in memory (like stdin)
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Source::Path(p) | Source::File(p, _) => write!(f, "{}", p.display()),
+            Source::Bytes(_) | Source::Image(_) => write!(f, "-"),
+        }
+    }
+}
@@ -1,11 +1,38 @@
 use crate::backends::*;
 
-/// faster algo than the `resized` module. Hardcoded to 512x512, ignores aspect ratio
-pub fn thumb(f: &Path) -> Result<Vec<u8>> {
-    let img = image::ImageReader::open(f)?
-        .with_guessed_format()?
-        .decode()?
-        .thumbnail(512, 512);
-    Ok(img.into_rgb8().into_raw())
+/// faster algo than the `resized` module. Fits the image in `width` x `height` (512x512 by default)
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[cfg_attr(feature = "schema" , derive(schemars::JsonSchema))]
+#[serde(default, deny_unknown_fields)]
+pub struct Thumb {
+    pub width: u32,
+    pub height: u32,
+    /// Resize with this filter instead of the (faster) thumbnail sampling
+    pub filter: Option<ResizeFilter>,
 }
 
+impl Default for Thumb {
+    fn default() -> Self {
+        Self { width: 512, height: 512, filter: None }
+    }
+}
+
+impl ImageParser for Thumb {
+    fn read(&self, f: &Source, l: &Loader) -> Result<Pixels> {
+        let (img, crop, frames) = l.load(f)?;
+        let (w, h) = (self.width.max(1), self.height.max(1));
+        let img = match self.filter {
+            Some(s) => img.resize(w, h, s.into()),
+            None => img.thumbnail(w, h),
+        };
+        let img = img.into_rgba8();
+        l.pixels(&img, img.dimensions(), frames, crop)
+    }
+
+    fn key(&self) -> String {
+        match self.filter {
+            Some(s) => format!("_{}x{}_{s}", self.width, self.height),
+            None => format!("_{}x{}", self.width, self.height),
+        }
+    }
+}
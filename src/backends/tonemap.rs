@@ -0,0 +1,98 @@
+//! # Tone mapping
+//! 16 bit (PNG, TIFF) and float (HDR, EXR) images keep their precision until here, where the
+//! pixels are mapped into the 8 bit sRGB range the rest of the pipeline uses. Float images are
+//! linear light and can go way above `1.0`, simply clipping them burns the highlights, so an
+//! operator compresses them instead. 16 bit images are already display referred (gamma encoded),
+//! white is white, so they are only rescaled to 8 bits, whatever the operator.
+//!
+//! Refs:
+//! - <https://64.github.io/tonemapping/>
+//! - <https://knarkowicz.wordpress.com/2016/01/06/aces-filmic-tone-mapping-curve/>
+use std::fmt;
+
+use image::{ColorType, DynamicImage, RgbaImage};
+use palette::{LinSrgb, Srgb};
+use serde::{Serialize, Deserialize};
+
+/// How to map HDR pixels into 8 bits
+#[derive(Debug, PartialEq, Eq, Deserialize, Serialize, Clone, Copy, Default, clap::ValueEnum)]
+#[cfg_attr(feature = "doc" , derive(documented::Documented, documented::DocumentedFields))]
+#[cfg_attr(feature = "iter", derive(strum::EnumIter))]
+#[cfg_attr(feature = "schema" , derive(schemars::JsonSchema))]
+#[serde(rename_all = "lowercase")]
+pub enum ToneMap {
+    /// Clip everything above white, same as viewers without HDR support
+    #[default]
+    Clip,
+    /// Reinhard on the luminance, keeps the hue while softly compressing the highlights
+    Reinhard,
+    /// ACES filmic curve (Narkowicz fit), punchier contrast with desaturated highlights
+    Aces,
+    /// Uncharted 2 filmic curve (John Hable), soft shoulder with a white point of 11.2
+    Hable,
+}
+
+impl ToneMap {
+    /// Turn 16 bit and float images into rgba8, any other image is returned as is. Only float
+    /// images are tone mapped, 16 bit ones are just rescaled.
+    pub fn apply(&self, img: DynamicImage) -> DynamicImage {
+        match img.color() {
+            // hdr and exr store linear light
+            ColorType::Rgb32F | ColorType::Rgba32F => (),
+            ColorType::L16 | ColorType::La16 | ColorType::Rgb16 | ColorType::Rgba16 => return img.into_rgba8().into(),
+            _ => return img,
+        }
+
+        let buf = img.into_rgba32f();
+        let (w, h) = buf.dimensions();
+
+        let raw = buf
+            .pixels()
+            .flat_map(|p| {
+                let [r, g, b, a] = p.0.map(|c| if c.is_nan() { 0.0 } else { c.max(0.0) });
+                let (r, g, b) = Srgb::from_linear(self.map(LinSrgb::new(r, g, b))).into_components();
+                [r, g, b, a].map(|c| (c.clamp(0.0, 1.0) * 255.0).round() as u8)
+            })
+            .collect();
+
+        RgbaImage::from_raw(w, h, raw).expect("same size as the source").into()
+    }
+
+    /// Map a linear color into `0.0..=1.0`
+    fn map(&self, c: LinSrgb) -> LinSrgb {
+        let c = match self {
+            ToneMap::Clip => c,
+            ToneMap::Reinhard => {
+                let l = 0.2126 * c.red + 0.7152 * c.green + 0.0722 * c.blue;
+                if l <= 0.0 { c } else { c * (1.0 / (1.0 + l)) }
+            },
+            ToneMap::Aces => {
+                // the fit expects the exposure adjusted by 0.6
+                let f = |x: f32| { let x = x * 0.6; (x * (2.51 * x + 0.03)) / (x * (2.43 * x + 0.59) + 0.14) };
+                LinSrgb::new(f(c.red), f(c.green), f(c.blue))
+            },
+            ToneMap::Hable => {
+                let f = |x: f32| {
+                    let (a, b, c, d, e, f) = (0.15, 0.50, 0.10, 0.20, 0.02, 0.30);
+                    ((x * (a * x + c * b) + d * e) / (x * (a * x + b) + d * f)) - e / f
+                };
+                // exposure bias of 2, normalized to the white point
+                let white = f(11.2);
+                LinSrgb::new(f(2.0 * c.red) / white, f(2.0 * c.green) / white, f(2.0 * c.blue) / white)
+            },
+        };
+
+        LinSrgb::new(c.red.min(1.0), c.green.min(1.0), c.blue.min(1.0))
+    }
+}
+
+impl fmt::Display for ToneMap {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ToneMap::Clip     => write!(f, "Clip"),
+            ToneMap::Reinhard => write!(f, "Reinhard"),
+            ToneMap::Aces     => write!(f, "Aces"),
+            ToneMap::Hable    => write!(f, "Hable"),
+        }
+    }
+}
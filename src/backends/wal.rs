@@ -8,14 +8,22 @@
 //!   skip      ^
 //!       we care bout this one
 //! ```
+//! Images with an alpha channel add a fourth value, `(92,64,54,255)`, which is checked against
+//! the [`Loader`] cutoff.
+//!
+//! Animated images read the frames picked by [`frames::indexes`], already coalesced (full frames
+//! instead of the differences) and appended into one tall image.
+//!
+//! Images in memory are piped through stdin, see [`Source::encoded`].
 use crate::backends::*;
-use std::process::Command;
+use std::io::Write;
+use std::process::{Command, Stdio};
 use std::str;
 use palette::Srgb;
 use palette::cast::AsComponents;
 use std::fmt;
 
-enum IMcmd {
+pub enum IMcmd {
     Magick,
     Convert,
 }
@@ -31,49 +39,86 @@ impl fmt::Display for IMcmd {
 
 
 /// Inspired by how pywal uses Image Magick :)
-pub fn wal(f: &Path) -> Result<Vec<u8>> {
-    let mut cols: Vec<Srgb<u8>> = Vec::with_capacity(16); // there will be no more than 16 colors
+#[derive(Debug, Clone, Copy)]
+pub struct Wal;
 
-    let magick_command = has_im()?.to_string();
+impl ImageParser for Wal {
+    fn read(&self, f: &Source, l: &Loader) -> Result<Pixels> {
+        let mut cols: Vec<Srgb<u8>> = Vec::with_capacity(16); // there will be no more than 16 colors
 
-    let mut raw_colors = String::new();
+        let magick_command = has_im()?.to_string();
 
-    // we start with 1, since we already 'did' an iteration by initializing the variable.
-    for i in 0..20 {
-        raw_colors = imagemagick(16 + i, f, &magick_command)?;
+        let mut raw_colors = String::new();
 
-        if raw_colors.lines().count() > 16 { break }
+        // `-crop WxH+X+Y`, applied before resizing, without the borders and inside the region
+        let rect = l.rect(f)?;
+        let crop = match rect {
+            Some((x, y, w, h)) => vec!["-crop".into(), format!("{w}x{h}+{x}+{y}"), "+repage".into()],
+            None => vec![],
+        };
 
-        if i == 19 {
-            anyhow::bail!("Imagemagick couldn't generate a suitable palette.");
+        // how to read the image, for animated ones pick the same frames as the other backends and
+        // stack them on top of each other, since those can be repeated (weighted), clone them.
+        let frames = if l.frames > 1 { frames::indexes(f, l.frames)? } else { None };
+        let (name, stdin) = match f.path() {
+            Some(p) => (p.display().to_string(), None),
+            None => ("-".to_string(), Some(f.encoded()?)),
+        };
+        let input: Vec<String> = match frames {
+            Some(idx) if !idx.is_empty() => {
+                let idx: Vec<String> = idx.iter().map(|i| i.to_string()).collect();
+                [
+                    vec![name, "-coalesce".into(), "(".into(), "-clone".into(), idx.join(",")],
+                    crop,
+                    vec!["-append".into(), ")".into(), "-delete".into(), "0--2".into()],
+                ].concat()
+            },
+            // gif edge case, use the first frame. Rotated like the other backends, so the region matches
+            _ => [vec![format!("{name}[0]"), "-auto-orient".into()], crop].concat(),
+        };
+
+        // we start with 1, since we already 'did' an iteration by initializing the variable.
+        for i in 0..20 {
+            raw_colors = imagemagick(16 + i, &input, &magick_command, stdin.as_deref())?;
+
+            if raw_colors.lines().count() > 16 { break }
+
+            if i == 19 {
+                anyhow::bail!("Imagemagick couldn't generate a suitable palette.");
+            }
+            // else {
+                // No need to print, just keep trying.
+                // eprintln!("Imagemagick couldn't generate a palette.");
+                // eprintln!("Trying a larger palette size {}", 16 + i);
+            // }
         }
-        // else {
-            // No need to print, just keep trying.
-            // eprintln!("Imagemagick couldn't generate a palette.");
-            // eprintln!("Trying a larger palette size {}", 16 + i);
-        // }
-    }
 
-    for line in raw_colors.lines().skip(1) {
-        let mut s = line.split_ascii_whitespace().skip(1);
-        let hex = s.next().expect("Should always be present, without spaces in between e.g. (0,0,0)");
-        //let hex : Srgb<u8> = *hex.parse::<Srgba<u8>>()?.into_format::<u8, u8>();
-        let hex = &hex[1..hex.len() - 1];
-        let rgbs: Vec<u8> = hex
-                                .split(',')
-                                .map(|x| x.parse::<u8>().expect("Should be a number"))
-                                .collect();
-        let hex = Srgb::new(rgbs[0], rgbs[1], rgbs[2]);
-        cols.push(hex);
-    }
+        for line in raw_colors.lines().skip(1) {
+            let mut s = line.split_ascii_whitespace().skip(1);
+            let hex = s.next().expect("Should always be present, without spaces in between e.g. (0,0,0)");
+            //let hex : Srgb<u8> = *hex.parse::<Srgba<u8>>()?.into_format::<u8, u8>();
+            let hex = &hex[1..hex.len() - 1];
+            let rgbs: Vec<u8> = hex
+                                    .split(',')
+                                    .map(|x| x.parse::<u8>().expect("Should be a number"))
+                                    .collect();
+            if rgbs.get(3).is_some_and(|&a| !l.is_visible(a)) { continue; }
+            let hex = Srgb::new(rgbs[0], rgbs[1], rgbs[2]);
+            cols.push(hex);
+        }
+
+        if cols.is_empty() {
+            anyhow::bail!("Every color image magick found is transparent (below the {}% alpha cutoff).", l.alpha_cutoff);
+        }
 
-    Ok(cols.as_components().to_vec())
+        Ok(Pixels { rgb: cols.as_components().to_vec(), crop: rect, size: None })
+    }
 }
 
-fn imagemagick(color_count: u8, img: &Path, magick_command: &str) -> Result<String> {
-    let im = Command::new(magick_command)
+fn imagemagick(color_count: u8, input: &[String], magick_command: &str, stdin: Option<&[u8]>) -> Result<String> {
+    let mut im = Command::new(magick_command)
+        .args(input)
         .args([
-            &format!("{}[0]", img.display()), // gif edge case, use the first frame
             "-resize", "25%",
             "-colors", &color_count.to_string(),
             "-unique-colors",
@@ -81,14 +126,25 @@ fn imagemagick(color_count: u8, img: &Path, magick_command: &str) -> Result<Stri
             "-depth", "8", // 8 bit
             "txt:-",
         ])
-        .output()
+        .stdin(if stdin.is_some() { Stdio::piped() } else { Stdio::null() })
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
         .expect("This should run, given that `has_im()` should fail first, unless IM flags are deprecated.");
 
+    // the pipe is closed (dropped) before waiting, so image magick knows the image ended
+    let written = match (stdin, im.stdin.take()) {
+        (Some(bytes), Some(mut pipe)) => pipe.write_all(bytes),
+        _ => Ok(()),
+    };
+    let im = im.wait_with_output()?;
+    written?;
+
     Ok(str::from_utf8(&im.stdout)?.to_owned())
 }
 
 ///whether to use `magick` or good old `convert`
-fn has_im() -> Result<IMcmd> {
+pub fn has_im() -> Result<IMcmd> {
     let m = "magick";
     let c = "convert";
 
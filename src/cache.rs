@@ -7,6 +7,8 @@ use std::path::Path;
 use std::path::PathBuf;
 use palette::Srgb;
 
+use crate::args::Input;
+use crate::backends::Source;
 use crate::colors::Colors;
 use crate::config::Config;
 
@@ -16,7 +18,7 @@ use anyhow::{Result, Context};
 /// For example, when there is an internal change in how the
 /// scheme is generated, the cache format won't change, however,
 /// there is a need for a regeneration, so we bump up the version.
-pub const CACHE_VER: &str = "1.7";
+pub const CACHE_VER: &str = "1.11";
 
 /// Used to manage cache, rather than passing arguments in main() a lot
 #[derive(Debug, Default)]
@@ -43,8 +45,8 @@ impl fmt::Display for Cache {
     }
 }
 
-/// Simple shadow for colorscheme return type
-type CSret = (Vec<Srgb>, Vec<Srgb>, bool);
+/// Simple shadow for colorscheme return type, with the threshold used
+type CSret = (Vec<Srgb>, Vec<Srgb>, bool, u8, Vec<usize>);
 
 /// Cache order
 #[derive(Debug)]
@@ -59,30 +61,95 @@ pub enum IsCached {
 impl Cache {
     /// # Filename structure, magic numbers (cachefmt) after this impl block:
     /// *Each hash image has it's own dir*, inside there is multiple files:
-    /// 1. Backend file, with the full name, maybe reductant with the `full` backend.
+    /// 1. Backend file, with the full name and the [`crate::backends::Loader`] options, maybe reductant with the `full` backend.
     /// 2. ColorSpace + threshold, since it depends on the threshold
     /// 3. Scheme + ColorSpace + threshold, since the palette depends on the colorspace, and the colorspace on the threshold
     ///    This new structure allows you to reuse some parts, when configuring, avoding more time waiting.
     pub fn new(file: &Path, c: &Config, cache_path: &Path) -> Result<Self> {
+        Self::with_inputs(&[Input::from(file.to_path_buf())], c, cache_path)
+    }
+
+    /// Like [`Cache::new`], for the palette of several images combined, see [`crate::combine`].
+    pub fn with_inputs(inputs: &[Input], c: &Config, cache_path: &Path) -> Result<Self> {
+        Self::with_backend(inputs, c, c.backend, cache_path)
+    }
+
+    /// Like [`Cache::with_inputs`], read with `backend` instead of the one in `c`.
+    pub fn with_backend(inputs: &[Input], c: &Config, backend: crate::backends::Backend, cache_path: &Path) -> Result<Self> {
         // create cache (e.g. `~/.cache/wallust`)
         let cachepath = cache_path.join("wallust");
 
         // hash value for the file, since you can duplicate it, but the contents are the same.
-        let hash  = base36(fnv1a(&std::fs::read(file)?));
+        let hash = match inputs {
+            [i] if i.weight == 1.0 => base36(hash(&i.source)?),
+            // the hash of every file with it's weight, in order
+            _ => {
+                let mut bytes = vec![];
+                for i in inputs {
+                    bytes.extend(hash(&i.source)?.to_le_bytes());
+                    bytes.extend(i.weight.to_le_bytes());
+                }
+                format!("m{}", base36(fnv1a(&bytes)))
+            },
+        };
 
         let name = cachepath.join(format!("{hash}_{CACHE_VER}"));
         // Create cache dir (with all of it's parents)
         fs::create_dir_all(&name).with_context(|| "Failed to create {cachepath}")?;
 
-        let th    = if c.true_th == 0 { "auto" } else { &c.true_th.to_string() };
+        let th = match (c.color_count, c.true_th) {
+            (Some(n), _) => format!("n{n}"),
+            (None, 0) => "auto".into(),
+            (None, t) => t.to_string(),
+        };
         // wallust/image_1.0/
         let base = cachepath.join(format!("{hash}_{CACHE_VER}"));
 
-        let back = c.backend.to_string();
-        let cs  = c.color_space.to_string();
-        let palet = c.palette.to_string();
+        // how the image was loaded changes the backend output, so it's part of every file
+        let loader = c.loader();
+        let back = format!("{backend}{}_{loader}", backend.parser(&c.backend_params).key());
+        // the pixels are weighted as they are read, see [`crate::colorspaces::weighting`]
+        let back = match c.weighting.unwrap_or_default() {
+            crate::colorspaces::Weighting::None => back,
+            w => format!("{back}_{w}"),
+        };
+        // ignored colors, the fallback generator and scoring change which colors are found, but
+        // not the backend output
+        let mut cs = format!("{}{}", c.color_space, c.color_space_bounds.key());
+        if let Some(v) = c.ignore_colors.as_ref().filter(|v| !v.is_empty()) {
+            let tol = c.ignore_tolerance.unwrap_or(crate::colorspaces::ignore::TOLERANCE);
+            let ignored = v.iter().map(|c| c.to_string()).collect::<String>();
+            cs.push_str(&format!("_i{}", base36(fnv1a(format!("{ignored}{tol}").as_bytes()))));
+        }
+        // lchansi buckets changed the colors they find, so older caches of it aren't read, see
+        // CHANGELOG.md
+        if c.color_space == crate::colorspaces::ColorSpace::LchAnsi { cs.push_str("_v2"); }
+        // the fallback generator fills the palette of images without enough colors
+        match c.fallback_generator.unwrap_or_default() {
+            crate::colorspaces::FallbackGenerator::Interpolate => (),
+            g => cs.push_str(&format!("_{g:?}")),
+        }
+        match c.scoring.unwrap_or_default() {
+            crate::colorspaces::Scoring::Count => (),
+            s => cs.push_str(&format!("_{s}")),
+        }
+        // the colors are sorted for the palette, so `light` doesn't read the ones of `dark`
+        if c.sort_ord() == crate::colorspaces::ColorOrder::DarkFirst {
+            cs.push_str("_DarkFirst");
+        }
+        // custom palettes change with their `[palettes.<name>]` table, and `auto` with the palettes
+        // it chooses from
+        let palet = |p: &crate::palettes::PaletteName| match c.custom(p) {
+            Some(t) => format!("{p}_{}", base36(fnv1a(format!("{t:?}").as_bytes()))),
+            None => p.to_string(),
+        };
+        let palet = match &c.palette {
+            crate::palettes::PaletteName::Builtin(crate::palettes::Palette::Auto) =>
+                format!("{}_{}_{}", c.palette, palet(&c.palette_auto.dark), palet(&c.palette_auto.light)),
+            p => palet(p),
+        };
         let preset = match &c.preset {
-            Some(s) => Some(base.join(s.to_string())),
+            Some(s) => Some(base.join(format!("{s}_{loader}"))),
             None => None,
         };
 
@@ -192,6 +259,21 @@ impl Cache {
 
 /* helpers */
 
+/// [`fnv1a`] of the contents of the image, so the same one shares the cache whether it's read
+/// from a file or stdin. Decoded images hash their pixels, with their size and color type. Files
+/// should be already read (see [`Source::read`]), otherwise they are read here only to hash them.
+pub fn hash(s: &Source) -> Result<u32> {
+    Ok(match s {
+        Source::Path(p) => fnv1a(&fs::read(p)?),
+        Source::File(_, b) | Source::Bytes(b) => fnv1a(b),
+        Source::Image(img) => {
+            let mut bytes = format!("{}x{}{:?}", img.width(), img.height(), img.color()).into_bytes();
+            bytes.extend_from_slice(img.as_bytes());
+            fnv1a(&bytes)
+        },
+    })
+}
+
 /// Pretty fcking fast hashing
 /// the 32 bit version, should be enough for this use case
 /// Ref: https://en.m.wikipedia.org/wiki/Fowler%E2%80%93Noll%E2%80%93Vo_hash_function
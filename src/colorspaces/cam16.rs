@@ -0,0 +1,107 @@
+//! # CAM16-UCS
+//! The uniform colorspace of the CIE CAM16 color appearance model. Where CIEDE2000 on Lab merges
+//! saturated colors that look clearly apart (vivid blues and purples, neons), the distance in
+//! CAM16-UCS keeps them separated, since it models how colorfulness is perceived.
+//! - ref: <https://docs.rs/palette/latest/palette/cam16/index.html>
+use std::sync::OnceLock;
+
+use palette::cam16::{BakedParameters, Cam16Jmh, Cam16UcsJab, Cam16UcsJmh, Parameters, StaticWp};
+use palette::convert::FromColorUnclamped;
+use palette::white_point::D65;
+use palette::{LinSrgb, Xyz};
+
+use super::*;
+
+pub struct Cam16;
+
+/// CAM16-UCS J'a'b', wrapped since it can't be converted from or to RGB by itself: it depends on
+/// the viewing conditions, see [`params`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Spec(pub Cam16UcsJab<f32>);
+
+/// Minimum CAM16-UCS lightness (J', from 0 to 100) required for a color to be accepted
+pub const DARKEST: f32 = 4.5;
+
+/// Maximum CAM16-UCS lightness (J', from 0 to 100) allowed for a color to be accepted
+pub const LIGHTEST: f32 = 95.5;
+
+/// CAM16-UCS colorfulness M' (up to ~52) per CIE LCh chroma (up to ~130), to scale
+/// [`Bounds::min_chroma`]
+pub const CHROMA: f32 = 0.39;
+
+/// A screen in a dim room (40 nits) with a medium gray surround, computed once.
+fn params() -> BakedParameters<StaticWp<D65>, f32> {
+    static PARAMS: OnceLock<BakedParameters<StaticWp<D65>, f32>> = OnceLock::new();
+    *PARAMS.get_or_init(|| Parameters::default_static_wp(40.0).bake())
+}
+
+impl FromColorUnclamped<LinSrgb> for Spec {
+    fn from_color_unclamped(c: LinSrgb) -> Self {
+        let xyz: Xyz<D65> = Xyz::from_color_unclamped(c);
+        let jmh = Cam16UcsJmh::from_color_unclamped(Cam16Jmh::from_xyz(xyz, params()));
+        Self(Cam16UcsJab::from_color_unclamped(jmh))
+    }
+}
+
+impl FromColorUnclamped<Srgb> for Spec {
+    fn from_color_unclamped(c: Srgb) -> Self {
+        Self::from_color_unclamped(c.into_linear())
+    }
+}
+
+impl FromColorUnclamped<Spec> for Srgb {
+    fn from_color_unclamped(c: Spec) -> Self {
+        let jmh = Cam16Jmh::from_color_unclamped(Cam16UcsJmh::from_color_unclamped(c.0));
+        let xyz: Xyz<D65> = jmh.into_xyz(params());
+        Srgb::from_color_unclamped(xyz)
+    }
+}
+
+impl Clamp for Spec {
+    fn clamp(self) -> Self { Self(self.0.clamp()) }
+}
+
+impl Mix for Spec {
+    type Scalar = f32;
+    fn mix(self, other: Self, factor: f32) -> Self { Self(self.0.mix(other.0, factor)) }
+}
+
+impl ColorTrait for Spec {}
+
+impl Difference for Spec {
+    /// CAM16-UCS's own ΔE', the euclidean distance. Not the "improved" `1.41 * ΔE'^0.63`, that
+    /// would bring it back to about the same scale (and merges) of CIEDE2000.
+    fn col_diff(&self, a: &Self, threshold: u8) -> bool {
+        use palette::color_difference::DeltaE;
+        self.0.delta_e(a.0) <= f32::from(threshold)
+    }
+}
+
+impl BuildHisto<Spec> for Cam16 {
+    /// Only by lightness, unless there is a `min_chroma`, compared to the colorfulness M'.
+    fn filter_cols(histo: Vec<Histo<Spec>>, bounds: &Bounds) -> Vec<Histo<Spec>> {
+        let lights = histo.iter().map(|c| c.color.0.lightness).collect::<Vec<_>>();
+        let darkest  = lights.iter().fold(f32::INFINITY, |a, &b| a.min(b)).max(bounds.darkest.unwrap_or(DARKEST));
+        let lightest = lights.iter().fold(f32::NEG_INFINITY, |a, &b| a.max(b)).min(bounds.lightest.unwrap_or(LIGHTEST));
+        let ch = bounds.min_chroma.unwrap_or(0.0) * CHROMA;
+
+        let filt = |x: &Spec| x.0.lightness >= darkest && x.0.lightness <= lightest && x.0.a.hypot(x.0.b) >= ch;
+
+        histo.into_iter().filter(|c| filt(&c.color)).collect()
+    }
+
+    fn sort_col(histo: Vec<Histo<Spec>>, cs: &ColorOrder) -> Vec<Histo<Spec>> {
+        let mut histo = histo;
+
+        histo.sort_by(|a, b| match cs {
+            ColorOrder::LightFirst => b.color.0.lightness.partial_cmp(&a.color.0.lightness).unwrap_or(std::cmp::Ordering::Equal),
+            ColorOrder::DarkFirst  => a.color.0.lightness.partial_cmp(&b.color.0.lightness).unwrap_or(std::cmp::Ordering::Equal),
+        });
+
+        histo
+    }
+
+    fn sort_by_key_fn(a: Histo<Spec>) -> impl Ord {
+        (a.color.0.lightness as u32, a.color.0.a as i32, a.color.0.b as i32)
+    }
+}
@@ -4,8 +4,7 @@ use std::fmt;
 use crate::colors::Compl;
 
 use serde::{Serialize, Deserialize};
-use palette::Mix;
-use palette::Srgb;
+use palette::{convert::FromColorUnclamped, FromColor, IsWithinBounds, Mix, Oklch, Srgb};
 use owo_colors::AnsiColors;
 
 #[derive(Debug, PartialEq, Eq, Deserialize, Serialize, Clone, Copy, Default, clap::ValueEnum)]
@@ -20,6 +19,16 @@ pub enum FallbackGenerator {
     Interpolate,
     /// uses [`complementary`]
     Complementary,
+    /// uses [`triadic`]
+    Triadic,
+    /// uses [`tetradic`]
+    Tetradic,
+    /// uses [`analogous`]
+    Analogous,
+    /// uses [`split_complementary`]
+    #[clap(alias = "split-complementary", name = "splitcomplementary")] //claps prefers this-name
+    #[serde(alias = "split-complementary")]
+    SplitComplementary,
 }
 
 use self::FallbackGenerator as G;
@@ -29,6 +38,10 @@ impl FallbackGenerator {
         match self {
             G::Interpolate => interpolate,
             G::Complementary => complementary,
+            G::Triadic => triadic,
+            G::Tetradic => tetradic,
+            G::Analogous => analogous,
+            G::SplitComplementary => split_complementary,
         }
     }
 
@@ -36,6 +49,10 @@ impl FallbackGenerator {
         match self {
             G::Interpolate => AnsiColors::Blue,
             G::Complementary => AnsiColors::Green,
+            G::Triadic => AnsiColors::Magenta,
+            G::Tetradic => AnsiColors::Cyan,
+            G::Analogous => AnsiColors::Yellow,
+            G::SplitComplementary => AnsiColors::BrightGreen,
         }
     }
 }
@@ -57,7 +74,6 @@ fn interpolate(color_a: Srgb, color_b: Srgb, n: u8) -> Vec<Srgb> {
     v
 }
 
-//TODO implement triards, cuartets, quints
 pub fn complementary(color_a: Srgb, color_b: Srgb, _: u8) -> Vec<Srgb> {
     vec![
         color_a.complementary(),
@@ -65,11 +81,52 @@ pub fn complementary(color_a: Srgb, color_b: Srgb, _: u8) -> Vec<Srgb> {
     ]
 }
 
+/// Rotates the hue of both colors by each of `angles` (in degrees), in OKLch, so the lightness is
+/// kept. Colors that end up out of sRGB lose some chroma until they fit, instead of shifting hue.
+fn rotate(color_a: Srgb, color_b: Srgb, angles: &[f32]) -> Vec<Srgb> {
+    [color_a, color_b].into_iter()
+        .flat_map(|c| {
+            let lch = Oklch::from_color(c);
+            angles.iter().map(move |&a| {
+                let mut new = Oklch { hue: lch.hue + a, ..lch };
+                while !Srgb::from_color_unclamped(new).is_within_bounds() && new.chroma > 0.001 {
+                    new.chroma *= 0.95;
+                }
+                Srgb::from_color(new)
+            })
+        })
+        .collect()
+}
+
+/// The two colors that, with each one, split the hue wheel in three.
+pub fn triadic(color_a: Srgb, color_b: Srgb, _: u8) -> Vec<Srgb> {
+    rotate(color_a, color_b, &[120.0, 240.0])
+}
+
+/// The three colors that, with each one, split the hue wheel in four.
+pub fn tetradic(color_a: Srgb, color_b: Srgb, _: u8) -> Vec<Srgb> {
+    rotate(color_a, color_b, &[90.0, 180.0, 270.0])
+}
+
+/// The neighbours of each color, 30 degrees at both sides.
+pub fn analogous(color_a: Srgb, color_b: Srgb, _: u8) -> Vec<Srgb> {
+    rotate(color_a, color_b, &[-30.0, 30.0])
+}
+
+/// Both neighbours of the complementary of each color, 30 degrees away from it.
+pub fn split_complementary(color_a: Srgb, color_b: Srgb, _: u8) -> Vec<Srgb> {
+    rotate(color_a, color_b, &[150.0, 210.0])
+}
+
 impl fmt::Display for G {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
             G::Interpolate => write!(f, "Interpolate"),
             G::Complementary => write!(f, "Complementary"),
+            G::Triadic => write!(f, "Triadic"),
+            G::Tetradic => write!(f, "Tetradic"),
+            G::Analogous => write!(f, "Analogous"),
+            G::SplitComplementary => write!(f, "Split Complementary"),
         }
     }
 }
@@ -0,0 +1,35 @@
+//! # Ignore
+//! Colors that shouldn't be part of the palette, like the black bars of a film still that weren't
+//! trimmed, or the white background of a drawing. Pixels close enough to any of them are dropped
+//! before looking for the colors.
+use std::borrow::Cow;
+
+use anyhow::Result;
+use palette::Srgb;
+
+use crate::colors::Myrgb;
+
+/// Default maximum difference, in any channel, to match an ignored color
+pub const TOLERANCE: u8 = 10;
+
+/// Drop the `rgb` pixels within `tolerance` (the maximum difference in any channel, 0 to 255) of
+/// any of the `colors`. Errors if every pixel is dropped.
+pub fn ignore<'a>(rgb: &'a [u8], colors: &[Myrgb], tolerance: u8) -> Result<Cow<'a, [u8]>> {
+    if colors.is_empty() { return Ok(Cow::Borrowed(rgb)); }
+
+    let colors: Vec<[u8; 3]> = colors.iter().map(|&c| Srgb::<u8>::from(c).into()).collect();
+    let close = |p: &[u8]| colors.iter().any(|c| (0..3).all(|i| p[i].abs_diff(c[i]) <= tolerance));
+
+    let ret: Vec<u8> = rgb
+        .chunks_exact(3)
+        .filter(|p| !close(p))
+        .flatten()
+        .copied()
+        .collect();
+
+    if ret.is_empty() {
+        anyhow::bail!("Every pixel of the image is one of the ignored colors.");
+    }
+
+    Ok(Cow::Owned(ret))
+}
@@ -0,0 +1,238 @@
+//! # K-means
+//! Instead of merging the colors closer than a threshold, the colors of the image are split in `k`
+//! clusters with k-means, in OKLab, where a plain euclidean distance works as deltaE. Every cluster
+//! gives its average color, and how many pixels are in it as the count. There is no threshold to
+//! find, and the same image always gives the same palette.
+//!
+//! The first centroids are chosen with k-means++, away from each other, from a fixed seed. This is
+//! done [`RUNS`] times, one per thread and each with its own seed, keeping the tightest clusters.
+//! - ref: <https://en.wikipedia.org/wiki/K-means%2B%2B>
+use super::*;
+use super::oklab::{OkLab, Spec};
+
+pub struct KMeans;
+
+/// Times the clusters are made, keeping the ones with the colors closest to their centroid
+pub const RUNS: u64 = 4;
+
+/// Most iterations of every run
+pub const MAX_ITER: usize = 100;
+
+/// Stop iterating once no centroid moves more than this (OKLab goes from 0 to ~1)
+const CONVERGE: f32 = 1e-4;
+
+/// Seed of the first run, the next ones add one
+const SEED: u64 = 0xBEEF;
+
+/// Threshold used to tell apart the colors made up by [`BuildHisto::fallback`]
+const FALLBACK_TH: u8 = 10;
+
+/// Like [`run_once`], but with `k` clusters ([`MAX_COLS`] without it) instead of a threshold, so
+/// the threshold returned is always 0.
+pub fn run(
+    bytes: &[u8],
+    k: Option<u8>,
+    gen: &G,
+    ord: &ColorOrder,
+    bounds: &Bounds,
+    score: &Scoring,
+) -> Option<(Vec<Srgb>, Vec<Srgb>, bool, u8, Vec<usize>)> {
+    let pixels = KMeans::pixels(bytes, bounds);
+    let mut histo = cluster(&pixels, k.unwrap_or(MAX_COLS).into());
+    let mut warn = false;
+    let len = histo.len();
+
+    if len < 2 { return None }
+
+    if len == 2 {
+        warn = true;
+        histo = KMeans::fallback_monochromatic(histo, gen, score);
+    } else if len < MIN_COLS.into() {
+        warn = true;
+        histo = KMeans::fallback(histo, FALLBACK_TH, gen, bounds, score);
+    } else {
+        // best colors first, by count unless another [`Scoring`] is chosen
+        score.sort(&mut histo);
+    }
+
+    let orig = KMeans::to_rgb(&histo);
+    let counts = histo.iter().map(|h| h.count).collect();
+    let top  = KMeans::sort_col(histo, ord);
+    let top  = KMeans::to_rgb(&top);
+
+    Some( (top, orig, warn, 0, counts) )
+}
+
+/// Splits the `pixels` in `k` clusters, or less if there aren't that many colors.
+pub fn cluster(pixels: &[Histo<Spec>], k: usize) -> Vec<Histo<Spec>> {
+    use std::thread;
+
+    if pixels.len() <= k { return pixels.to_vec() }
+
+    let runs = thread::scope(|s| {
+        (0..RUNS)
+            .map(|r| s.spawn(move || lloyd(pixels, plus_plus(pixels, k, &mut Rng(SEED + r)))))
+            .collect::<Vec<_>>()
+            .into_iter()
+            .map(|t| t.join().expect("Waiting for the thread."))
+            .collect::<Vec<_>>()
+    });
+
+    // the first one wins a tie, so it doesn't depend on the threads
+    let best = runs.into_iter()
+        .reduce(|best, r| if r.1 < best.1 { r } else { best })
+        .map(|(histo, _)| histo)
+        .unwrap_or_default();
+
+    merge(best)
+}
+
+/// Clusters whose centroids converged to the same color, once in sRGB, are only one, with the
+/// average of both (by count) as its color.
+fn merge(histo: Vec<Histo<Spec>>) -> Vec<Histo<Spec>> {
+    let rgb8 = |c: Spec| -> Srgb<u8> {
+        let c: Srgb = c.into_color();
+        c.into_format()
+    };
+    let mut ret: Vec<Histo<Spec>> = Vec::with_capacity(histo.len());
+
+    for h in histo {
+        match ret.iter_mut().find(|r| rgb8(r.color) == rgb8(h.color)) {
+            Some(r) => {
+                r.color = r.color.mix(h.color, h.count as f32 / (r.count + h.count) as f32);
+                r.count += h.count;
+            },
+            None => ret.push(h),
+        }
+    }
+
+    ret
+}
+
+/// k-means++: the first centroid is a random color, and every next one is more likely the further
+/// it is from the ones already chosen. Colors are as likely as their count.
+fn plus_plus(pixels: &[Histo<Spec>], k: usize, rng: &mut Rng) -> Vec<Spec> {
+    let mut weights = pixels.iter().map(|h| h.count as f64).collect::<Vec<_>>();
+    let mut centroids = Vec::with_capacity(k);
+
+    while centroids.len() < k {
+        let Some(i) = rng.pick(&weights) else { break };
+        let c = pixels[i].color;
+        centroids.push(c);
+
+        // squared distance to the closest centroid, the first time it's to this one
+        for (w, h) in weights.iter_mut().zip(pixels) {
+            let d = h.count as f64 * f64::from(distance(&h.color, &c));
+            *w = if centroids.len() == 1 { d } else { w.min(d) };
+        }
+    }
+
+    centroids
+}
+
+/// Lloyd's algorithm: every color goes to its closest centroid, and every centroid moves to the
+/// average of its colors, until they stay still. Returns the clusters, without the empty ones,
+/// and how far the colors are from their centroid in total (squared).
+fn lloyd(pixels: &[Histo<Spec>], mut centroids: Vec<Spec>) -> (Vec<Histo<Spec>>, f64) {
+    let mut cluster = vec![0; pixels.len()];
+
+    for _ in 0..MAX_ITER {
+        for (c, h) in cluster.iter_mut().zip(pixels) {
+            *c = nearest(&h.color, &centroids);
+        }
+
+        let mut sums = vec![([0.0_f64; 3], 0_usize); centroids.len()];
+        for (&c, h) in cluster.iter().zip(pixels) {
+            let n = h.count as f64;
+            let (s, count) = &mut sums[c];
+            s[0] += f64::from(h.color.l) * n;
+            s[1] += f64::from(h.color.a) * n;
+            s[2] += f64::from(h.color.b) * n;
+            *count += h.count;
+        }
+
+        let mut moved = 0.0_f32;
+        for (centroid, (s, count)) in centroids.iter_mut().zip(sums) {
+            // an empty cluster keeps its centroid
+            if count == 0 { continue }
+            let n = count as f64;
+            let new = Spec::new((s[0] / n) as f32, (s[1] / n) as f32, (s[2] / n) as f32);
+            moved = moved.max(distance(centroid, &new));
+            *centroid = new;
+        }
+
+        if moved < CONVERGE * CONVERGE { break }
+    }
+
+    let mut counts = vec![0; centroids.len()];
+    let mut total = 0.0;
+    for h in pixels {
+        let c = nearest(&h.color, &centroids);
+        counts[c] += h.count;
+        total += h.count as f64 * f64::from(distance(&h.color, &centroids[c]));
+    }
+
+    let histo = centroids.into_iter()
+        .zip(counts)
+        .filter(|&(_, n)| n > 0)
+        .map(|(c, n)| Histo::new(c, n))
+        .collect();
+
+    (histo, total)
+}
+
+/// Index of the centroid closest to `color`
+fn nearest(color: &Spec, centroids: &[Spec]) -> usize {
+    centroids.iter()
+        .map(|c| distance(color, c))
+        .enumerate()
+        .fold((0, f32::INFINITY), |best, (i, d)| if d < best.1 { (i, d) } else { best })
+        .0
+}
+
+/// Squared euclidean distance, enough to compare them
+fn distance(x: &Spec, y: &Spec) -> f32 {
+    (x.l - y.l).powi(2) + (x.a - y.a).powi(2) + (x.b - y.b).powi(2)
+}
+
+/// xorshift64*, only to pick the first centroids, the same seed gives the same ones.
+struct Rng(u64);
+
+impl Rng {
+    /// Between 0 and 1
+    fn next(&mut self) -> f64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        (self.0.wrapping_mul(0x2545_F491_4F6C_DD1D) >> 11) as f64 / (1_u64 << 53) as f64
+    }
+
+    /// A random index, each one as likely as its weight. `None` if they are all 0.
+    fn pick(&mut self, weights: &[f64]) -> Option<usize> {
+        let total = weights.iter().sum::<f64>();
+        if total <= 0.0 { return None }
+
+        let mut target = self.next() * total;
+        for (i, &w) in weights.iter().enumerate() {
+            if target < w && w > 0.0 { return Some(i) }
+            target -= w;
+        }
+        // rounding errors
+        weights.iter().rposition(|&w| w > 0.0)
+    }
+}
+
+/// Same as [`OkLab`], only the way colors are gathered changes.
+impl BuildHisto<Spec> for KMeans {
+    fn filter_cols(histo: Vec<Histo<Spec>>, bounds: &Bounds) -> Vec<Histo<Spec>> {
+        OkLab::filter_cols(histo, bounds)
+    }
+
+    fn sort_col(histo: Vec<Histo<Spec>>, cs: &ColorOrder) -> Vec<Histo<Spec>> {
+        OkLab::sort_col(histo, cs)
+    }
+
+    fn sort_by_key_fn(a: Histo<Spec>) -> impl Ord {
+        OkLab::sort_by_key_fn(a)
+    }
+}
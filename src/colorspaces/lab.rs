@@ -28,14 +28,16 @@ impl Difference for Spec {
 }
 
 impl BuildHisto<Spec> for Lab {
-    fn filter_cols(histo: Vec<Spec>) -> Vec<Spec> {
-        let lights = histo.iter().map(|c| c.l).collect::<Vec<_>>();
-        let darkest  = lights.iter().fold(f32::INFINITY, |a, &b| a.min(b)).max(DARKEST);
-        let lightest = lights.iter().fold(f32::INFINITY, |a, &b| a.max(b)).min(LIGHTEST);
+    /// Only by lightness, unless there is a `min_chroma`.
+    fn filter_cols(histo: Vec<Histo<Spec>>, bounds: &Bounds) -> Vec<Histo<Spec>> {
+        let lights = histo.iter().map(|c| c.color.l).collect::<Vec<_>>();
+        let darkest  = lights.iter().fold(f32::INFINITY, |a, &b| a.min(b)).max(bounds.darkest.unwrap_or(DARKEST));
+        let lightest = lights.iter().fold(f32::INFINITY, |a, &b| a.max(b)).min(bounds.lightest.unwrap_or(LIGHTEST));
+        let ch = bounds.min_chroma.unwrap_or(0.0);
 
-        let filt = |x: Spec| x.l >= darkest && x.l <= lightest;
+        let filt = |x: Spec| x.l >= darkest && x.l <= lightest && x.a.hypot(x.b) >= ch;
 
-        histo.into_iter().filter(|&c| filt(c)).collect()
+        histo.into_iter().filter(|c| filt(c.color)).collect()
 
     }
 
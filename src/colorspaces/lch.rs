@@ -35,21 +35,21 @@ impl BuildHisto<Spec> for Lch {
 
     /// This filter gets the average to remove extreme colors.
     /// TODO even another filter to avoid blank `black/white`.
-    fn filter_cols(histo: Vec<Spec>) -> Vec<Spec> {
-        let lights = histo.iter().map(|c| c.l).collect::<Vec<_>>();
-        let darkest  = lights.iter().fold(f32::INFINITY, |a, &b| a.min(b)).max(DARKEST);
-        let lightest = lights.iter().fold(f32::NEG_INFINITY, |a, &b| a.max(b)).min(LIGHTEST);
+    fn filter_cols(histo: Vec<Histo<Spec>>, bounds: &Bounds) -> Vec<Histo<Spec>> {
+        let lights = histo.iter().map(|c| c.color.l).collect::<Vec<_>>();
+        let darkest  = lights.iter().fold(f32::INFINITY, |a, &b| a.min(b)).max(bounds.darkest.unwrap_or(DARKEST));
+        let lightest = lights.iter().fold(f32::NEG_INFINITY, |a, &b| a.max(b)).min(bounds.lightest.unwrap_or(LIGHTEST));
 
         // We don't care about mexchroma, but 0.0 to 1.0 chroma is grayscale like
         // we use lesschroma on monochromatic or similar imgs, so it doesn't error out
-        let chromas = histo.iter().map(|c| c.chroma).collect::<Vec<_>>();
-        let origch = util::avg(&chromas);
-        let lessch  = chromas.iter().fold(f32::INFINITY, |a, &b| a.min(b));
+        let origch = util::weighted_avg(histo.iter().map(|c| (c.color.chroma, c.count)));
+        let lessch  = histo.iter().fold(f32::INFINITY, |a, b| a.min(b.color.chroma));
         let ch = if origch <= MIN_CHROMA { lessch } else { origch / 2.5 };
+        let ch = bounds.min_chroma.unwrap_or(ch);
 
         let filt = |x: &Spec| (x.l >= darkest && x.l <= lightest) && x.chroma >= ch;
 
-        histo.into_iter().filter(filt).collect()
+        histo.into_iter().filter(|c| filt(&c.color)).collect()
     }
 
     fn sort_col(histo: Vec<Hist>, cs: &ColorOrder) -> Vec<Hist> {
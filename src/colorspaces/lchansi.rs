@@ -4,7 +4,7 @@
 use palette::{GetHue, LabHue};
 
 use super::*;
-use util::avg;
+use util::{avg, weighted_avg};
 
 pub struct LchAnsi;
 
@@ -12,6 +12,7 @@ pub struct LchAnsi;
 pub type Spec = palette::Lch;
 
 /// Used for a better handle of colors and it's (perception) 'limits'
+#[derive(Debug, Clone, Copy, PartialEq)]
 struct ColSettings {
     hue_start: f32,
     hue_end: f32,
@@ -19,13 +20,147 @@ struct ColSettings {
     chroma_def: f32,
 }
 
+impl ColSettings {
+    /// Whether `hue` (0 to 360) falls in `hue_start..hue_end`, wrapping around 360 if the end is
+    /// lower than the start.
+    fn contains(&self, hue: f32) -> bool {
+        if self.hue_start <= self.hue_end {
+            (self.hue_start..self.hue_end).contains(&hue)
+        } else {
+            hue >= self.hue_start || hue < self.hue_end
+        }
+    }
+}
+
+/// Default settings: hue range, lightness and chroma. In the same order as [`Buckets::NAMES`].
+const DEFAULTS: [ColSettings; 6] = [
+    ColSettings { hue_start:   0.0, hue_end:  60.0, light_def: 50.0, chroma_def: 181.0 }, // red
+    ColSettings { hue_start:  60.0, hue_end: 120.0, light_def: 80.0, chroma_def: 128.0 }, // yellow
+    ColSettings { hue_start: 120.0, hue_end: 180.0, light_def: 50.0, chroma_def: 128.0 }, // green
+    ColSettings { hue_start: 180.0, hue_end: 210.0, light_def: 80.0, chroma_def: 128.0 }, // cyan
+    ColSettings { hue_start: 210.0, hue_end: 280.0, light_def: 40.0, chroma_def: 181.0 }, // blue
+    ColSettings { hue_start: 280.0, hue_end: 360.0, light_def: 70.0, chroma_def: 128.0 }, // magenta
+];
+
+/// A hue range of [`LchAnsi`], from `hue[0]` up to (not including) `hue[1]`, in degrees. When
+/// `hue[1]` is lower, it wraps around 360, like `[330, 30]`. The lightness and chroma are the ones
+/// of the color when the image has none in that range, and pull the average of the ones found
+/// towards them.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[cfg_attr(feature = "schema" , derive(schemars::JsonSchema))]
+#[serde(deny_unknown_fields)]
+pub struct Bucket {
+    pub hue: [f32; 2],
+    pub lightness: Option<f32>,
+    pub chroma: Option<f32>,
+}
+
+/// The hue ranges of [`LchAnsi`], one per ANSI color. Leaving one out uses its default, but all of
+/// them, together, should cover the whole hue circle, 0 to 360, without overlapping.
+///
+/// ```toml
+/// [color_space.buckets]
+/// red  = { hue = [340, 50] }
+/// cyan = { hue = [180, 200], lightness = 75 }
+/// blue = { hue = [200, 280] }
+/// magenta = { hue = [280, 340] }
+/// yellow = { hue = [50, 120] }
+/// ```
+#[derive(Debug, Default, Clone, Copy, PartialEq, Deserialize)]
+#[cfg_attr(feature = "schema" , derive(schemars::JsonSchema))]
+#[serde(default, deny_unknown_fields)]
+pub struct Buckets {
+    pub red: Option<Bucket>,
+    pub yellow: Option<Bucket>,
+    pub green: Option<Bucket>,
+    pub cyan: Option<Bucket>,
+    pub blue: Option<Bucket>,
+    pub magenta: Option<Bucket>,
+}
+
+impl Buckets {
+    /// Names of the buckets, in the same order as [`Buckets::settings`].
+    pub const NAMES: [&'static str; 6] = ["red", "yellow", "green", "cyan", "blue", "magenta"];
+
+    /// Settings of every bucket, falling back to the defaults.
+    fn settings(&self) -> [ColSettings; 6] {
+        let set = [self.red, self.yellow, self.green, self.cyan, self.blue, self.magenta];
+        let mut ret = DEFAULTS;
+        for (r, b) in ret.iter_mut().zip(set) {
+            let Some(b) = b else { continue };
+            *r = ColSettings {
+                hue_start: b.hue[0],
+                hue_end: b.hue[1],
+                light_def: b.lightness.unwrap_or(r.light_def),
+                chroma_def: b.chroma.unwrap_or(r.chroma_def),
+            };
+        }
+        ret
+    }
+
+    /// Hues between 0 and 360, lightness between 0 and 100 and chroma between 0 and 181. The
+    /// ranges, with the defaults of the ones left out, should cover 0 to 360 without overlapping.
+    pub fn validate(&self) -> Result<(), String> {
+        let set = self.settings();
+
+        for (name, c) in Self::NAMES.iter().zip(&set) {
+            if [c.hue_start, c.hue_end].iter().any(|h| !(0.0..=360.0).contains(h)) {
+                return Err(format!("the hues of the {name} bucket should be between 0 and 360, not {} to {}", c.hue_start, c.hue_end));
+            }
+            if c.hue_start == c.hue_end {
+                return Err(format!("the {name} bucket is empty, it starts and ends at {}", c.hue_start));
+            }
+            if !(0.0..=100.0).contains(&c.light_def) {
+                return Err(format!("the lightness of the {name} bucket should be between 0 and 100, not {}", c.light_def));
+            }
+            if !(0.0..=181.0).contains(&c.chroma_def) {
+                return Err(format!("the chroma of the {name} bucket should be between 0 and 181, not {}", c.chroma_def));
+            }
+        }
+
+        // wrapping ranges are split in two, then they should follow one another from 0 to 360
+        let mut ranges = Self::NAMES.iter().zip(&set)
+            .flat_map(|(name, c)| if c.hue_start < c.hue_end {
+                vec![(c.hue_start, c.hue_end, name)]
+            } else {
+                vec![(c.hue_start, 360.0, name), (0.0, c.hue_end, name)]
+            })
+            .filter(|(s, e, _)| s < e)
+            .collect::<Vec<_>>();
+        ranges.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+        let mut end = 0.0;
+        let mut prev = &"";
+        for (s, e, name) in ranges {
+            if s < end {
+                return Err(format!("the {prev} and {name} buckets overlap, from {s} to {end}"));
+            }
+            if s > end {
+                return Err(format!("no bucket covers the hues from {end} to {s}"));
+            }
+            (end, prev) = (e, name);
+        }
+        if end < 360.0 {
+            return Err(format!("no bucket covers the hues from {end} to 360"));
+        }
+        Ok(())
+    }
+
+    /// Whether any bucket was set.
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
 impl BuildHisto<Spec> for LchAnsi {
 
     /// We don't care much about filter colors here. Use the old formula, since it's faster, to
     /// rapidly get the values. Since we modify them anyway.
-    fn filter_cols(histo: Vec<Spec>) -> Vec<Spec> {
-        let filt = |x: Spec| (x.l >= lch::DARKEST && x.l <= lch::LIGHTEST) &&  x.chroma > lch::MIN_CHROMA;
-        histo.into_iter().filter(|&c| filt(c)).collect()
+    fn filter_cols(histo: Vec<Histo<Spec>>, bounds: &Bounds) -> Vec<Histo<Spec>> {
+        let (darkest, lightest) = (bounds.darkest.unwrap_or(lch::DARKEST), bounds.lightest.unwrap_or(lch::LIGHTEST));
+        let ch = bounds.min_chroma.unwrap_or(lch::MIN_CHROMA);
+        let filt = |x: Spec| (x.l >= darkest && x.l <= lightest) &&  x.chroma > ch;
+        histo.into_iter().filter(|c| filt(c.color)).collect()
     }
 
     ///NO SORTING, since we set up everything in `gather_cols`
@@ -39,23 +174,21 @@ impl BuildHisto<Spec> for LchAnsi {
     ///  2. make sure we have MIN_COLS to not trigger a FallbackGenerator, hence assure that, for
     ///     example, color1 will always be greenish.
     ///     Red     falls between 0   and 60  degrees.
-    ///     Yellow  falls between 61  and 120 degrees.
-    ///     Green   falls between 121 and 180 degrees.
-    ///     Cyan    falls between 181 and 240 degrees.
-    ///     Blue    falls between 241 and 300 degrees.
-    ///     Magenta falls between 301 and 360 degrees.
+    ///     Yellow  falls between 60  and 120 degrees.
+    ///     Green   falls between 120 and 180 degrees.
+    ///     Cyan    falls between 180 and 210 degrees.
+    ///     Blue    falls between 210 and 280 degrees.
+    ///     Magenta falls between 280 and 360 degrees.
+    ///
+    ///     These are the [`DEFAULTS`], which can be changed with [`Buckets`].
     /// - Ref: <https://docs.rs/palette/latest/palette/lch/struct.Lch.html>
     // comments below are from the palette docs
-    fn gather_cols(colors: Vec<Spec>, _threshold: u8, _mix: bool) -> Vec<Histo<Spec>> {
+    fn gather_cols(colors: &[Histo<Spec>], _threshold: u8, _mix: bool, bounds: &Bounds) -> Vec<Histo<Spec>> {
 
-        let red     = ColSettings { hue_start:   0.0, hue_end:  60.0, light_def: 50.0, chroma_def: 181.0 };
-        let yellow  = ColSettings { hue_start:  61.0, hue_end: 120.0, light_def: 80.0, chroma_def: 128.0 };
-        let green   = ColSettings { hue_start: 121.0, hue_end: 180.0, light_def: 50.0, chroma_def: 128.0 };
-        let cyan    = ColSettings { hue_start: 181.0, hue_end: 210.0, light_def: 80.0, chroma_def: 128.0 };
-        let blue    = ColSettings { hue_start: 211.0, hue_end: 280.0, light_def: 40.0, chroma_def: 181.0 };
-        let magenta = ColSettings { hue_start: 281.0, hue_end: 360.0, light_def: 70.0, chroma_def: 128.0 };
+        let [red, yellow, green, cyan, blue, magenta] = bounds.buckets.settings();
 
-        let mut cols = colors.clone();
+        // the averages below weigh every color by how many pixels have it
+        let mut cols = colors.to_vec();
 
         let black = {
             //dummy
@@ -68,25 +201,25 @@ impl BuildHisto<Spec> for LchAnsi {
             let dark = 5.0;
 
             for c in &cols {
-                if c.l < dark {
-                    ret = Histo::new_no_count(*c);
+                if c.color.l < dark {
+                    ret = Histo::new_no_count(c.color);
                     break;
                 }
-                lights.push(c.l);
-                hues.push(c.hue.into_inner());
-                chromas.push(c.chroma);
+                lights.push((c.color.l, c.count));
+                hues.push((c.color.hue.into_inner(), c.count));
+                chromas.push((c.color.chroma, c.count));
             }
 
             if ret.count != 777 { //dummy value gone
                 //ret
                 Histo::new_no_count(Spec::new(ret.color.l, 15.0, ret.color.hue))
             } else {
-                let a = avg(&lights);
+                let a = wavg(&lights);
                 let l = (7.0*dark + a) / 8.0;
 
-                let avg_c = avg(&chromas);
+                let avg_c = wavg(&chromas);
                 let chroma = (2.0*0.0 + avg_c) / 3.0;
-                Histo::new_no_count(Spec::new(l, chroma, LabHue::new(avg(&hues))))
+                Histo::new_no_count(Spec::new(l, chroma, LabHue::new(wavg(&hues))))
                 // let r = Histo::new_no_count(Spec::new(l, chroma, LabHue::new(avg(&hues))));
                 // println!("{r:?}");
                 // r
@@ -104,13 +237,13 @@ impl BuildHisto<Spec> for LchAnsi {
             let lighty = 95.0;
 
             for c in &cols {
-                if c.l > lighty {
-                    ret = Histo::new_no_count(*c);
+                if c.color.l > lighty {
+                    ret = Histo::new_no_count(c.color);
                     break;
                 }
-                lights.push(c.l);
-                hues.push(c.hue.into_inner());
-                chromas.push(c.chroma);
+                lights.push((c.color.l, c.count));
+                hues.push((c.color.hue.into_inner(), c.count));
+                chromas.push((c.color.chroma, c.count));
             }
 
             if ret.count != 777 { //dummy value gone
@@ -118,15 +251,15 @@ impl BuildHisto<Spec> for LchAnsi {
                 Histo::new_no_count(Spec::new(ret.color.l, 15.0, ret.color.hue))
             } else {
                 // I'm very agressive with gray here, since it's more 'uncommon' than pitch black.
-                let a = avg(&lights);
+                let a = wavg(&lights);
                 let l = (4.0*lighty + a) / 5.0; //usually gets >80
 
-                let avg_c = avg(&chromas);
+                let avg_c = wavg(&chromas);
                 let chroma = (2.0*0.0 + avg_c) / 3.0;
                 // let r = Histo::new_no_count(Spec::new(l, chroma, LabHue::new(avg(&hues))));
                 //println!("{r:?}");
                 //r
-                Histo::new_no_count(Spec::new(l, chroma, LabHue::new(avg(&hues))))
+                Histo::new_no_count(Spec::new(l, chroma, LabHue::new(wavg(&hues))))
             }
         };
 
@@ -161,7 +294,7 @@ impl BuildHisto<Spec> for LchAnsi {
         histogram
     }
 
-    fn color_generator(_histo: &[Histo<Spec>], _threshold: u8, _gen: &FallbackGenerator) -> Vec<Histo<Spec>> {
+    fn color_generator(_histo: &[Histo<Spec>], _threshold: u8, _gen: &FallbackGenerator, _bounds: &Bounds) -> Vec<Histo<Spec>> {
         // gather_colors SHOULD ALWAYS fill at least MIN_COLORS.
         unreachable!()
     }
@@ -169,7 +302,7 @@ impl BuildHisto<Spec> for LchAnsi {
 
 
 ///TODO check lightness to not be black or white
-fn get_colors(cols: &mut Vec<Spec>, color: ColSettings) -> Histo<Spec> {
+fn get_colors(cols: &mut Vec<Histo<Spec>>, color: ColSettings) -> Histo<Spec> {
     let mut hues = vec![];
     let mut lights = vec![];
     let mut chromes = vec![];
@@ -186,38 +319,46 @@ fn get_colors(cols: &mut Vec<Spec>, color: ColSettings) -> Histo<Spec> {
     //     println!("{i}")
     // }
 
-    cols.retain(|c| {
-        let hue = c.get_hue().into_inner();
-        if (color.hue_start..color.hue_end).contains(&hue) {
-            hues.push(hue);
-            lights.push(c.l);
-            chromes.push(c.chroma);
-            true
-        } else {
+    // take out the colors of this bucket
+    cols.retain(|h| {
+        let (c, n) = (h.color, h.count);
+        let hue = c.get_hue().into_positive_degrees();
+        if color.contains(hue) {
+            hues.push((hue, n));
+            lights.push((c.l, n));
+            chromes.push((c.chroma, n));
             false
+        } else {
+            true
         }
     });
 
+    // a range that wraps around 360 continues past it, e.g. 330..390 instead of 330..30
+    let end = if color.hue_end < color.hue_start { color.hue_end + 360.0 } else { color.hue_end };
+
     //artificially make the color in between
     let hue = if hues.is_empty() {
         let mut fallback = vec![];
-        for i in color.hue_start as usize..color.hue_end as usize {
+        for i in color.hue_start as usize..end as usize {
             fallback.push(i as f32);
         }
         let x = avg(&fallback);
         //get half (avg) of the hue
-        let m = color.hue_start + color.hue_end / 2.0;
+        let m = (color.hue_start + end) / 2.0;
         //weighted ecuation
         (m + 2.0*x) / 3.0
+    } else if end > 360.0 {
+        // same for the hues found, so the average of 350 and 10 is 0, not 180
+        weighted_avg(hues.iter().map(|&(h, n)| if h < color.hue_start { (h + 360.0, n) } else { (h, n) }))
     } else {
-        avg(&hues)
+        wavg(&hues)
     };
 
     // C* is the colorfulness of the color. It’s similar to saturation. 0.0 gives gray
     // scale colors, and numbers around 128-181 gives fully saturated colors. The upper
     // limit of 128 should include the whole L*a*b* space and some more.
     let chroma = if chromes.is_empty() { color.chroma_def } else {
-        let a = avg(&chromes);
+        let a = wavg(&chromes);
         (color.chroma_def + 2.0*a) / 3.0
             // if a <= 64.0 {
             //     a + 30.0
@@ -231,7 +372,7 @@ fn get_colors(cols: &mut Vec<Spec>, color: ColSettings) -> Histo<Spec> {
     // L* is the lightness of the color. 0.0 gives absolute black and 100.0 gives the
     // brightest white.
     let light = if lights.is_empty() { color.light_def } else {
-        let a = avg(&lights);
+        let a = wavg(&lights);
         (color.light_def + 2.0*a) / 3.0
             // if a <= 10.0 {
             //     a + 30.0
@@ -244,3 +385,6 @@ fn get_colors(cols: &mut Vec<Spec>, color: ColSettings) -> Histo<Spec> {
     //println!("L {light} | c {chroma} | h {hue}");
     Histo::new_no_count(Spec::new(light, chroma, LabHue::new(hue)))
 }
+
+/// Average of the values, each one weighted by how many pixels have it.
+fn wavg(v: &[(f32, usize)]) -> f32 { weighted_avg(v.iter().copied()) }
@@ -18,12 +18,22 @@ use serde::{Serialize, Deserialize};
 use owo_colors::AnsiColors;
 use itertools::Itertools;
 pub use fallback_generator::FallbackGenerator;
+pub use weighting::Weighting;
+pub use scoring::Scoring;
+pub use lchansi::{Bucket, Buckets};
 
 pub mod rgb;
+pub mod weighting;
+pub mod scoring;
+pub mod ignore;
 
 mod lab;
 mod lch;
 mod lchansi;
+mod oklab;
+mod oklch;
+mod cam16;
+mod kmeans;
 mod util;
 mod fallback_generator;
 use fallback_generator::FallbackGenerator as G;
@@ -39,6 +49,7 @@ pub const MAX_COLS: u8 = 16;
 /// like to use (e.g. light scheme or dark scheme), since you got them as the first colors.
 /// Using these with [`full`] or [`resize`] backends, the LightFirst will give a more pastel
 /// colors. While the DarkFrist will give you more heavy ones (more hue ones)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ColorOrder {
     /// `colors[0]` will be the lightest, and `colors.last()` will be the darkest
     LightFirst,
@@ -77,6 +88,123 @@ pub enum ColorSpace {
     #[clap(alias = "lch-ansi", name = "lchansi")] //claps prefers this-name
     #[serde(alias = "lch-ansi")]
     LchAnsi,
+
+    /// OKLab, a more perceptually uniform L*a*b*, hues don't shift when mixing nor get too close
+    /// to be told apart.
+    #[clap(alias = "ok-lab", name = "oklab")] //claps prefers this-name
+    #[serde(alias = "ok-lab")]
+    OkLab,
+    /// Variant of `oklab` that mixes the colors gathered.
+    #[clap(alias = "oklab-mixed", name = "oklabmixed")] //claps prefers this-name
+    #[serde(alias = "oklab-mixed")]
+    OkLabMixed,
+    /// OKLCh, OKLab with chroma and hue, like `lch` is to `lab`.
+    #[clap(alias = "ok-lch", name = "oklch")] //claps prefers this-name
+    #[serde(alias = "ok-lch")]
+    OkLch,
+    /// Variant of `oklch` that mixes the colors gathered.
+    #[clap(alias = "oklch-mixed", name = "oklchmixed")] //claps prefers this-name
+    #[serde(alias = "oklch-mixed")]
+    OkLchMixed,
+
+    /// CAM16-UCS, from the CIE CAM16 color appearance model. Tells apart saturated colors that
+    /// `lab` would merge, good with vivid or HDR-like wallpapers.
+    #[clap(alias = "cam16-ucs", name = "cam16")] //claps prefers this-name
+    #[serde(alias = "cam16-ucs")]
+    Cam16,
+
+    /// Splits the colors in clusters with k-means++, in OKLab, without any threshold. As many
+    /// clusters as `color_count`, or 16, the same image always gives the same palette.
+    #[clap(alias = "k-means", name = "kmeans")] //claps prefers this-name
+    #[serde(alias = "k-means")]
+    KMeans,
+}
+
+/// Lightness and chroma limits of the colors gathered, see [`BuildHisto::filter_cols`]. Lightness
+/// goes from 0 to 100 and chroma is like CIE LCh's (up to ~130) in every colorspace, OKLab, OKLCh
+/// and CAM16 scale them to theirs. The ones not set keep the colorspace defaults. `lchansi` also takes its
+/// hue ranges, see [`Buckets`], and `grid` puts near colors together before comparing them, see
+/// [`GRID_BITS`].
+/// ```toml
+/// [color_space]
+/// name = "lch"
+/// darkest = 1
+/// lightest = 99
+/// min_chroma = 0
+/// grid = true
+/// ```
+#[derive(Debug, Default, Clone, Copy, PartialEq, Deserialize)]
+#[cfg_attr(feature = "schema" , derive(schemars::JsonSchema))]
+#[serde(default)]
+pub struct Bounds {
+    /// Colors darker than this are left out (default is 4.5)
+    pub darkest: Option<f32>,
+    /// Colors lighter than this are left out (default is 95.5)
+    pub lightest: Option<f32>,
+    /// Colors less colorful than this are left out. By default Lch leaves out the ones well below
+    /// the average chroma of the image, and Lab none.
+    pub min_chroma: Option<f32>,
+    /// Hue ranges of `lchansi`, see [`Buckets`]
+    pub buckets: Buckets,
+    /// Average the near colors on a grid before comparing them, a lot faster with big images, but
+    /// the colors found change slightly, see [`GRID_BITS`] (default is false)
+    pub grid: bool,
+}
+
+impl Bounds {
+    /// Every bound between 0 and 100, with `darkest` below `lightest`.
+    pub fn validate(&self) -> Result<(), String> {
+        for (name, v) in [("darkest", self.darkest), ("lightest", self.lightest), ("min_chroma", self.min_chroma)] {
+            if let Some(v) = v.filter(|v| !(0.0..=100.0).contains(v)) {
+                return Err(format!("{name} should be between 0 and 100, not {v}"));
+            }
+        }
+        match (self.darkest, self.lightest) {
+            (Some(d), Some(l)) if d >= l => return Err(format!("darkest ({d}) should be below lightest ({l})")),
+            _ => (),
+        }
+        self.buckets.validate()
+    }
+
+    /// The bounds that were set, part of the cache file name. Empty if there are none.
+    pub fn key(&self) -> String {
+        let mut ret = String::new();
+        if let Some(d) = self.darkest { ret.push_str(&format!("_d{d}")); }
+        if let Some(l) = self.lightest { ret.push_str(&format!("_l{l}")); }
+        if let Some(c) = self.min_chroma { ret.push_str(&format!("_c{c}")); }
+        if !self.buckets.is_empty() {
+            use crate::cache::{base36, fnv1a};
+            ret.push_str(&format!("_b{}", base36(fnv1a(format!("{:?}", self.buckets).as_bytes()))));
+        }
+        if self.grid { ret.push_str("_grid"); }
+        ret
+    }
+
+    /// Bits per channel [`read`] tells colors apart with, `8` keeps every one.
+    pub fn grid_bits(&self) -> u8 {
+        if self.grid { GRID_BITS } else { 8 }
+    }
+}
+
+/// The `color_space` key of the config file, which is either only the name, `color_space =
+/// "lch"`, or a `[color_space]` table with the name and the [`Bounds`].
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "schema" , derive(schemars::JsonSchema))]
+pub struct ColorSpaceTable {
+    /// Which colorspace to use
+    pub name: Option<ColorSpace>,
+    /// Lightness and chroma bounds
+    pub bounds: Bounds,
+}
+
+impl<'de> Deserialize<'de> for ColorSpaceTable {
+    fn deserialize<D: serde::Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        use serde::de::Error;
+
+        let (name, bounds): (_, Bounds) = crate::config::str_or_table(d, "a colorspace name or a [color_space] table", &["name", "darkest", "lightest", "min_chroma", "buckets", "grid"])?;
+        bounds.validate().map_err(D::Error::custom)?;
+        Ok(ColorSpaceTable { name, bounds })
+    }
 }
 
 /// Simple Histogram
@@ -96,15 +224,31 @@ impl<T: ColorTrait> Histo<T> {
     pub fn new_no_count(color: T) -> Self { Self { color, count: usize::MAX } }
 }
 
+/// Thresholds [`run_search`] looks at, the same range as [`run_dynamic`].
+pub const DYNAMIC_TH: (u8, u8) = (2, 44);
+
+/// Which threshold [`ColorSpace::run`] uses.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Threshold {
+    /// This one, see [`run_once`]
+    Fixed(u8),
+    /// The one that gives this many colors, see [`run_search`], or between [`MIN_COLS`] and
+    /// [`MAX_COLS`] with `None`, see [`run_dynamic`]
+    Auto(Option<u8>),
+}
+
 /// This a multithreaded function to look up for the best threshold that has the best palette color generation.
-pub fn run_dynamic<C: BuildHisto<U>, U: ColorTrait + std::marker::Send> (
+/// Also returns the threshold used.
+#[allow(clippy::too_many_arguments)]
+pub fn run_dynamic<C: BuildHisto<U>, U: ColorTrait + Send + Sync> (
     bytes: &[u8],
-    _threshold: u8,
     gen: &G,
     mix: bool,
     ord: &ColorOrder,
     dedup: bool,
-) -> Option<(Vec<Srgb>, Vec<Srgb>, bool)> {
+    bounds: &Bounds,
+    score: &Scoring,
+) -> Option<(Vec<Srgb>, Vec<Srgb>, bool, u8, Vec<usize>)> {
 
     use std::thread;
     use std::collections::HashMap;
@@ -115,6 +259,9 @@ pub fn run_dynamic<C: BuildHisto<U>, U: ColorTrait + std::marker::Send> (
     let mut fallback = false;
     let mut threshold = 20; //initial threshold
 
+    // converted (and filtered) only once, every threshold tried reuses them
+    let pixels = &C::pixels(bytes, bounds);
+
     let (txfinal, rxfinal) = mpsc::channel();
 
     thread::scope(|s| {
@@ -122,7 +269,7 @@ pub fn run_dynamic<C: BuildHisto<U>, U: ColorTrait + std::marker::Send> (
 
         // => This has to be a hardcoded tested allround value to avoid going to inifinity.
         // let max_threshold = 30;
-        let min_threshold = 2;
+        let min_threshold = DYNAMIC_TH.0;
 
         // The first element is going to be 0, this is to avoid `expect()` panicing
         // since, this hasmap will never be empty.
@@ -145,13 +292,13 @@ pub fn run_dynamic<C: BuildHisto<U>, U: ColorTrait + std::marker::Send> (
         // we go with a step of 2 because we are spawing 2 threads per loop
         // we go from 30 (threshold) to 2 (the minimun
         let th1 = idx[i];
-        let myread1 = s.spawn(move || C::init(bytes, th1, mix));
+        let myread1 = s.spawn(move || C::init_with(pixels, th1, mix, bounds));
 
         let th2 = idx[i+1];
-        let myread2 = s.spawn(move || C::init(bytes, th2, mix));
+        let myread2 = s.spawn(move || C::init_with(pixels, th2, mix, bounds));
 
         let th3 = idx[i+2];
-        let myread3 = s.spawn(move || C::init(bytes, th3, mix));
+        let myread3 = s.spawn(move || C::init_with(pixels, th3, mix, bounds));
 
 
         // since we go by a step of 2, check the threads that already have runed
@@ -163,7 +310,7 @@ pub fn run_dynamic<C: BuildHisto<U>, U: ColorTrait + std::marker::Send> (
             // There are colors! This threshold works.
             Some(s) => {
 
-                let s = if dedup { C::dedup_cols(s, threshold) } else { s };
+                let s = if dedup { C::dedup_cols(s, threshold, score) } else { s };
                 let len = s.len();
                 // enough colors, end
                 // we handle here before deduping in case is not needed
@@ -230,19 +377,108 @@ pub fn run_dynamic<C: BuildHisto<U>, U: ColorTrait + std::marker::Send> (
 
     if len == 2 {
         warn = true;
-        histo = C::fallback_monochromatic(histo, gen);
+        histo = C::fallback_monochromatic(histo, gen, score);
     } else if fallback || len < MIN_COLS.into() {
         warn = true;
-        histo = C::fallback(histo, threshold, gen);
+        histo = C::fallback(histo, threshold, gen, bounds, score);
     }
 
     let orig = C::to_rgb(&histo);
+    let counts = histo.iter().map(|h| h.count).collect();
     let top  = C::sort_col(histo, ord);
     let top  = C::to_rgb(&top);
 
-    Some( (top, orig, warn) )
+    Some( (top, orig, warn, threshold, counts) )
 }
 
+/// Looks for the threshold that gives `target` colors. The higher the threshold, the less colors
+/// there are, so this is a binary search that tries three thresholds at once, one per thread,
+/// splitting the range in four every step. If none gives exactly that many colors, the closest one
+/// is used. Returns the threshold as well.
+#[allow(clippy::too_many_arguments)]
+pub fn run_search<C: BuildHisto<U>, U: ColorTrait + Send + Sync> (
+    bytes: &[u8],
+    target: u8,
+    gen: &G,
+    mix: bool,
+    ord: &ColorOrder,
+    dedup: bool,
+    bounds: &Bounds,
+    score: &Scoring,
+) -> Option<(Vec<Srgb>, Vec<Srgb>, bool, u8, Vec<usize>)> {
+    use std::thread;
+    use std::cmp::Reverse;
+
+    let target = usize::from(target);
+    // how far from the target, and then, more colors are better than less
+    let rank = |len: usize| (len.abs_diff(target), Reverse(len));
+
+    // converted (and filtered) only once, every threshold tried reuses them
+    let pixels = &C::pixels(bytes, bounds);
+
+    let (mut lo, mut hi) = DYNAMIC_TH;
+    let mut best: Option<(u8, Vec<Histo<U>>)> = None;
+
+    'search: while lo <= hi {
+        let mut probes = vec![lo + (hi - lo) / 4, lo + (hi - lo) / 2, hi - (hi - lo) / 4];
+        probes.dedup();
+
+        let found = thread::scope(|s| {
+            probes.iter()
+                .map(|&th| s.spawn(move || (th, C::init_with(pixels, th, mix, bounds))))
+                .collect::<Vec<_>>()
+                .into_iter()
+                .map(|t| t.join().expect("Waiting for the thread."))
+                .collect::<Vec<_>>()
+        });
+
+        // from the lowest threshold (most colors) to the highest
+        for (th, histo) in found {
+            let histo = match histo {
+                Some(s) if dedup => C::dedup_cols(s, th, score),
+                Some(s) => s,
+                None => vec![],
+            };
+            let len = histo.len();
+
+            if best.as_ref().is_none_or(|(_, b)| rank(len) < rank(b.len())) {
+                best = Some((th, histo));
+            }
+
+            if len > target {
+                lo = th + 1;
+            } else if len < target {
+                hi = th - 1;
+                break;
+            } else {
+                break 'search;
+            }
+        }
+    }
+
+    let (threshold, mut histo) = best?;
+    let mut warn = false;
+    let len = histo.len();
+
+    if len < 2 { return None }
+
+    if len == 2 {
+        warn = true;
+        histo = C::fallback_monochromatic(histo, gen, score);
+    } else if len < MIN_COLS.into() {
+        warn = true;
+        histo = C::fallback(histo, threshold, gen, bounds, score);
+    }
+
+    let orig = C::to_rgb(&histo);
+    let counts = histo.iter().map(|h| h.count).collect();
+    let top  = C::sort_col(histo, ord);
+    let top  = C::to_rgb(&top);
+
+    Some( (top, orig, warn, threshold, counts) )
+}
+
+#[allow(clippy::too_many_arguments)]
 pub fn run_once<C: BuildHisto<U>, U: ColorTrait>(
     bytes: &[u8],
     threshold: u8,
@@ -250,14 +486,16 @@ pub fn run_once<C: BuildHisto<U>, U: ColorTrait>(
     mix: bool,
     ord: &ColorOrder,
     dedup: bool,
-) -> Option<(Vec<Srgb>, Vec<Srgb>, bool)> {
+    bounds: &Bounds,
+    score: &Scoring,
+) -> Option<(Vec<Srgb>, Vec<Srgb>, bool, u8, Vec<usize>)> {
 
     let mut warn = false;
 
-    let ret = match C::init(bytes, threshold, mix) {
+    let ret = match C::init(bytes, threshold, mix, bounds) {
         Some(s) => {
             let s = if dedup {
-                C::dedup_cols(s, threshold)
+                C::dedup_cols(s, threshold, score)
             } else {
                 s
             };
@@ -267,10 +505,10 @@ pub fn run_once<C: BuildHisto<U>, U: ColorTrait>(
 
             if len == 2 { //exactly two colors
                 warn = true;
-                Some(C::fallback_monochromatic(s, gen))
+                Some(C::fallback_monochromatic(s, gen, score))
             } else if len < MIN_COLS.into() { // less than MIN_COLs, requires fallback
                 warn = true;
-                Some(C::fallback(s, threshold, gen))
+                Some(C::fallback(s, threshold, gen, bounds, score))
             } else if len < 2 { // one color?
                 warn = true;
                 None
@@ -288,22 +526,52 @@ pub fn run_once<C: BuildHisto<U>, U: ColorTrait>(
     };
 
     let orig = C::to_rgb(&ret);
+    let counts = ret.iter().map(|h| h.count).collect();
     let top  = C::sort_col(ret, ord);
     let top  = C::to_rgb(&top);
 
-    Some( (top, orig, warn) )
+    Some( (top, orig, warn, threshold, counts) )
+}
+
+/// [`run_dynamic`] without a `target`, [`run_search`] with it.
+#[allow(clippy::too_many_arguments)]
+fn dynamic<C: BuildHisto<U>, U: ColorTrait + Send + Sync>(
+    bytes: &[u8],
+    target: Option<u8>,
+    gen: &G,
+    mix: bool,
+    ord: &ColorOrder,
+    dedup: bool,
+    bounds: &Bounds,
+    score: &Scoring,
+) -> Option<(Vec<Srgb>, Vec<Srgb>, bool, u8, Vec<usize>)> {
+    match target {
+        Some(n) => run_search::<C, U>(bytes, n, gen, mix, ord, dedup, bounds, score),
+        None => run_dynamic::<C, U>(bytes, gen, mix, ord, dedup, bounds, score),
+    }
+}
+
+/// See [`ColorSpace::histogram`].
+fn histogram<C: BuildHisto<U>, U: ColorTrait>(bytes: &[u8], threshold: u8, mix: bool, bits: Option<u8>, bounds: &Bounds) -> usize {
+    let cols = match bits {
+        Some(b) => read(bytes, b),
+        None => per_pixel(bytes),
+    };
+    C::gather_cols(&C::filter_cols(cols, bounds), threshold, mix, bounds).len()
 }
 
 impl ColorSpace {
-    /// main function from ColorSpace, uses a respective dynamic or manual function
-    pub fn run(&self, dynamic: bool, bytes_rgb8: &[u8], threshold: u8, gen: &G, ord: &ColorOrder) -> Option<(Vec<Srgb>, Vec<Srgb>, bool)> {
-        match dynamic {
-            true  => self.run_dynamic(bytes_rgb8, threshold, gen, ord),
-            false => self.run_once   (bytes_rgb8, threshold, gen, ord),
+    /// main function from ColorSpace, uses a respective dynamic or manual function. Also returns
+    /// the threshold used, and how many pixels went to each color of `orig`, see [`Histo`].
+    /// `score` ranks the colors, see [`Scoring`].
+    pub fn run(&self, th: Threshold, bytes_rgb8: &[u8], gen: &G, ord: &ColorOrder, bounds: &Bounds, score: &Scoring) -> Option<(Vec<Srgb>, Vec<Srgb>, bool, u8, Vec<usize>)> {
+        match th {
+            Threshold::Auto(target) => self.run_dynamic(bytes_rgb8, target, gen, ord, bounds, score),
+            Threshold::Fixed(t)     => self.run_once   (bytes_rgb8, t, gen, ord, bounds, score),
         }
     }
 
-    pub fn run_once(&self, bytes_rgb8: &[u8], threshold: u8 /* dummy */, gen: &G, ord: &ColorOrder) -> Option<(Vec<Srgb>, Vec<Srgb>, bool)> {
+    pub fn run_once(&self, bytes_rgb8: &[u8], threshold: u8, gen: &G, ord: &ColorOrder, bounds: &Bounds, score: &Scoring) -> Option<(Vec<Srgb>, Vec<Srgb>, bool, u8, Vec<usize>)> {
         let mix = self.mixed();
         let dedup = self.to_dedup();
 
@@ -314,42 +582,98 @@ impl ColorSpace {
             Cs::Lch => run_once::<lch::Lch, lch::Spec>,
             Cs::LchMixed => run_once::<lch::Lch, lch::Spec>,
             Cs::LchAnsi => run_once::<lchansi::LchAnsi, lch::Spec>,
+
+            Cs::OkLab => run_once::<oklab::OkLab, oklab::Spec>,
+            Cs::OkLabMixed => run_once::<oklab::OkLab, oklab::Spec>,
+
+            Cs::OkLch => run_once::<oklch::OkLch, oklch::Spec>,
+            Cs::OkLchMixed => run_once::<oklch::OkLch, oklch::Spec>,
+
+            Cs::Cam16 => run_once::<cam16::Cam16, cam16::Spec>,
+
+            // no threshold, see [`kmeans::run`]
+            Cs::KMeans => return kmeans::run(bytes_rgb8, None, gen, ord, bounds, score),
         };
 
-        f(bytes_rgb8, threshold, gen, mix, ord, dedup)
+        f(bytes_rgb8, threshold, gen, mix, ord, dedup, bounds, score)
 
     }
 
-    pub fn run_dynamic(&self, bytes_rgb8: &[u8], threshold: u8, gen: &G, ord: &ColorOrder) -> Option<(Vec<Srgb>, Vec<Srgb>, bool)> {
+    /// [`run_dynamic`], or [`run_search`] when there's a `target` count of colors.
+    pub fn run_dynamic(&self, bytes_rgb8: &[u8], target: Option<u8>, gen: &G, ord: &ColorOrder, bounds: &Bounds, score: &Scoring) -> Option<(Vec<Srgb>, Vec<Srgb>, bool, u8, Vec<usize>)> {
         let mix = self.mixed();
         let dedup = self.to_dedup();
 
-        match self {
-            Cs::Lab => run_dynamic::<lab::Lab, lab::Spec>(bytes_rgb8, threshold, gen, mix, ord, dedup),
-            Cs::LabMixed => run_dynamic::<lab::Lab, lab::Spec>(bytes_rgb8, threshold, gen, mix, ord, dedup),
+        let f = match self {
+            Cs::Lab => dynamic::<lab::Lab, lab::Spec>,
+            Cs::LabMixed => dynamic::<lab::Lab, lab::Spec>,
 
-            Cs::Lch => run_dynamic::<lch::Lch, lch::Spec>(bytes_rgb8, threshold, gen, mix, ord, dedup),
-            Cs::LchMixed => run_dynamic::<lch::Lch, lch::Spec>(bytes_rgb8, threshold, gen, mix, ord, dedup),
-            Cs::LchAnsi => run_dynamic::<lchansi::LchAnsi, lch::Spec>(bytes_rgb8, threshold, gen, mix, ord, dedup),
-        }
+            Cs::Lch => dynamic::<lch::Lch, lch::Spec>,
+            Cs::LchMixed => dynamic::<lch::Lch, lch::Spec>,
+            Cs::LchAnsi => dynamic::<lchansi::LchAnsi, lch::Spec>,
+
+            Cs::OkLab => dynamic::<oklab::OkLab, oklab::Spec>,
+            Cs::OkLabMixed => dynamic::<oklab::OkLab, oklab::Spec>,
+
+            Cs::OkLch => dynamic::<oklch::OkLch, oklch::Spec>,
+            Cs::OkLchMixed => dynamic::<oklch::OkLch, oklch::Spec>,
+
+            Cs::Cam16 => dynamic::<cam16::Cam16, cam16::Spec>,
+
+            Cs::KMeans => return kmeans::run(bytes_rgb8, target, gen, ord, bounds, score),
+        };
+
+        f(bytes_rgb8, target, gen, mix, ord, dedup, bounds, score)
+    }
+
+    /// How many colors the histogram of `bytes` gathers with `threshold`, the image read on a grid
+    /// of `bits` per channel, see [`GRID_BITS`], or every pixel on its own with `None`, like before
+    /// [`read`] counted each color once. Only the stage the grid speeds up, for the benches.
+    #[doc(hidden)]
+    pub fn histogram(&self, bytes_rgb8: &[u8], threshold: u8, bits: Option<u8>, bounds: &Bounds) -> usize {
+        let mix = self.mixed();
+
+        let f = match self {
+            Cs::Lab | Cs::LabMixed => histogram::<lab::Lab, lab::Spec>,
+            Cs::Lch | Cs::LchMixed => histogram::<lch::Lch, lch::Spec>,
+            Cs::LchAnsi => histogram::<lchansi::LchAnsi, lch::Spec>,
+            Cs::OkLab | Cs::OkLabMixed => histogram::<oklab::OkLab, oklab::Spec>,
+            Cs::OkLch | Cs::OkLchMixed => histogram::<oklch::OkLch, oklch::Spec>,
+            Cs::Cam16 => histogram::<cam16::Cam16, cam16::Spec>,
+            Cs::KMeans => {
+                let cols = match bits {
+                    Some(b) => read(bytes_rgb8, b),
+                    None => per_pixel(bytes_rgb8),
+                };
+                return kmeans::cluster(&kmeans::KMeans::filter_cols(cols, bounds), MAX_COLS.into()).len();
+            },
+        };
+
+        f(bytes_rgb8, threshold, mix, bits, bounds)
     }
 
     /// XXX just use matches!
     pub fn mixed(&self) -> bool {
         match self {
-            Cs::LabMixed | Cs::LchMixed  => true,
-            Cs::Lch | Cs::Lab | Cs::LchAnsi => false,
+            Cs::LabMixed | Cs::LchMixed | Cs::OkLabMixed | Cs::OkLchMixed => true,
+            Cs::Lch | Cs::Lab | Cs::LchAnsi | Cs::OkLab | Cs::OkLch | Cs::Cam16 | Cs::KMeans => false,
         }
     }
 
-    /// Only LCHANSI requires to preserve it's order, no deduping!
+    /// Only LCHANSI requires to preserve it's order, no deduping! KMEANS doesn't have a threshold.
     pub fn to_dedup(&self) -> bool {
         match self {
-            Cs::LabMixed | Cs::LchMixed | Cs::Lch | Cs::Lab => true,
-            Cs::LchAnsi => false,
+            Cs::LabMixed | Cs::LchMixed | Cs::Lch | Cs::Lab
+            | Cs::OkLab | Cs::OkLabMixed | Cs::OkLch | Cs::OkLchMixed | Cs::Cam16 => true,
+            Cs::LchAnsi | Cs::KMeans => false,
         }
     }
 
+    /// LCHANSI keeps it's ansi order, without sorting by the [`ColorOrder`].
+    pub fn sorts(&self) -> bool {
+        !matches!(self, Cs::LchAnsi)
+    }
+
     /// Assign a color for the ColorSpace
     pub fn col(&self) -> AnsiColors {
         match self {
@@ -358,6 +682,12 @@ impl ColorSpace {
             Cs::Lch => AnsiColors::Magenta,
             Cs::LchMixed => AnsiColors::Magenta,
             Cs::LchAnsi => AnsiColors::Cyan,
+            Cs::OkLab => AnsiColors::BrightBlue,
+            Cs::OkLabMixed => AnsiColors::BrightGreen,
+            Cs::OkLch => AnsiColors::BrightMagenta,
+            Cs::OkLchMixed => AnsiColors::BrightMagenta,
+            Cs::Cam16 => AnsiColors::Yellow,
+            Cs::KMeans => AnsiColors::BrightCyan,
         }
     }
 }
@@ -410,15 +740,25 @@ pub trait ColorTrait:
 
 pub trait BuildHisto<C: ColorTrait> {
     /// If this fails, then there are less than 2 colors.
-    fn init(bytes: &[u8], threshold: u8, mix: bool) -> Option<Vec<Histo<C>>> {
-        let b = Self::read(bytes);
-        //let b = Self::additional(self, b);
-        let ret = Self::gather_cols(b, threshold, mix);
+    fn init(bytes: &[u8], threshold: u8, mix: bool, bounds: &Bounds) -> Option<Vec<Histo<C>>> {
+        Self::init_with(&Self::pixels(bytes, bounds), threshold, mix, bounds)
+    }
+
+    /// Like [`BuildHisto::init`], but with the [`BuildHisto::pixels`] already read, so trying
+    /// many thresholds doesn't convert them every time.
+    fn init_with(pixels: &[Histo<C>], threshold: u8, mix: bool, bounds: &Bounds) -> Option<Vec<Histo<C>>> {
+        let ret = Self::gather_cols(pixels, threshold, mix, bounds);
         if ret.len() < 2 { None } else { Some(ret) }
     }
 
+    /// The colors of the image, each one with the number of pixels that have it, without the ones
+    /// [`BuildHisto::filter_cols`] leaves out.
+    fn pixels(bytes: &[u8], bounds: &Bounds) -> Vec<Histo<C>> {
+        Self::filter_cols(Self::read(bytes, bounds.grid_bits()), bounds)
+    }
+
     /// If this fails, just quit. Here we try to artificially generate colors.
-    fn fallback(histo: Vec<Histo<C>>, threshold: u8, gen: &G) -> Vec<Histo<C>> {
+    fn fallback(histo: Vec<Histo<C>>, threshold: u8, gen: &G, bounds: &Bounds, score: &Scoring) -> Vec<Histo<C>> {
         let mut histo = histo;
         // Artificially generate colors with linear interpolation in between the colors that we already
         // have. However even this can even fail and not generate enough different colors, so there is
@@ -426,12 +766,12 @@ pub trait BuildHisto<C: ColorTrait> {
 
         // fallback_generator
         // XXX Is this really necesary with the new "automatic handling of the threshold?"
-        let mut new = Self::color_generator(&histo, threshold, gen);
+        let mut new = Self::color_generator(&histo, threshold, gen, bounds);
 
         histo.append(&mut new);
 
-        // sort vec by count, most used colors first (if they are more than the MAX)
-        histo.sort_by(|a, b| b.count.cmp(&a.count));
+        // best colors first (if they are more than the MAX), see [`Scoring`]
+        score.sort(&mut histo);
 
         // take the *necessary* most used colors
         histo.truncate(MAX_COLS.into());
@@ -439,7 +779,7 @@ pub trait BuildHisto<C: ColorTrait> {
     }
 
     /// No need for a threshold, since here we only got 2 colors.
-    fn fallback_monochromatic(histo: Vec<Histo<C>>, gen: &G) -> Vec<Histo<C>> {
+    fn fallback_monochromatic(histo: Vec<Histo<C>>, gen: &G, score: &Scoring) -> Vec<Histo<C>> {
         let mut histo = histo;
         let mut new = gen.gen()(histo[0].color.into_color(), histo[1].color.into_color(), MIN_COLS)
             .iter()
@@ -451,8 +791,8 @@ pub trait BuildHisto<C: ColorTrait> {
 
         histo.append(&mut new);
 
-        // sort vec by count, most used colors first (if they are more than the MAX)
-        histo.sort_by(|a, b| b.count.cmp(&a.count));
+        // best colors first (if they are more than the MAX), see [`Scoring`]
+        score.sort(&mut histo);
 
         // take the *necessary* most used colors
         histo.truncate(MAX_COLS.into());
@@ -466,7 +806,7 @@ pub trait BuildHisto<C: ColorTrait> {
     ///    similar colors, not resulting in an stable palette. By using these two methods below, we
     ///    'asure' (lazyly) to have no duplicates, and thus, the benefit of 'more colors' won't
     ///    imply 'bad scheme'.
-    fn dedup_cols(histo: Vec<Histo<C>>, threshold: u8) -> Vec<Histo<C>> {
+    fn dedup_cols(histo: Vec<Histo<C>>, threshold: u8, score: &Scoring) -> Vec<Histo<C>> {
         let mut histo = histo;
 
         // histo.sort_by_key(|e| (e.color.l as u32, e.color.a as i32, e.color.b as i32));
@@ -482,19 +822,19 @@ pub trait BuildHisto<C: ColorTrait> {
             .dedup_by_with_count(|a, b| a.color.col_diff(&b.color, threshold))
             .for_each(|x| x.1.count += x.0);
 
-        // sort vec by count, most used colors first
-        histo.sort_by(|a, b| b.count.cmp(&a.count));
+        // best colors first, by count unless another [`Scoring`] is chosen
+        score.sort(&mut histo);
 
         // remove excess elements
         histo.truncate(MAX_COLS.into());
         histo
     }
 
-    /// Function that read the image rgb8 bytes and converts them into it's colorspace
-    fn read(bytes: &[u8]) -> Vec<C> { read(bytes) }
+    /// Function that read the image rgb8 bytes and converts them into it's colorspace, see [`read`]
+    fn read(bytes: &[u8], bits: u8) -> Vec<Histo<C>> { read(bytes, bits) }
 
-    // What colors to avoid before adding. e.g. too dark/light
-    fn filter_cols(histo: Vec<C>) -> Vec<C>;
+    // What colors to avoid before adding. e.g. too dark/light, see [`Bounds`]
+    fn filter_cols(histo: Vec<Histo<C>>, bounds: &Bounds) -> Vec<Histo<C>>;
 
     /// Simple Sort algo that determines how to order colors
     /// usecase: `histo.sort_by(|a, b| color_ord.sort_algo(a, b))`
@@ -508,7 +848,7 @@ pub trait BuildHisto<C: ColorTrait> {
     /// This is how we try to artificially generate colors when there are not at least [`MIN_COLS`].
     /// `pred` is for gather_cols() and `method` indicates how the colors are gonna be filled.
     /// This was called 'new_colors()' (generates a new Vec of Histograms)
-    fn color_generator(histo: &[Histo<C>], threshold: u8, gen: &G) -> Vec<Histo<C>> {
+    fn color_generator(histo: &[Histo<C>], threshold: u8, gen: &G, bounds: &Bounds) -> Vec<Histo<C>> {
         let mut new_cols = vec![];
         // try to generate new colors with interpolation in between the already gathered colors
         for comb in histo.iter().combinations(2) {
@@ -516,12 +856,12 @@ pub trait BuildHisto<C: ColorTrait> {
             let color_b: Srgb = comb[1].color.into_color();
 
             let rgbs = gen.gen()(color_a, color_b, MAX_COLS)
-                .iter().map(|&x| x.into_color()).collect();
+                .iter().map(|&x| Histo::new(x.into_color(), 1)).collect();
 
             //similar to how it's done at the start of `lab()`
             // save the new colors, or discard them if similar enough
             // no more color mixing, we don't have much colors left.
-            new_cols.append(&mut Self::gather_cols(rgbs, threshold, false));
+            new_cols.append(&mut Self::gather_cols(&Self::filter_cols(rgbs, bounds), threshold, false, bounds));
 
             let len = histo.len() + new_cols.len();
 
@@ -531,27 +871,28 @@ pub trait BuildHisto<C: ColorTrait> {
         new_cols
     }
 
-    /// This is a generic way of creating a histogram.
-    fn gather_cols(colors: Vec<C>, threshold: u8, mix: bool) -> Vec<Histo<C>> {
+    /// This is a generic way of creating a histogram, out of the (already filtered) colors of the
+    /// image and how many pixels have each one.
+    fn gather_cols(colors: &[Histo<C>], threshold: u8, mix: bool, _bounds: &Bounds) -> Vec<Histo<C>> {
         let mut histogram: Vec<Histo<C>> = vec![];
-        let colors: Vec<C> = Self::filter_cols(colors);
 
         'outter: for c in colors {
             // Check if whether the color is new or is already in the vec
             for hist in &mut histogram {
                 // if any color is between a threshold, count it up
-                if c.col_diff(&hist.color, threshold) {
-                    if mix { hist.color = hist.color.mix(c, 0.5); }
-                    hist.count += 1;
+                if c.color.col_diff(&hist.color, threshold) {
+                    // like mixing it once per pixel, halving what's left every time
+                    if mix { hist.color = hist.color.mix(c.color, 1.0 - 0.5_f32.powi(c.count.min(32) as i32)); }
+                    hist.count += c.count;
                     continue 'outter;
                 }
             }
             // if we reach here, the color hasn't been found in the histrogram,
             // so we found a new color.
-            histogram.push(Histo { color: c, count: 1 });
+            histogram.push(*c);
         }
 
-        histogram.into()
+        histogram
     }
 
     fn to_rgb(histo: &[Histo<C>]) -> Vec<Srgb> { histo.iter().map(|x| x.color.into_color()).collect() }
@@ -566,17 +907,86 @@ impl fmt::Display for Cs {
             Cs::Lch => write!(f, "Lch"),
             Cs::LchMixed => write!(f, "LchMixed"),
             Cs::LchAnsi => write!(f, "LchAnsi"),
+            Cs::OkLab => write!(f, "OkLab"),
+            Cs::OkLabMixed => write!(f, "OkLabMixed"),
+            Cs::OkLch => write!(f, "OkLch"),
+            Cs::OkLchMixed => write!(f, "OkLchMixed"),
+            Cs::Cam16 => write!(f, "Cam16"),
+            Cs::KMeans => write!(f, "KMeans"),
         }
     }
 }
 
 /* generic impl */
 
-/// Function that read the image rgb8 bytes and converts them into it's colorspace
-fn read<T: ColorTrait>(bytes: &[u8]) -> Vec<T> {
+/// Bits of every rgb8 channel [`read`] keeps to tell colors apart with [`Bounds::grid`]: near
+/// colors, the ones that only differ in the lower bits, are put together in the same cell of this
+/// grid, a lot less than what any threshold merges. `8` keeps every color apart, the default.
+///
+/// The average of a cell replaces its colors, so the palette can move slightly (a few units of
+/// each channel) and a threshold right at the edge between two colors may merge them differently.
+pub const GRID_BITS: u8 = 6;
+
+/// The colors of the rgb8 `bytes`, in the order they first show up and with how many pixels have
+/// each one, converted into the colorspace. Images repeat their colors a lot (weighting repeats
+/// pixels on purpose), and have plenty of near ones (grain, gradients), so first every distinct
+/// color is counted, and then, with less than 8 `bits` per channel, the ones in the same cell of
+/// that grid are averaged, see [`GRID_BITS`]. This way, each cell is converted, and later
+/// compared, only once.
+fn read<T: ColorTrait>(bytes: &[u8], bits: u8) -> Vec<Histo<T>> {
+    use std::collections::hash_map::{Entry, HashMap};
+    use palette::LinSrgb;
+
+    let s: &[Srgb<u8>] = bytes.components_as();
+    let mut index: HashMap<u32, usize, util::PixelHash> = HashMap::default();
+    let mut uniq: Vec<(Srgb<u8>, usize)> = vec![];
+
+    for x in s {
+        match index.entry(u32::from_be_bytes([0, x.red, x.green, x.blue])) {
+            Entry::Occupied(e) => uniq[*e.get()].1 += 1,
+            Entry::Vacant(e) => {
+                e.insert(uniq.len());
+                uniq.push((*x, 1));
+            },
+        }
+    }
+
+    if bits >= 8 {
+        return uniq
+            .into_iter()
+            .map(|(x, n)| Histo::new(x.into_linear().into_color(), n))
+            .collect();
+    }
+
+    // the sum of the (linear) colors in every cell, and how many pixels fell in it
+    let shift = 8 - bits;
+    index.clear();
+    let mut cells: Vec<(LinSrgb, usize)> = vec![];
+
+    for (x, n) in uniq {
+        let lin = x.into_linear::<f32>() * n as f32;
+        match index.entry(u32::from_be_bytes([0, x.red >> shift, x.green >> shift, x.blue >> shift])) {
+            Entry::Occupied(e) => {
+                let c = &mut cells[*e.get()];
+                c.0 += lin;
+                c.1 += n;
+            },
+            Entry::Vacant(e) => {
+                e.insert(cells.len());
+                cells.push((lin, n));
+            },
+        }
+    }
+
+    cells
+        .into_iter()
+        .map(|(sum, n)| Histo::new((sum / n as f32).into_color(), n))
+        .collect()
+}
+
+/// Every pixel of the rgb8 `bytes` converted on its own, repeated colors included. How the colors
+/// were read before [`read`], kept only to compare them in the benches.
+fn per_pixel<T: ColorTrait>(bytes: &[u8]) -> Vec<Histo<T>> {
     let s: &[Srgb<u8>] = bytes.components_as();
-    s
-        .iter()
-        .map(|x| x.into_linear().into_color())
-        .collect::<Vec<T>>()
+    s.iter().map(|x| Histo::new(x.into_linear().into_color(), 1)).collect()
 }
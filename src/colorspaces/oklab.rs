@@ -0,0 +1,65 @@
+//! # OKLab
+//! A perceptual colorspace like CIE L*a*b*, but with a more uniform hue and lightness, so
+//! blues don't shift to purple when mixing and a plain euclidean distance is enough as deltaE.
+//! - ref: <https://bottosson.github.io/posts/oklab/>
+use super::*;
+
+pub struct OkLab;
+
+/// Shadow the colorspace type (Spectrum)
+pub type Spec = palette::Oklab;
+
+/// Minimum OKLab lightness (L, from 0 to 1) required for a color to be accepted, about the same as lab's 4.5
+pub const DARKEST: f32 = 0.17;
+
+/// Maximum OKLab lightness (L, from 0 to 1) allowed for a color to be accepted, about the same as lab's 95.5
+pub const LIGHTEST: f32 = 0.96;
+
+/// OKLab chroma (up to ~0.37) per CIE LCh chroma (up to ~130), to scale [`Bounds::min_chroma`]
+pub const CHROMA: f32 = 0.003;
+
+/// [`Bounds`] lightness from 0 to 100, or the defaults, as OKLab's 0 to 1
+pub fn lightness(bounds: &Bounds) -> (f32, f32) {
+    (bounds.darkest.map_or(DARKEST, |d| d / 100.0), bounds.lightest.map_or(LIGHTEST, |l| l / 100.0))
+}
+
+impl ColorTrait for Spec {}
+
+impl Difference for Spec {
+    /// Distances in OKLab go from 0 to ~1, scale them so the threshold means roughly the same as
+    /// with CIEDE2000.
+    fn col_diff(&self, a: &Self, threshold: u8) -> bool {
+        use palette::color_difference::EuclideanDistance;
+        self.distance(*a) * 100.0 <= f32::from(threshold)
+    }
+}
+
+impl BuildHisto<Spec> for OkLab {
+    /// Only by lightness, unless there is a `min_chroma`.
+    fn filter_cols(histo: Vec<Histo<Spec>>, bounds: &Bounds) -> Vec<Histo<Spec>> {
+        let (dark, light) = lightness(bounds);
+        let lights = histo.iter().map(|c| c.color.l).collect::<Vec<_>>();
+        let darkest  = lights.iter().fold(f32::INFINITY, |a, &b| a.min(b)).max(dark);
+        let lightest = lights.iter().fold(f32::NEG_INFINITY, |a, &b| a.max(b)).min(light);
+        let ch = bounds.min_chroma.unwrap_or(0.0) * CHROMA;
+
+        let filt = |x: &Spec| x.l >= darkest && x.l <= lightest && x.a.hypot(x.b) >= ch;
+
+        histo.into_iter().filter(|c| filt(&c.color)).collect()
+    }
+
+    fn sort_col(histo: Vec<Histo<Spec>>, cs: &ColorOrder) -> Vec<Histo<Spec>> {
+        let mut histo = histo;
+
+        histo.sort_by(|a, b| match cs {
+            ColorOrder::LightFirst => b.color.l.partial_cmp(&a.color.l).unwrap_or(std::cmp::Ordering::Equal),
+            ColorOrder::DarkFirst  => a.color.l.partial_cmp(&b.color.l).unwrap_or(std::cmp::Ordering::Equal),
+        });
+
+        histo
+    }
+
+    fn sort_by_key_fn(a: Histo<Spec>) -> impl Ord {
+        ((a.color.l * 100.0) as u32, (a.color.a * 100.0) as i32, (a.color.b * 100.0) as i32)
+    }
+}
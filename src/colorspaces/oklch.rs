@@ -0,0 +1,62 @@
+//! # OKLCh
+//! OKLab in polar coordinates (lightness, chroma and hue), what [`super::lch`] is to CIE L*a*b*.
+//! ref: <https://docs.rs/palette/latest/palette/oklch/struct.Oklch.html>
+use super::*;
+
+/// The OKLCh struct
+#[derive(Debug)]
+pub struct OkLch;
+
+pub type Spec = palette::Oklch;
+
+/// Simple shadow to avoid repetition
+pub type Hist = Histo<Spec>;
+
+use super::oklab::{lightness, CHROMA};
+
+/// Same as [`super::lch::MIN_CHROMA`], OKLCh chroma only goes up to ~0.37
+pub const MIN_CHROMA: f32 = 0.03;
+
+impl ColorTrait for Spec {}
+
+impl Difference for Spec {
+    /// Hue is an angle, so compare them as OKLab
+    fn col_diff(&self, a: &Self, threshold: u8) -> bool {
+        let (x, y): (palette::Oklab, palette::Oklab) = ((*self).into_color(), (*a).into_color());
+        x.col_diff(&y, threshold)
+    }
+}
+
+impl BuildHisto<Spec> for OkLch {
+    /// Same as with Lch, leave out the extremes and the colors with less than the average chroma.
+    fn filter_cols(histo: Vec<Histo<Spec>>, bounds: &Bounds) -> Vec<Histo<Spec>> {
+        let (dark, light) = lightness(bounds);
+        let lights = histo.iter().map(|c| c.color.l).collect::<Vec<_>>();
+        let darkest  = lights.iter().fold(f32::INFINITY, |a, &b| a.min(b)).max(dark);
+        let lightest = lights.iter().fold(f32::NEG_INFINITY, |a, &b| a.max(b)).min(light);
+
+        let origch = util::weighted_avg(histo.iter().map(|c| (c.color.chroma, c.count)));
+        let lessch  = histo.iter().fold(f32::INFINITY, |a, b| a.min(b.color.chroma));
+        let ch = if origch <= MIN_CHROMA { lessch } else { origch / 2.5 };
+        let ch = bounds.min_chroma.map_or(ch, |c| c * CHROMA);
+
+        let filt = |x: &Spec| (x.l >= darkest && x.l <= lightest) && x.chroma >= ch;
+
+        histo.into_iter().filter(|c| filt(&c.color)).collect()
+    }
+
+    fn sort_col(histo: Vec<Hist>, cs: &ColorOrder) -> Vec<Hist> {
+        let mut histo = histo;
+        use std::cmp::Ordering;
+
+        histo.sort_by(|a, b| match cs {
+            ColorOrder::LightFirst => (b.color.l, a.color.chroma).partial_cmp(&(a.color.l, b.color.chroma)).unwrap_or(Ordering::Equal),
+            ColorOrder::DarkFirst  => (a.color.l, b.color.chroma).partial_cmp(&(b.color.l, a.color.chroma)).unwrap_or(Ordering::Equal),
+        });
+        histo
+    }
+
+    fn sort_by_key_fn(a: Hist) -> impl Ord {
+        (a.color.chroma * 100.0) as i32
+    }
+}
@@ -0,0 +1,96 @@
+//! # Scoring
+//! Which colors make it into the palette, once [`super::BuildHisto::dedup_cols`] merged the
+//! similar ones, is decided by ranking them and keeping the first [`super::MAX_COLS`]. By `count`
+//! alone, big desaturated areas (skies, walls, shadows) always win and vivid accents are left out.
+//!
+//! Every colorspace is measured the same way, in OKLch: chroma goes up to about `0.32` inside
+//! sRGB, so it's scaled by [`MAX_CHROMA`] to go from 0 to 1, like the lightness.
+//!
+//! `vibrancy` follows the Android Palette API `Target`, a weighted sum of how close the color is
+//! to being fully saturated, to mid lightness and to being the most used one. The `Target` leaves
+//! out colors under [`MIN_VIBRANT`] saturation, here they are ranked after the ones above it.
+//!
+//! Ref: <https://developer.android.com/reference/androidx/palette/graphics/Target>
+use std::fmt;
+
+use palette::{FromColor, Oklch, Srgb};
+use serde::{Serialize, Deserialize};
+
+use super::{ColorTrait, Histo};
+
+/// OKLch chroma of the most saturated sRGB colors, roughly.
+pub const MAX_CHROMA: f32 = 0.32;
+
+/// Importance of the grays when weighting by chroma, so they still count a bit.
+const FLOOR: f32 = 0.1;
+
+/// Weights of the saturation, lightness and population in `vibrancy`, from Android's `Target`.
+const VIBRANT: (f32, f32, f32) = (0.24, 0.52, 0.24);
+
+/// Saturation under which colors don't count as vibrant, from Android's `Target`.
+pub const MIN_VIBRANT: f32 = 0.35;
+
+/// How to rank the colors, keeping the best ones
+#[derive(Debug, PartialEq, Eq, Deserialize, Serialize, Clone, Copy, Default, clap::ValueEnum)]
+#[cfg_attr(feature = "doc" , derive(documented::Documented, documented::DocumentedFields))]
+#[cfg_attr(feature = "iter", derive(strum::EnumIter))]
+#[cfg_attr(feature = "schema" , derive(schemars::JsonSchema))]
+#[serde(rename_all = "lowercase")]
+pub enum Scoring {
+    /// The most used colors first, by the area they cover
+    #[default]
+    Count,
+    /// The count, but weighted by chroma, so vivid colors count more than grays
+    Chroma,
+    /// Like Android's Palette API: saturated colors of mid lightness first, then the most used,
+    /// and grays last
+    Vibrancy,
+}
+
+impl Scoring {
+    /// Sorts `histo`, the best colors first. With [`Scoring::Count`], only by their `count`.
+    pub fn sort<C: ColorTrait>(&self, histo: &mut [Histo<C>]) {
+        if *self == Scoring::Count {
+            histo.sort_by_key(|h| std::cmp::Reverse(h.count));
+            return;
+        }
+
+        let max = histo.iter().map(|h| h.count).max().unwrap_or(1).max(1) as f32;
+        let mut scored = histo.iter()
+            .map(|h| (self.score(h, max), *h))
+            .collect::<Vec<_>>();
+        scored.sort_by(|a, b| b.0.total_cmp(&a.0));
+
+        for (h, (_, s)) in histo.iter_mut().zip(scored) {
+            *h = s;
+        }
+    }
+
+    /// Score of a color used `h.count` times, `max` being the count of the most used one.
+    fn score<C: ColorTrait>(&self, h: &Histo<C>, max: f32) -> f32 {
+        let rgb: Srgb = h.color.into_color();
+        let lch = Oklch::from_color(rgb);
+        let chroma = (lch.chroma / MAX_CHROMA).min(1.0);
+
+        match self {
+            Scoring::Count => h.count as f32,
+            Scoring::Chroma => h.count as f32 * (FLOOR + chroma),
+            Scoring::Vibrancy => {
+                let (s, l, p) = VIBRANT;
+                // the weights add up to 1, so vibrant colors always go first
+                let vibrant = if chroma >= MIN_VIBRANT { 1.0 } else { 0.0 };
+                vibrant + s * chroma + l * (1.0 - (lch.l - 0.5).abs() * 2.0) + p * (h.count as f32 / max)
+            },
+        }
+    }
+}
+
+impl fmt::Display for Scoring {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Scoring::Count    => write!(f, "Count"),
+            Scoring::Chroma   => write!(f, "Chroma"),
+            Scoring::Vibrancy => write!(f, "Vibrancy"),
+        }
+    }
+}
@@ -1,3 +1,33 @@
 //! Helper functions. Mostly unrealted to colors.
 
 pub fn avg(i: &[f32]) -> f32 { i.iter().sum::<f32>() / i.len() as f32 }
+
+/// Average of the values, each one counted as many times as its weight.
+pub fn weighted_avg(i: impl Iterator<Item = (f32, usize)>) -> f32 {
+    let (sum, n) = i.fold((0.0, 0), |(s, n), (v, w)| (s + v * w as f32, n + w));
+    sum / n as f32
+}
+
+/// Hashes the pixels packed in a `u32`, way faster than the default hasher, which is made to
+/// resist attacks that don't matter here.
+pub type PixelHash = std::hash::BuildHasherDefault<PixelHasher>;
+
+#[derive(Default)]
+pub struct PixelHasher(u64);
+
+/// 2^64 divided by the golden ratio
+const K: u64 = 0x9E37_79B9_7F4A_7C15;
+
+impl std::hash::Hasher for PixelHasher {
+    fn finish(&self) -> u64 { self.0 }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for &b in bytes { self.0 = (self.0.rotate_left(8) ^ u64::from(b)).wrapping_mul(K); }
+    }
+
+    // fibonacci hashing, with the high bits folded down since the table looks at the lower ones
+    fn write_u32(&mut self, n: u32) {
+        let h = u64::from(n).wrapping_mul(K);
+        self.0 = h ^ (h >> 32);
+    }
+}
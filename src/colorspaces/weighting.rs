@@ -0,0 +1,276 @@
+//! # Weighting
+//! Every pixel the backend returns counts the same, so a big flat sky or wall drowns out the
+//! subject of the wallpaper. Weighting makes the `count` of each color closer to how important it
+//! looks, by first building an importance map on a small version of the image:
+//! * `center`: how close the pixel is to the center (most subjects are framed there).
+//! * `contrast`: how much the pixel differs from its neighbours, flat areas count less.
+//! * `saliency`: how much the (blurred) pixel differs from the average color of the whole image,
+//!   frequency tuned saliency (Achanta et al. 2009).
+//!
+//! Backends that read the image as is (`full`, `resized`, `thumb`, `fastresize`) return their
+//! pixels row by row, see [`Pixels::size`], so the map is built from those same pixels, averaged
+//! into a small grid of at most 256 cells per side, and each pixel gets the importance of
+//! its cell. The ones that return new colors (`wal`, `kmeans`, `octree`) don't, so the image is
+//! decoded again and each color gets the average importance of where it shows up in the map, see
+//! [`ColorMap`]. Then pixels are repeated or skipped, keeping the same amount of pixels, before
+//! [`super::BuildHisto::gather_cols`] counts them.
+//!
+//! This happens to every image before [`crate::combine`] puts them together, so the weighted
+//! pixels are what gets cached as the backend output.
+//!
+//! Ref: <https://infoscience.epfl.ch/record/135217>
+use std::collections::HashMap;
+use std::fmt;
+
+use anyhow::Result;
+use image::RgbaImage;
+use palette::{IntoColor, Lab, Srgb};
+use serde::{Serialize, Deserialize};
+
+use crate::backends::{Loader, Pixels, Source};
+
+/// Most cells per side of the grid the importance map is built on.
+const SIZE: u32 = 256;
+
+/// Importance of the least important pixels, so flat areas still count a bit.
+const FLOOR: f32 = 0.1;
+
+/// How to weight the pixels before counting the colors
+#[derive(Debug, PartialEq, Eq, Deserialize, Serialize, Clone, Copy, Default, clap::ValueEnum)]
+#[cfg_attr(feature = "doc" , derive(documented::Documented, documented::DocumentedFields))]
+#[cfg_attr(feature = "iter", derive(strum::EnumIter))]
+#[cfg_attr(feature = "schema" , derive(schemars::JsonSchema))]
+#[serde(rename_all = "lowercase")]
+pub enum Weighting {
+    /// Every pixel counts the same, colors are ranked by the area they cover
+    #[default]
+    None,
+    /// Pixels closer to the center of the image count more
+    Center,
+    /// Pixels that stand out from their neighbours (edges, textures) count more than flat areas
+    Contrast,
+    /// Pixels that stand out from the average color of the image count more
+    Saliency,
+}
+
+impl Weighting {
+    /// Weight the pixels `p` the backend read from `f`, by where they are, or by their color when
+    /// the backend doesn't keep where they come from, see the module docs. Returns them as is with
+    /// [`Weighting::None`].
+    pub fn apply(&self, p: Pixels, f: &Source, l: &Loader) -> Result<Vec<u8>> {
+        if *self == Weighting::None { return Ok(p.rgb); }
+
+        let weights = match p.size {
+            Some(size) => self.by_position(&p.rgb, size),
+            None => {
+                let m = self.map(f, l)?;
+                p.rgb.chunks_exact(3).map(|c| m.get(c)).collect()
+            },
+        };
+
+        Ok(repeat(&p.rgb, &weights))
+    }
+
+    /// Importance of the colors of the image `f`, decoding it, for the backends that don't keep
+    /// where their pixels come from.
+    pub fn map(&self, f: &Source, l: &Loader) -> Result<ColorMap> {
+        let img = l.open(f)?.thumbnail(SIZE, SIZE).into_rgba8();
+        let (w, h) = img.dimensions();
+        let lab: Vec<Lab> = img.pixels().map(|p| lab(&p.0)).collect();
+
+        let mut sums = Sums::new();
+        sums.add(&img, &self.importance(&lab, w, h), l);
+        Ok(sums.into())
+    }
+
+    /// Importance of every one of the `rgb` pixels of a `(w, h, frames)` image, see
+    /// [`Pixels::size`]. Every frame gets its own map.
+    fn by_position(&self, rgb: &[u8], (w, h, frames): (u32, u32, u32)) -> Vec<f32> {
+        let (w, h, n) = (w as usize, h as usize, frames.max(1) as usize);
+        let mut ret = Vec::with_capacity(w * h);
+
+        for f in 0..n {
+            // rows of this frame, resizing may have left them uneven
+            let (top, bottom) = (f * h / n, (f + 1) * h / n);
+            let fh = bottom - top;
+            let (cw, ch) = (w.min(SIZE as usize), fh.min(SIZE as usize));
+            let cell = |x: usize, y: usize| (y - top) * ch / fh * cw + x * cw / w;
+
+            // average color of every cell
+            let mut sums = vec![([0_u32; 3], 0_u32); cw * ch];
+            for y in top..bottom {
+                for x in 0..w {
+                    let (p, c) = (&rgb[(y * w + x) * 3..][..3], &mut sums[cell(x, y)]);
+                    for i in 0..3 { c.0[i] += u32::from(p[i]); }
+                    c.1 += 1;
+                }
+            }
+            let cells: Vec<Lab> = sums.iter()
+                .map(|(s, n)| lab(&s.map(|x| (x / (*n).max(1)) as u8)))
+                .collect();
+
+            let imp = self.importance(&cells, cw as u32, ch as u32);
+            for y in top..bottom {
+                ret.extend((0..w).map(|x| imp[cell(x, y)]));
+            }
+        }
+
+        ret
+    }
+
+    /// Importance of every one of the `lab` pixels of a `w`x`h` image, between [`FLOOR`] and
+    /// `1.0`, row by row.
+    fn importance(&self, lab: &[Lab], w: u32, h: u32) -> Vec<f32> {
+        let raw: Vec<f32> = match self {
+            Weighting::None => vec![1.0; lab.len()],
+            Weighting::Center => (0..h).flat_map(|y| (0..w).map(move |x| {
+                let dx = (x as f32 + 0.5) / w as f32 - 0.5;
+                let dy = (y as f32 + 0.5) / h as f32 - 0.5;
+                // gaussian with a sigma of 0.2, halfway to the sides is at ~46%
+                (-(dx * dx + dy * dy) / 0.08).exp()
+            })).collect(),
+            Weighting::Contrast => {
+                let l: Vec<f32> = lab.iter().map(|c| c.l).collect();
+                let mean = box_blur(&l, w, h, 2);
+                let sq = box_blur(&l.iter().map(|x| x * x).collect::<Vec<_>>(), w, h, 2);
+                // standard deviation of the lightness in a 5x5 window
+                mean.iter().zip(sq).map(|(m, s)| (s - m * m).max(0.0).sqrt()).collect()
+            },
+            Weighting::Saliency => {
+                let blur = |f: fn(&Lab) -> f32| box_blur(&lab.iter().map(f).collect::<Vec<_>>(), w, h, 1);
+                let (bl, ba, bb) = (blur(|c| c.l), blur(|c| c.a), blur(|c| c.b));
+
+                let n = lab.len().max(1) as f32;
+                let mean = lab.iter().fold((0.0, 0.0, 0.0), |m, c| (m.0 + c.l / n, m.1 + c.a / n, m.2 + c.b / n));
+
+                (0..lab.len())
+                    .map(|i| ((bl[i] - mean.0).powi(2) + (ba[i] - mean.1).powi(2) + (bb[i] - mean.2).powi(2)).sqrt())
+                    .collect()
+            },
+        };
+
+        let max = raw.iter().copied().fold(0.0, f32::max);
+        raw.into_iter()
+            .map(|x| if max > 0.0 { FLOOR + (1.0 - FLOOR) * x / max } else { 1.0 })
+            .collect()
+    }
+}
+
+/// Average importance of the colors of the image, in buckets of 5 bits per channel, and coarser
+/// ones of 3 bits for the colors that don't show up. Only the buckets with some color are kept.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ColorMap {
+    fine: HashMap<usize, f32>,
+    coarse: HashMap<usize, f32>,
+    total: Option<f32>,
+}
+
+impl ColorMap {
+    /// Importance of the `rgb` color
+    pub fn get(&self, rgb: &[u8]) -> f32 {
+        self.fine.get(&bucket(rgb, 5))
+            .or_else(|| self.coarse.get(&bucket(rgb, 3)))
+            .or(self.total.as_ref())
+            .copied()
+            .unwrap_or(1.0)
+    }
+}
+
+/// Repeat or skip the `rgb` pixels by their `weights`, carrying the remainder, so there are as
+/// many as before.
+fn repeat(rgb: &[u8], weights: &[f32]) -> Vec<u8> {
+    let mean = weights.iter().map(|&w| f64::from(w)).sum::<f64>() / weights.len().max(1) as f64;
+    if mean <= 0.0 { return rgb.to_vec(); }
+
+    let mut ret = Vec::with_capacity(rgb.len());
+    let mut acc = 0.0;
+    for (p, &w) in rgb.chunks_exact(3).zip(weights) {
+        acc += f64::from(w) / mean;
+        // rounding, so the last one isn't lost by float errors
+        while acc >= 0.5 {
+            ret.extend_from_slice(p);
+            acc -= 1.0;
+        }
+    }
+
+    ret
+}
+
+/// The rgb8 color in Lab
+fn lab(rgb: &[u8]) -> Lab {
+    Srgb::new(rgb[0], rgb[1], rgb[2]).into_format::<f32>().into_linear().into_color()
+}
+
+/// Sum of the importance and amount of pixels of every bucket, while building the [`ColorMap`].
+struct Sums {
+    fine: Vec<(f32, u32)>,
+    coarse: Vec<(f32, u32)>,
+    total: (f32, u32),
+}
+
+impl Sums {
+    fn new() -> Self {
+        Self { fine: vec![(0.0, 0); 1 << 15], coarse: vec![(0.0, 0); 1 << 9], total: (0.0, 0) }
+    }
+
+    /// Add the `importance` of every visible pixel of `img`
+    fn add(&mut self, img: &RgbaImage, importance: &[f32], l: &Loader) {
+        for (p, &imp) in img.pixels().zip(importance) {
+            if !l.is_visible(p[3]) { continue; }
+            for (v, bits) in [(&mut self.fine, 5), (&mut self.coarse, 3)] {
+                let e = &mut v[bucket(&p.0, bits)];
+                e.0 += imp;
+                e.1 += 1;
+            }
+            self.total.0 += imp;
+            self.total.1 += 1;
+        }
+    }
+}
+
+impl From<Sums> for ColorMap {
+    fn from(s: Sums) -> Self {
+        let avg = |v: Vec<(f32, u32)>| v.into_iter()
+            .enumerate()
+            .filter(|(_, (_, n))| *n > 0)
+            .map(|(i, (sum, n))| (i, sum / n as f32))
+            .collect();
+        let (sum, n) = s.total;
+
+        Self { fine: avg(s.fine), coarse: avg(s.coarse), total: (n > 0).then(|| sum / n as f32) }
+    }
+}
+
+/// Index of the color, keeping the `bits` most significant bits of each channel.
+fn bucket(rgb: &[u8], bits: u8) -> usize {
+    let s = 8 - bits;
+    (usize::from(rgb[0] >> s) << (2 * bits)) | (usize::from(rgb[1] >> s) << bits) | usize::from(rgb[2] >> s)
+}
+
+/// Mean of the `(2r + 1)²` window around every value, clamped at the borders.
+fn box_blur(v: &[f32], w: u32, h: u32, r: i64) -> Vec<f32> {
+    let (w, h) = (i64::from(w), i64::from(h));
+    let at = |x: i64, y: i64| v[(y.clamp(0, h - 1) * w + x.clamp(0, w - 1)) as usize];
+    let n = ((2 * r + 1) * (2 * r + 1)) as f32;
+
+    (0..h).flat_map(|y| (0..w).map(move |x| {
+        let mut s = 0.0;
+        for dy in -r..=r {
+            for dx in -r..=r {
+                s += at(x + dx, y + dy);
+            }
+        }
+        s / n
+    })).collect()
+}
+
+impl fmt::Display for Weighting {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Weighting::None     => write!(f, "None"),
+            Weighting::Center   => write!(f, "Center"),
+            Weighting::Contrast => write!(f, "Contrast"),
+            Weighting::Saliency => write!(f, "Saliency"),
+        }
+    }
+}
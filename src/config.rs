@@ -3,14 +3,14 @@ use std::collections::HashMap;
 use std::path::PathBuf;
 use std::fs::read_to_string;
 use std::io::Write;
-use std::path::Path;
 
-use crate::args::WallustArgs;
+use crate::args::{Output, WallustArgs};
 use crate::args::Globals;
 use crate::colors::Colors;
 use crate::template;
 use crate::template::TemplateFields;
 use crate::presets::Preset;
+use crate::backends::{ImageParser, Loader, Pixels, Source};
 
 use anyhow::{Result, Context};
 use owo_colors::{AnsiColors, OwoColorize};
@@ -22,21 +22,64 @@ use serde::Deserialize;
 #[derive(Debug, Deserialize, Default)]
 #[cfg_attr(feature = "doc" , derive(documented::Documented, documented::DocumentedFields))]
 pub struct Config {
-    /// threshold to use to differentiate colors
+    /// threshold to use to differentiate colors, `kmeans` doesn't use one
     #[serde(default)]
     #[serde(deserialize_with = "validate_threshold")]
     pub threshold: Option<u8>,
+    /// How many colors to look for, instead of a threshold
+    #[serde(default)]
+    #[serde(deserialize_with = "validate_color_count")]
+    pub color_count: Option<u8>,
     /// Which backend to use, see backends.rs
-    #[serde(rename = "backend")]
+    #[serde(skip)]
     pub backend_user: Option<crate::backends::Backend>,
+    /// Parameters of the backends, the `[backend]` table
+    #[serde(skip)]
+    pub backend_params: crate::backends::Params,
     /// Which palette to use, see palettes.rs
     #[serde(rename = "palette")]
-    pub palette_user: Option<crate::palettes::Palette>,
+    pub palette_user: Option<crate::palettes::PaletteName>,
+    /// Palettes `auto` chooses from, the `[palette]` table
+    #[serde(skip)]
+    pub palette_auto: crate::palettes::Auto,
+    /// Also generate the `dark` and `light` palettes of `palette_auto`, see [`Config::wants_variants`]
+    pub variants: Option<bool>,
+    /// Palettes declared in the config file, see palettes/custom.rs
+    #[serde(default)]
+    #[serde(deserialize_with = "validate_palettes")]
+    pub palettes: Option<HashMap<String, crate::palettes::Custom>>,
     /// Which colorspace to use, see colorspaces.rs
-    #[serde(rename = "color_space")]
+    #[serde(skip)]
     pub color_space_user: Option<crate::colorspaces::ColorSpace>,
+    /// Lightness and chroma bounds of the colorspaces, the `[color_space]` table
+    #[serde(skip)]
+    pub color_space_bounds: crate::colorspaces::Bounds,
     /// Optional alpha value
     pub alpha: Option<u8>,
+    /// Pixels with an alpha below this percentage are ignored when reading the image
+    #[serde(default)]
+    #[serde(deserialize_with = "validate_threshold")]
+    pub alpha_cutoff: Option<u8>,
+    /// Only read this part of the image, see backends/region.rs
+    pub region: Option<crate::backends::Region>,
+    /// How many frames to read from animated images
+    #[serde(default)]
+    #[serde(deserialize_with = "validate_frames")]
+    pub frames: Option<u16>,
+    /// How to map 16 bit and HDR images to 8 bits, see backends/tonemap.rs
+    pub tone_map: Option<crate::backends::ToneMap>,
+    /// Read images as sRGB, even when they embed an ICC profile
+    pub ignore_icc: Option<bool>,
+    /// How to weight the pixels before counting the colors, see colorspaces/weighting.rs
+    pub weighting: Option<crate::colorspaces::Weighting>,
+    /// How to rank the colors found, see colorspaces/scoring.rs
+    pub scoring: Option<crate::colorspaces::Scoring>,
+    /// Leave out uniform bars on the sides of the image, see backends/borders.rs
+    pub trim_borders: Option<bool>,
+    /// Colors left out before looking for the palette, see colorspaces/ignore.rs
+    pub ignore_colors: Option<Vec<crate::colors::Myrgb>>,
+    /// How close a color has to be to one of `ignore_colors` to be left out
+    pub ignore_tolerance: Option<u8>,
     /// This flags ensures good contrast between images, by doing some w3m calculations.
     /// However it isn't required and should only be turn on when you notice bad contrast between many images.
     pub check_contrast: Option<bool>,
@@ -83,17 +126,31 @@ pub struct Config {
     #[serde(skip)]
     pub true_th: u8,
 
+    /// Threshold the colorspace ended up using, see [`crate::colorspaces::Threshold`]
+    #[serde(skip)]
+    pub used_th: u8,
+
     /// True backend value
     #[serde(skip)]
     pub backend: crate::backends::Backend,
 
+    /// Backends left to try if `backend` fails, `Some` only when it was chosen by `auto`, see
+    /// [`Config::auto_backend`]
+    #[serde(skip)]
+    pub backend_auto: Option<Vec<crate::backends::Backend>>,
+
     /// True colorspace value
     #[serde(skip)]
     pub color_space: crate::colorspaces::ColorSpace,
 
     /// True palette value
     #[serde(skip)]
-    pub palette: crate::palettes::Palette,
+    pub palette: crate::palettes::PaletteName,
+
+    /// Whether the palette is dark or light, the one chosen with `auto`, see
+    /// [`Config::palette_mode`]
+    #[serde(skip)]
+    pub mode: crate::palettes::Mode,
 }
 
 
@@ -102,26 +159,94 @@ pub struct Config {
 /// This is mainly to generate a pretty and accurate config JSON SCHEMA
 pub struct PrettyConfig {
     /// What threshold to use to differentiate colors, if not defined, wallust automatically looks
-    /// for the best fit
+    /// for the best fit. Ignored by the `kmeans` colorspace
     #[serde(default)]
     #[serde(deserialize_with = "validate_threshold")]
     pub threshold: Option<u8>,
 
-    /// Which backend to use, see backends.rs
-    pub backend: Option<crate::backends::Backend>,
-
-    /// Which palette to use, see palettes.rs
-    pub palette: Option<crate::palettes::Palette>,
+    /// How many colors to look for, between 6 and 16. The threshold that gives them is searched
+    /// for, overriding `threshold`
+    #[serde(default)]
+    #[serde(deserialize_with = "validate_color_count")]
+    pub color_count: Option<u8>,
+
+    /// Which backend to use, see backends.rs. Either only the name or a `[backend]` table with
+    /// the `name` and parameters for each backend
+    pub backend: Option<crate::backends::BackendTable>,
+
+    /// Which palette to use, see palettes.rs, or the name of one of the `palettes`. Either only
+    /// the name or a `[palette]` table with the `name` and the `dark` and `light` palettes `auto`
+    /// chooses from
+    #[cfg_attr(feature = "schema", schemars(with = "Option<String>"))]
+    pub palette: Option<crate::palettes::PaletteTable>,
+
+    /// Also generate the `dark` and `light` palettes of the `[palette]` table, available to
+    /// templates as `dark` and `light`, or with `variant` in a template (default is false)
+    pub variants: Option<bool>,
+
+    /// Palettes declared as `[palettes.<name>]` tables, every slot (background, foreground,
+    /// cursor, color0 to color15) being a color like `top[3]`, `darkest` or `#EEEEEE` followed by
+    /// changes like `darken 0.2`, `blend color7` or `complementary`
+    #[serde(default)]
+    #[serde(deserialize_with = "validate_palettes")]
+    pub palettes: Option<HashMap<String, crate::palettes::Custom>>,
 
     /// Which preset to use, see presets.rs
     pub preset: Option<Preset>,
 
-    /// Which colorspace to use, see colorspaces.rs
-    pub color_space: Option<crate::colorspaces::ColorSpace>,
+    /// Which colorspace to use, see colorspaces.rs. Either only the name or a `[color_space]`
+    /// table with the `name` and the `darkest`, `lightest` and `min_chroma` colors to keep
+    pub color_space: Option<crate::colorspaces::ColorSpaceTable>,
 
     /// Optional alpha value
     pub alpha: Option<u8>,
 
+    /// Pixels with an alpha below this percentage are ignored when reading the image, so
+    /// transparent areas don't count as black (default is 0, every pixel is kept)
+    #[serde(default)]
+    #[serde(deserialize_with = "validate_threshold")]
+    pub alpha_cutoff: Option<u8>,
+
+    /// Only read this part of the image, either a `x,y,w,h` rectangle or `gravity:WxH`, all in
+    /// fractions of the image size (e.g. `0.5,0,0.5,1` or `north:1x0.05`)
+    #[serde(alias = "crop")]
+    #[cfg_attr(feature = "schema", schemars(with = "Option<String>"))]
+    pub region: Option<crate::backends::Region>,
+
+    /// How many frames, evenly spaced in time, to read from animated images (GIF, APNG, WebP),
+    /// so the palette represents the whole loop (default is 1, only the first frame)
+    #[serde(default)]
+    #[serde(deserialize_with = "validate_frames")]
+    pub frames: Option<u16>,
+
+    /// How to map 16 bit and HDR (float) images to 8 bits before looking at the colors, see
+    /// backends/tonemap.rs (default is clip)
+    pub tone_map: Option<crate::backends::ToneMap>,
+
+    /// Read images as sRGB, ignoring their embedded ICC profile (Display P3, Adobe RGB..), which
+    /// is used by default to convert them into sRGB
+    pub ignore_icc: Option<bool>,
+
+    /// Make some pixels count more than others when looking for the most used colors, instead of
+    /// only by the area they cover: center, contrast or saliency (default is none)
+    pub weighting: Option<crate::colorspaces::Weighting>,
+
+    /// How to rank the colors found, keeping the best ones: count, chroma or vibrancy (default is
+    /// count, the most used colors)
+    pub scoring: Option<crate::colorspaces::Scoring>,
+
+    /// Detect uniform black, gray or white bars on the sides of the image (letterbox,
+    /// pillarbox) and leave them out (default is false)
+    pub trim_borders: Option<bool>,
+
+    /// Colors to leave out when looking for the palette, as hex (e.g. `["#000000", "#FFFFFF"]`)
+    #[cfg_attr(feature = "schema", schemars(with = "Option<Vec<String>>"))]
+    pub ignore_colors: Option<Vec<crate::colors::Myrgb>>,
+
+    /// Maximum difference, in any channel (0 to 255), for a color to match one of
+    /// `ignore_colors` (default is 10)
+    pub ignore_tolerance: Option<u8>,
+
     /// This flags ensures good contrast between images, by doing some w3m calculations.
     /// However it isn't required and should only be turn on when you notice bad contrast between many images.
     pub check_contrast: Option<bool>,
@@ -158,6 +283,8 @@ pub struct Fields {
     /// This flag allows for 'src', when a dir, to be templated recursively
     /// If 'src' is a file, this has no effect.
     pub max_depth: Option<u8>,
+    /// Template it with the `dark` or `light` palette, see `variants` (default is the palette in use)
+    pub variant: Option<crate::palettes::Mode>,
 }
 
 #[derive(Debug, Deserialize, Clone)]
@@ -256,7 +383,8 @@ impl Config {
         // defined or defaults.
         ret.backend = ret.backend_user.unwrap_or_default();
         ret.color_space = ret.color_space_user.unwrap_or_default();
-        ret.palette = ret.palette_user.unwrap_or_default();
+        ret.palette = ret.palette_user.clone().unwrap_or_default();
+        ret.check_palette()?;
         //println!("{:#?}", ret);
 
         Ok(ret)
@@ -277,16 +405,65 @@ impl Config {
                 )
         } else { String::new() };
 
-        let th = match self.threshold {
-            Some(s) => format!("Using a threshold of {s} in between colors."),
-            None => format!("Not defined, using {} default thresholds.", "best".bold()),
+        use crate::colorspaces::{ColorSpace, MAX_COLS};
+        let th = match (self.color_space, self.color_count, self.threshold) {
+            (ColorSpace::KMeans, n, _) => format!("None with kmeans, making {} clusters.", n.unwrap_or(MAX_COLS).bold()),
+            (_, Some(n), _) => format!("Looking for the one that gives {} colors.", n.bold()),
+            (_, None, Some(s)) => format!("Using a threshold of {s} in between colors."),
+            (_, None, None) => format!("Not defined, using {} default thresholds.", "best".bold()),
+        };
+
+        // kmeans clusters the colors instead, see [`ColorSpace::KMeans`]
+        let ignored = match (self.color_space, self.threshold) {
+            (ColorSpace::KMeans, Some(t)) => format!("\n[{}] {}: Ignoring {t}, kmeans doesn't use a threshold",
+                "W".red().bold(),
+                "threshold".magenta().bold(),
+                ),
+            _ => String::new(),
+        };
+
+        let region = if let Some(r) = self.region {
+            format!("\n[{}] {}: Only reading {r} of the image",
+                "I".blue().bold(),
+                "region".magenta().bold()
+                )
+        } else { String::new() };
+
+        let weighting = match self.weighting.unwrap_or_default() {
+            crate::colorspaces::Weighting::None => String::new(),
+            w => format!("\n[{}] {}: Weighting pixels by {}",
+                "I".blue().bold(),
+                "weighting".magenta().bold(),
+                w.to_string().to_lowercase(),
+                ),
+        };
+
+        let scoring = match self.scoring.unwrap_or_default() {
+            crate::colorspaces::Scoring::Count => String::new(),
+            s => format!("\n[{}] {}: Ranking colors by {}",
+                "I".blue().bold(),
+                "scoring".magenta().bold(),
+                s.to_string().to_lowercase(),
+                ),
         };
 
+        let variants = if self.wants_variants() {
+            let (d, l) = (&self.palette_auto.dark, &self.palette_auto.light);
+            format!("\n[{}] {}: Also generating the {} and {} palettes",
+                "I".blue().bold(),
+                "variants".magenta().bold(),
+                d.bold().color(d.col()),
+                l.bold().color(l.col()),
+                )
+        } else { String::new() };
+
+        let auto = if self.backend_auto.is_some() { " (auto)" } else { "" };
+
         let no_preset = format!(
-"[{i}] {back_f}: Using {back} backend parser
-[{i}] {th_f}: {th}
+"[{i}] {back_f}: Using {back} backend parser{auto}
+[{i}] {th_f}: {th}{ignored}
 [{i}] {cs_f}: Using {cs} colorspace variation
-[{i}] {palette_f}: Using {palette} palette{k}{sat}",
+[{i}] {palette_f}: Using {palette} palette{variants}{k}{sat}{region}{weighting}{scoring}",
             back     = self.backend.bold().color(self.backend.col()),
             palette  = self.palette.bold().color(self.palette.col()),
             cs       = self.color_space.bold().color(self.color_space.col()),
@@ -298,14 +475,18 @@ impl Config {
         );
 
         match &self.preset {
-            Some(s) => println!("[{i}] Using {p} preset.", i = "I".blue().bold(), p = s.red().bold()),
+            Some(s) => println!("[{i}] Using {p} preset.{region}", i = "I".blue().bold(), p = s.red().bold()),
             None => println!("{no_preset}"),
         }
     }
 
     /// Writes templates defined in the config file (if any)
     /// Should print a warning if you are using the old `[[entry]]` syntax (since it's going to be deprecated in v3).
-    pub fn write_entry(&self, wal_str: &WalStr, colors: &Colors, quiet: bool) -> Result<()> {
+    ///
+    /// `outputs` are the palettes of each monitor, with `--output`, and `variants` the `dark` and
+    /// `light` palettes, see [`Config::wants_variants`]. Both along with the backend and threshold
+    /// each one used.
+    pub fn write_entry(&self, wal_str: &WalStr, colors: &Colors, outputs: &[(&Output, crate::Generated)], variants: &[(crate::palettes::Mode, crate::Generated)], quiet: bool) -> Result<()> {
         let init = format!("[{info}] {t}: ", info = "I".blue().bold(), t = "templates".magenta().bold());
 
         let templates_header = match &self.templates {
@@ -328,13 +509,35 @@ impl Config {
             WalStr::Path(p) => dunce::canonicalize(p).expect("PATH EXIST, validation from clap").display().to_string(),
         };
 
-        let values = TemplateFields {
+        let output_paths: Vec<String> = outputs.iter()
+            .map(|(o, _)| match o.input.source.path() {
+                Some(p) => dunce::canonicalize(p).expect("PATH EXIST, already read").display().to_string(),
+                None => o.input.source.to_string(),
+            })
+            .collect();
+
+        let fields = |image_path, colors| TemplateFields {
             alpha: self.alpha.unwrap_or(100),
+            threshold: self.used_th,
             backend: &self.backend,
             colorspace: &self.color_space,
             palette: &self.palette,
-            image_path: &image_path,
+            mode: self.mode,
+            image_path,
             colors,
+            outputs: vec![],
+            variants: vec![],
+        };
+
+        let values = TemplateFields {
+            // each monitor with what was used for its own image
+            outputs: outputs.iter().zip(&output_paths)
+                .map(|((o, g), p)| (o.name.as_str(), TemplateFields { threshold: g.th, backend: &g.backend, mode: g.mode, ..fields(p, &g.colors) }))
+                .collect(),
+            variants: variants.iter()
+                .map(|(m, g)| (*m, TemplateFields { threshold: g.th, backend: &g.backend, palette: self.palette_auto.pick(*m), mode: *m, ..fields(&image_path, &g.colors) }))
+                .collect(),
+            ..fields(&image_path, colors)
         };
 
         template::write_template(&self.templates_dir, templates_header, &values, quiet, self.env_vars.unwrap_or_default())
@@ -350,18 +553,74 @@ impl Config {
             self.color_space = col;
         }
 
-        if let Some(f) = cli.palette {
-            self.palette = f;
+        if let Some(d) = cli.darkest {
+            self.color_space_bounds.darkest = Some(d);
+        }
+
+        if let Some(l) = cli.lightest {
+            self.color_space_bounds.lightest = Some(l);
+        }
+
+        if let Some(c) = cli.min_chroma {
+            self.color_space_bounds.min_chroma = Some(c);
+        }
+
+        if cli.grid {
+            self.color_space_bounds.grid = true;
+        }
+
+        if let Some(f) = &cli.palette {
+            self.palette = f.clone();
+        }
+
+        if cli.variants {
+            self.variants = Some(cli.variants);
         }
 
         if let Some(t) = cli.threshold {
             self.threshold = Some(t as u8); //t is [1..=100]
         }
 
+        if let Some(n) = cli.color_count {
+            self.color_count = Some(n as u8); //n is [6..=16]
+        }
+
         if let Some(a) = cli.alpha {
             self.alpha = Some(a as u8);
         }
 
+        if let Some(a) = cli.alpha_cutoff {
+            self.alpha_cutoff = Some(a as u8); //a is [0..=100]
+        }
+
+        if let Some(r) = cli.region {
+            self.region = Some(r);
+        }
+
+        if let Some(n) = cli.frames {
+            self.frames = Some(n as u16); //n is [1..=MAX_FRAMES]
+        }
+
+        if let Some(t) = cli.tone_map {
+            self.tone_map = Some(t);
+        }
+
+        if cli.ignore_icc {
+            self.ignore_icc = Some(cli.ignore_icc);
+        }
+
+        if let Some(w) = cli.weighting {
+            self.weighting = Some(w);
+        }
+
+        if let Some(s) = cli.scoring {
+            self.scoring = Some(s);
+        }
+
+        if cli.trim_borders {
+            self.trim_borders = Some(cli.trim_borders);
+        }
+
         if cli.check_contrast {
             self.check_contrast = Some(cli.check_contrast);
         }
@@ -375,10 +634,175 @@ impl Config {
         }
     }
 
-    pub fn backend_or_preset(&self, p: &Path) -> Result<Vec<u8>> {
+    /// How the colorspace chooses the threshold: searching for `color_count` colors, the
+    /// `threshold` given, or the best one without any (or with `dynamic_th`).
+    pub fn threshold_mode(&self, dynamic_th: bool) -> crate::colorspaces::Threshold {
+        use crate::colorspaces::Threshold;
+        match (self.color_count, self.threshold) {
+            (Some(n), _) => Threshold::Auto(Some(n)),
+            (None, Some(t)) if !dynamic_th => Threshold::Fixed(t),
+            _ => Threshold::Auto(None),
+        }
+    }
+
+    /// The `[palettes.<name>]` in use, if `palette` isn't a built in one.
+    pub fn custom_palette(&self) -> Option<&crate::palettes::Custom> {
+        self.custom(&self.palette)
+    }
+
+    /// The `[palettes.<name>]` of `p`, if it isn't a built in one.
+    pub fn custom(&self, p: &crate::palettes::PaletteName) -> Option<&crate::palettes::Custom> {
+        match p {
+            crate::palettes::PaletteName::Custom(name) => self.palettes.as_ref()?.get(name),
+            crate::palettes::PaletteName::Builtin(_) => None,
+        }
+    }
+
+    /// The `[color_space]` bounds, once merged with `--darkest`, `--lightest` and `--min-chroma`
+    /// (each one is checked on its own), see [`crate::colorspaces::Bounds::validate`].
+    pub fn check_bounds(&self) -> Result<()> {
+        self.color_space_bounds.validate().map_err(|e| anyhow::anyhow!("color_space: {e}"))
+    }
+
+    /// A custom `palette` should be declared in the config file, also the ones `auto` chooses from
+    /// and the variants.
+    pub fn check_palette(&self) -> Result<()> {
+        use crate::palettes::{Palette, PaletteName};
+        let names = match &self.palette {
+            PaletteName::Builtin(Palette::Auto) => vec![&self.palette_auto.dark, &self.palette_auto.light],
+            p if self.wants_variants() => vec![p, &self.palette_auto.dark, &self.palette_auto.light],
+            p => vec![p],
+        };
+
+        for p in names {
+            if let PaletteName::Custom(name) = p {
+                if self.custom(p).is_none() {
+                    anyhow::bail!("No palette named '{name}', it isn't a built in one nor a [palettes.{name}] table in the config file.")
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether to also generate the `dark` and `light` palettes: with `variants` or when a
+    /// template wants one of them.
+    pub fn wants_variants(&self) -> bool {
+        self.variants.unwrap_or(false)
+            || self.templates.as_ref().is_some_and(|t| t.values().any(|f| f.variant.is_some()))
+    }
+
+    /// The palette that ends up filling the colors, with `auto` the one for `mode`.
+    pub fn palette_for(&self, mode: crate::palettes::Mode) -> &crate::palettes::PaletteName {
+        match &self.palette {
+            crate::palettes::PaletteName::Builtin(crate::palettes::Palette::Auto) => self.palette_auto.pick(mode),
+            p => p,
+        }
+    }
+
+    /// How the palette in use wants the colors sorted, see [`crate::palettes::Palette::sort_ord`].
+    /// With `auto`, the one of its `dark` palette.
+    pub fn sort_ord(&self) -> crate::colorspaces::ColorOrder {
+        self.ord_of(self.palette_for(crate::palettes::Mode::Dark))
+    }
+
+    fn ord_of(&self, p: &crate::palettes::PaletteName) -> crate::colorspaces::ColorOrder {
+        match (p, self.custom(p)) {
+            (_, Some(c)) => c.sort_ord(),
+            (crate::palettes::PaletteName::Builtin(p), None) => p.sort_ord(),
+            // not declared, see [`Config::check_palette`]
+            (crate::palettes::PaletteName::Custom(_), None) => crate::palettes::Palette::default().sort_ord(),
+        }
+    }
+
+    /// Whether the palette is dark or light: the one `auto` chose for the image (by the colors
+    /// found, `orig`, and their `counts`), otherwise by the background of the `colors`.
+    pub fn palette_mode(&self, orig: &[palette::Srgb], counts: &[usize], colors: &Colors) -> crate::palettes::Mode {
+        use crate::palettes::{Mode, Palette, PaletteName};
+        match self.palette {
+            PaletteName::Builtin(Palette::Auto) => Mode::of_image(orig, counts),
+            _ => Mode::of_colors(colors),
+        }
+    }
+
+    /// Fills the palette in use with the colors, `top` sorted by [`Config::sort_ord`]. With
+    /// `auto`, the one for the image, by the colors found and their `counts` (see
+    /// [`crate::palettes::Mode::of_image`]), and `top` is reversed if it wants the other order.
+    pub fn run_palette(&self, top: Vec<palette::Srgb>, orig: Vec<palette::Srgb>, counts: &[usize]) -> Colors {
+        let p = self.palette_for(crate::palettes::Mode::of_image(&orig, counts));
+        let mut top = top;
+        if self.color_space.sorts() && self.ord_of(p) != self.sort_ord() {
+            top.reverse();
+        }
+
+        match (p, self.custom(p)) {
+            (_, Some(c)) => c.run(top, orig),
+            (crate::palettes::PaletteName::Builtin(p), None) => p.run(top, orig),
+            (crate::palettes::PaletteName::Custom(_), None) => crate::palettes::Palette::default().run(top, orig),
+        }
+    }
+
+    /// With `backend = "auto"`, choose the backend for the image `p`, keeping the rest as
+    /// fallbacks, see [`crate::backends::auto`]. Nothing changes with any other backend.
+    pub fn auto_backend(&mut self, p: &Source) -> Result<()> {
+        if self.backend != crate::backends::Backend::Auto || self.preset.is_some() { return Ok(()); }
+
+        let mut candidates = crate::backends::auto::candidates(p, &self.loader())?;
+        self.backend = candidates.remove(0);
+        self.backend_auto = Some(candidates);
+        Ok(())
+    }
+
+    /// Backends to try on the image `p`, in order: `backend` and, when chosen by `auto`, the
+    /// fallbacks, see [`Config::auto_backend`]. Unlike it, nothing is changed, `auto` is resolved
+    /// here if it wasn't already.
+    pub fn backends(&self, p: &Source) -> Result<Vec<crate::backends::Backend>> {
+        use crate::backends::Backend;
+
+        Ok(match (&self.backend_auto, self.backend) {
+            _ if self.preset.is_some() => vec![self.backend],
+            (Some(rest), b) => std::iter::once(b).chain(rest.iter().copied()).collect(),
+            (None, Backend::Auto) => crate::backends::auto::candidates(p, &self.loader())?,
+            (None, b) => vec![b],
+        })
+    }
+
+    /// What happens to the (already weighted) backend output before the colorspace: the
+    /// `ignore_colors` are left out, see [`crate::colorspaces::Weighting::apply`].
+    pub fn filter_pixels<'a>(&self, rgb: &'a [u8]) -> Result<std::borrow::Cow<'a, [u8]>> {
+        use crate::colorspaces::ignore;
+
+        let tolerance = self.ignore_tolerance.unwrap_or(ignore::TOLERANCE);
+        ignore::ignore(rgb, self.ignore_colors.as_deref().unwrap_or_default(), tolerance)
+    }
+
+    /// The pixels `p` the backend read from `f`, weighted by `weighting`, see
+    /// [`crate::colorspaces::Weighting::apply`].
+    pub fn weigh(&self, p: Pixels, f: &Source) -> Result<Vec<u8>> {
+        self.weighting.unwrap_or_default().apply(p, f, &self.loader())
+    }
+
+    pub fn backend_or_preset(&self, p: &Source) -> Result<Vec<u8>> {
         match &self.preset {
-            Some(s) => s.backend(p),
-            None => self.backend.main()(p),
+            Some(s) => s.backend(p, &self.loader()),
+            None => self.parser().parse(p, &self.loader()),
+        }
+    }
+
+    /// The backend in use, with its parameters
+    pub fn parser(&self) -> Box<dyn ImageParser> {
+        self.backend.parser(&self.backend_params)
+    }
+
+    /// How backends should read the image
+    pub fn loader(&self) -> Loader {
+        let def = Loader::default();
+        Loader {
+            alpha_cutoff: self.alpha_cutoff.unwrap_or(def.alpha_cutoff),
+            region: self.region,
+            frames: self.frames.unwrap_or(def.frames).max(1),
+            tone_map: self.tone_map.unwrap_or(def.tone_map),
+            icc: !self.ignore_icc.unwrap_or(!def.icc),
+            trim_borders: self.trim_borders.unwrap_or(def.trim_borders),
         }
     }
 
@@ -430,22 +854,51 @@ impl std::fmt::Display for Config {
 Config directory: {dir}
 Config file: {file}
 Configuration options:
-    backend        = {b}
-    color_space    = {c}
+    backend        = {b} {bp:?}
+    color_space    = {c} {cb:?}
     threshold      = {t:?}
-    palette        = {f}
+    color_count    = {cc:?}
+    palette        = {f} {pa:?}
+    palettes       = {ps}
+    variants       = {va:?}
     check_contrast = {con:?}
     saturation     = {sat:?}
     alpha          = {a:?}
+    alpha_cutoff   = {ac:?}
+    region         = {r}
+    frames         = {fr:?}
+    tone_map       = {tm:?}
+    ignore_icc     = {icc:?}
+    weighting      = {w:?}
+    scoring        = {sc:?}
+    trim_borders   = {tb:?}
+    ignore_colors  = {ic}
+    ignore_tolerance = {it:?}
 Templates:
 {templates}",
             b = self.backend,
+            bp = self.parser(),
             c = self.color_space,
+            cb = self.color_space_bounds,
             t = self.threshold,
+            cc = self.color_count,
             f = self.palette,
+            pa = self.palette_auto,
+            va = self.variants,
+            ps = self.palettes.as_ref().map(|p| p.keys().cloned().collect::<Vec<_>>().join(", ")).unwrap_or("None".into()),
             con = self.check_contrast,
             sat = self.saturation,
             a = self.alpha,
+            ac = self.alpha_cutoff,
+            r = self.region.map(|r| r.to_string()).unwrap_or("None".into()),
+            fr = self.frames,
+            tm = self.tone_map,
+            icc = self.ignore_icc,
+            w = self.weighting,
+            sc = self.scoring,
+            tb = self.trim_borders,
+            ic = self.ignore_colors.as_ref().map(|v| v.iter().map(|c| c.to_string()).collect::<Vec<_>>().join(", ")).unwrap_or("None".into()),
+            it = self.ignore_tolerance,
             dir = self.dir.display(),
             file = self.file.display(),
             )
@@ -470,14 +923,135 @@ fn validate_threshold<'de, D>(d: D) -> Result<Option<u8>, D::Error>
 }
 
 
+fn validate_color_count<'de, D>(d: D) -> Result<Option<u8>, D::Error>
+    where D: serde::de::Deserializer<'de>
+{
+    use serde::de;
+    use crate::colorspaces::{MIN_COLS, MAX_COLS};
+
+    let value = Option::deserialize(d)?;
+    let value = match value {
+        Some(s) => s,
+        None => return Ok(None),
+    };
+
+    if (MIN_COLS..=MAX_COLS).contains(&value) { return Ok(Some(value)); }
+
+    Err(de::Error::invalid_value(de::Unexpected::Unsigned(value as u64), &"a value between 6 and 16."))
+}
+
+fn validate_frames<'de, D>(d: D) -> Result<Option<u16>, D::Error>
+    where D: serde::de::Deserializer<'de>
+{
+    use serde::de;
+    use crate::backends::MAX_FRAMES;
+
+    let value = Option::deserialize(d)?;
+    let value = match value {
+        Some(s) => s,
+        None => return Ok(None),
+    };
+
+    if (1..=MAX_FRAMES).contains(&value) { return Ok(Some(value)); }
+
+    Err(de::Error::invalid_value(de::Unexpected::Unsigned(value as u64), &"a value between 1 and 256."))
+}
+
+/// Every `[palettes.<name>]` without slots going in circles, nor named like a built in palette.
+fn validate_palettes<'de, D>(d: D) -> Result<Option<HashMap<String, crate::palettes::Custom>>, D::Error>
+    where D: serde::de::Deserializer<'de>
+{
+    use serde::de;
+    use crate::palettes::PaletteName;
+
+    let value: Option<HashMap<String, crate::palettes::Custom>> = Option::deserialize(d)?;
+
+    for (name, p) in value.iter().flatten() {
+        if let Ok(PaletteName::Builtin(b)) = name.parse() {
+            return Err(de::Error::custom(format!("palettes.{name}: there is already a {b} palette, choose another name")));
+        }
+        p.validate().map_err(|e| de::Error::custom(format!("palettes.{name}: {e}")))?;
+    }
+
+    Ok(value)
+}
+
+/// Keys of the config file that are either only the name, `key = "name"`, or a `[key]` table with
+/// the `name` and some parameters `P`, flattened next to it. Returns both, `P::default()` when
+/// only the name is given. Keys of the table other than `name` and the `fields` of `P` are an
+/// error, `expecting` goes in the message of any other type, like "a backend name or a [backend] table".
+pub(crate) fn str_or_table<'de, D, N, P>(d: D, expecting: &'static str, fields: &'static [&'static str]) -> Result<(Option<N>, P), D::Error>
+    where D: serde::de::Deserializer<'de>,
+          N: Deserialize<'de>,
+          P: Deserialize<'de> + Default,
+{
+    use std::marker::PhantomData;
+    use serde::de::{self, IntoDeserializer, MapAccess, Visitor};
+
+    struct StrOrTable<N, P> {
+        expecting: &'static str,
+        fields: &'static [&'static str],
+        types: PhantomData<(N, P)>,
+    }
+
+    impl<'de, N: Deserialize<'de>, P: Deserialize<'de> + Default> Visitor<'de> for StrOrTable<N, P> {
+        type Value = (Option<N>, P);
+
+        fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+            f.write_str(self.expecting)
+        }
+
+        fn visit_str<E: de::Error>(self, v: &str) -> Result<Self::Value, E> {
+            Ok((Some(N::deserialize(v.into_deserializer())?), P::default()))
+        }
+
+        fn visit_map<M: MapAccess<'de>>(self, map: M) -> Result<Self::Value, M::Error> {
+            #[derive(Deserialize)]
+            #[serde(bound(deserialize = "N: Deserialize<'de>, P: Deserialize<'de>"))]
+            struct Table<N, P> {
+                name: Option<N>,
+                #[serde(flatten)]
+                params: P,
+                /// what's left, `deny_unknown_fields` doesn't work along with `flatten`
+                #[serde(flatten)]
+                rest: std::collections::BTreeMap<String, de::IgnoredAny>,
+            }
+
+            let t = Table::<N, P>::deserialize(de::value::MapAccessDeserializer::new(map))?;
+            if let Some(k) = t.rest.keys().next() {
+                return Err(de::Error::unknown_field(k, self.fields));
+            }
+            Ok((t.name, t.params))
+        }
+    }
+
+    d.deserialize_any(StrOrTable { expecting, fields, types: PhantomData })
+}
+
 impl From<PrettyConfig> for Config {
     fn from(value: PrettyConfig) -> Self {
         Self {
             alpha: value.alpha,
+            alpha_cutoff: value.alpha_cutoff,
+            region: value.region,
+            frames: value.frames,
+            tone_map: value.tone_map,
+            ignore_icc: value.ignore_icc,
+            weighting: value.weighting,
+            scoring: value.scoring,
+            trim_borders: value.trim_borders,
+            ignore_colors: value.ignore_colors,
+            ignore_tolerance: value.ignore_tolerance,
             threshold: value.threshold,
-            backend_user: value.backend,
-            color_space_user: value.color_space,
-            palette_user: value.palette,
+            color_count: value.color_count,
+            backend_user: value.backend.and_then(|b| b.name),
+            backend_params: value.backend.map(|b| b.params).unwrap_or_default(),
+            color_space_user: value.color_space.and_then(|c| c.name),
+            color_space_bounds: value.color_space.map(|c| c.bounds).unwrap_or_default(),
+            palette_user: value.palette.as_ref().and_then(|p| p.name.clone()),
+            palette_auto: value.palette.map(|p| p.auto).unwrap_or_default(),
+            variants: value.variants,
+            palettes: value.palettes,
             fallback_generator: value.fallback_generator,
             check_contrast: value.check_contrast,
             saturation: value.saturation,
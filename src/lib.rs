@@ -21,8 +21,32 @@ use owo_colors::OwoColorize;
 
 use self::colors::Colors;
 use self::colorspaces::FallbackGenerator;
-use self::args::Globals;
+use self::args::{Globals, Input};
+use self::backends::{Backend, Source};
+use self::palettes::Mode;
 
+/// The colorspace didn't find enough colors in the image.
+#[derive(Debug, thiserror::Error)]
+#[error("Not enough colors!")]
+pub struct NotEnoughColors;
+
+/// What [`gen_colors`] made, along with what it ended up using to make it.
+#[derive(Debug)]
+pub struct Generated {
+    pub colors: Colors,
+    /// The backend that worked, the one chosen by `backend = "auto"`, if that's the case
+    pub backend: Backend,
+    /// Threshold used, see [`crate::config::Config::threshold_mode`]
+    pub th: u8,
+    /// How many colors the colorspace found with `th`, which may not be the `color_count` asked
+    /// for. `None` with presets, which don't use one.
+    pub count: Option<usize>,
+    /// Whether the palette is dark or light, see [`crate::config::Config::palette_mode`]
+    pub mode: Mode,
+    /// Part of each image read, see [`backends::Pixels::crop`]. Empty if the backend output was
+    /// cached, since the images weren't read.
+    pub crops: Vec<Option<(u32, u32, u32, u32)>>,
+}
 
 /// Simple wrapper around spinner, to avoid allocations and the like.
 //#[derive(Debug)]
@@ -87,15 +111,86 @@ impl Globals {
 }
 
 
-/// How [`crate::colors::Colors`] is filled, returns the colors itself and a bool that indicates whether
-/// [`backends`] had some warnings or not (ugly workaround ik)
-pub fn gen_colors(file: &std::path::Path, c: &crate::config::Config, dynamic_th: bool, cache_path: &std::path::Path, no_cache: bool, quiet: bool, overwrite_cache: bool) -> anyhow::Result<crate::colors::Colors> {
+/// Read every image with `read` (a backend) and put their pixels together, each image resampled
+/// to its share of the pixels, by its weight, so a big image doesn't drown out a small one. A
+/// single image is returned as is.
+pub fn combine(inputs: &[Input], mut read: impl FnMut(&Source) -> Result<Vec<u8>>) -> Result<Vec<u8>> {
+    if let [one] = inputs { return read(&one.source); }
+
+    let bufs = inputs.iter().map(|i| read(&i.source)).collect::<Result<Vec<_>>>()?;
+    let total: usize = bufs.iter().map(|b| b.len() / 3).sum();
+    let weights: f64 = inputs.iter().map(|i| f64::from(i.weight)).sum();
+
+    let mut ret = Vec::with_capacity(total * 3);
+    for (b, i) in bufs.iter().zip(inputs) {
+        let n = b.len() / 3;
+        if n == 0 { continue; }
+        let share = (total as f64 * f64::from(i.weight) / weights).round() as usize;
+        for k in 0..share {
+            let px = k * n / share * 3;
+            ret.extend_from_slice(&b[px..px + 3]);
+        }
+    }
+
+    Ok(ret)
+}
+
+/// How [`crate::colors::Colors`] is filled, returns the colors along with the backend, threshold
+/// and mode used, see [`Generated`].
+///
+/// More than one image are combined into one palette, see [`combine`].
+///
+/// With `backend = "auto"`, when the chosen backend can't decode the image (or doesn't give enough
+/// colors), the next one is tried, see [`crate::config::Config::backends`] and [`falls_back`].
+///
+/// Every file is read once, the cache hashes the same bytes the backends decode, see [`Source::read`].
+pub fn gen_colors(inputs: &[Input], c: &crate::config::Config, dynamic_th: bool, cache_path: &std::path::Path, no_cache: bool, quiet: bool, overwrite_cache: bool) -> anyhow::Result<Generated> {
+    let inputs = &inputs.iter()
+        .map(|i| Ok(Input { source: i.source.read()?, weight: i.weight }))
+        .collect::<Result<Vec<_>>>()?;
+    let Some(first) = inputs.first() else { anyhow::bail!("No image given.") };
+    let mut backends = c.backends(&first.source)?.into_iter();
+    let mut backend = backends.next().expect("there is always one backend");
+
+    loop {
+        match gen_colors_with(inputs, c, backend, dynamic_th, cache_path, no_cache, quiet, overwrite_cache) {
+            Ok(gen) => return Ok(gen),
+            Err(e) if falls_back(&e) => {
+                let Some(next) = backends.next() else { return Err(e) };
+                if !quiet {
+                    println!("\n[{w}] {b}: {backend} failed ({e}), trying {next}",
+                        w = "W".red().bold(),
+                        b = "auto backend".magenta().bold(),
+                        next = next.bold().color(next.col()),
+                    );
+                }
+                backend = next;
+            },
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// Whether another backend could do better after the error `e`: the image couldn't be decoded, or
+/// there weren't enough colors. Reading the file, or writing the cache, fails the same way with any
+/// backend.
+pub fn falls_back(e: &anyhow::Error) -> bool {
+    use image::ImageError;
+
+    e.chain().any(|e| e.is::<NotEnoughColors>()
+        || matches!(e.downcast_ref::<ImageError>(), Some(i) if !matches!(i, ImageError::IoError(_))))
+}
+
+/// [`gen_colors`] with the `backend`.
+#[allow(clippy::too_many_arguments)]
+fn gen_colors_with(inputs: &[Input], c: &crate::config::Config, backend: Backend, dynamic_th: bool, cache_path: &std::path::Path, no_cache: bool, quiet: bool, overwrite_cache: bool) -> anyhow::Result<Generated> {
 
     let gen = &c.fallback_generator.unwrap_or_default();
-    let ord = &c.palette.sort_ord();
-    let dynamic = if c.threshold.is_some() && !dynamic_th { false } else { true };
+    let ord = &c.sort_ord();
+    let th = c.threshold_mode(dynamic_th);
 
-    let cache = cache::Cache::new(file, c, cache_path)?;
+    let cache = cache::Cache::with_backend(inputs, c, backend, cache_path)?;
+    let parser = backend.parser(&c.backend_params);
     use cache::IsCached as C;
 
     // Having to only read the schemepalette is TOO FAST to have the spinner.
@@ -105,57 +200,69 @@ pub fn gen_colors(file: &std::path::Path, c: &crate::config::Config, dynamic_th:
     // println!("{:?}", cache.is_cached_all());
 
     if overwrite_cache {
-            let rgb8s = c.backend.main()(file)?;
+            let mut crops = Vec::with_capacity(inputs.len());
+            let rgb8s = combine(inputs, |f| {
+                let p = parser.read(f, &c.loader())?;
+                crops.push(p.crop);
+                c.weigh(p, f)
+            })?;
             if !no_cache { cache.write_backend(&rgb8s)? } //BACKEND
+            let rgb8s = c.filter_pixels(&rgb8s)?;
 
-            let cs = match c.color_space.run(dynamic, &rgb8s, c.threshold.unwrap_or_default(), gen, ord) {
+            let cs = match c.color_space.run(th, &rgb8s, gen, ord, &c.color_space_bounds, &c.scoring.unwrap_or_default()) {
                 Some(s) => s,
-                None => anyhow::bail!("Not enough colors!"),
+                None => return Err(NotEnoughColors.into()),
             };
 
-            let (ref top, ref orig, warn) = cs;
+            let (ref top, ref orig, warn, th, ref counts) = cs;
             if !no_cache { cache.write_cs(&cs)? } //COLORSPACE
 
 
-            let mut colors = c.palette.run(top.to_vec(), orig.to_vec());
+            let mut colors = c.run_palette(top.to_vec(), orig.to_vec(), counts);
+            let mode = c.palette_mode(orig, counts, &colors);
             if !no_cache { cache.write_palette(&colors)? } //COLORS
             postcolor(c, &mut colors);
             if warn { spi.stop_warn(gen) } else { spi.stop() }
-            Ok(colors)
+            Ok(Generated { colors, backend, th, count: Some(orig.len()), mode, crops })
     } else {
         match is_cached_all {
             C::BackendnCSnPalette => { // (cache)Palette -> Done
                 let mut colors = cache.read_palette()?;
+                let (_, orig, _, th, counts) = cache.read_cs()?;
+                let mode = c.palette_mode(&orig, &counts, &colors);
                 postcolor(c, &mut colors);
                 spi.stop();
                 if !quiet { print!("[{info}] Using cache at {}", cache.name.display(), info = "I".blue().bold()); }
-                Ok(colors)
+                Ok(Generated { colors, backend, th, count: Some(orig.len()), mode, crops: vec![] })
             },
             C::BackendnCS => { // (cached)CS -> Palette -> Done
-                let (top, orig, warn) = cache.read_cs()?;
-                let mut colors = c.palette.run(top, orig);
+                let (top, orig, warn, th, counts) = cache.read_cs()?;
+                let mut colors = c.run_palette(top, orig.clone(), &counts);
+                let mode = c.palette_mode(&orig, &counts, &colors);
                 if !no_cache { cache.write_palette(&colors)? } // COLORS
                 postcolor(c, &mut colors);
                 if warn { spi.stop_warn(gen) } else { spi.stop() }
                 if !quiet { print!("[{info}] Using cache at {}", cache.name.display(), info = "I".blue().bold()); }
-                Ok(colors)
+                Ok(Generated { colors, backend, th, count: Some(orig.len()), mode, crops: vec![] })
             },
             C::Backend => { // (cached)Backend -> CS -> Palette -> Done
                 let rgb8s = cache.read_backend()?;
+                let rgb8s = c.filter_pixels(&rgb8s)?;
 
-                let cs = match c.color_space.run(dynamic, &rgb8s, c.threshold.unwrap_or_default(), gen, ord) {
+                let cs = match c.color_space.run(th, &rgb8s, gen, ord, &c.color_space_bounds, &c.scoring.unwrap_or_default()) {
                     Some(s) => s,
-                    None => anyhow::bail!("Not enough colors!"),
+                    None => return Err(NotEnoughColors.into()),
                 };
 
-                let (ref top, ref orig, warn) = cs;
+                let (ref top, ref orig, warn, th, ref counts) = cs;
                 if !no_cache { cache.write_cs(&cs)? } //COLORSPACE
 
-                let mut colors = c.palette.run(top.to_vec(), orig.to_vec());
+                let mut colors = c.run_palette(top.to_vec(), orig.to_vec(), counts);
+                let mode = c.palette_mode(orig, counts, &colors);
                 if !no_cache { cache.write_palette(&colors)? } //COLORS
                 postcolor(c, &mut colors);
                 if warn { spi.stop_warn(gen); } else { spi.stop(); }
-                Ok(colors)
+                Ok(Generated { colors, backend, th, count: Some(orig.len()), mode, crops: vec![] })
             },
             C::Preset => {
                 // TODO Cache PresetnBackend scenario
@@ -164,38 +271,70 @@ pub fn gen_colors(file: &std::path::Path, c: &crate::config::Config, dynamic_th:
                     cache.read_preset()?
                 } else {
                     let p = c.preset.as_ref().expect("Already validated that this isn't None.");
-                    let rgb8s = p.backend(file)?;
+                    let rgb8s = combine(inputs, |f| p.backend(f, &c.loader()))?;
                     let cs = p.cs(rgb8s);
                     p.palette(cs)
                 };
 
                 if !no_cache { cache.write_preset(&colors)? }
                 spi.stop();
-                Ok(colors)
+                // presets don't use one
+                let mode = Mode::of_colors(&colors);
+                Ok(Generated { colors, backend, th: c.true_th, count: None, mode, crops: vec![] })
             },
             C::None => { // Generate Backend from scratch => CS -> Palette -> Done.
-                let rgb8s = c.backend.main()(file)?;
+                let mut crops = Vec::with_capacity(inputs.len());
+                let rgb8s = combine(inputs, |f| {
+                    let p = parser.read(f, &c.loader())?;
+                    crops.push(p.crop);
+                    c.weigh(p, f)
+                })?;
                 if !no_cache { cache.write_backend(&rgb8s)? } //BACKEND
+                let rgb8s = c.filter_pixels(&rgb8s)?;
 
-                let cs = match c.color_space.run(dynamic, &rgb8s, c.threshold.unwrap_or_default(), gen, ord) {
+                let cs = match c.color_space.run(th, &rgb8s, gen, ord, &c.color_space_bounds, &c.scoring.unwrap_or_default()) {
                     Some(s) => s,
-                    None => anyhow::bail!("Not enough colors!"),
+                    None => return Err(NotEnoughColors.into()),
                 };
 
-                let (ref top, ref orig, warn) = cs;
+                let (ref top, ref orig, warn, th, ref counts) = cs;
                 if !no_cache { cache.write_cs(&cs)? } //COLORSPACE
 
-                let mut colors = c.palette.run(top.to_vec(), orig.to_vec());
+                let mut colors = c.run_palette(top.to_vec(), orig.to_vec(), counts);
+                let mode = c.palette_mode(orig, counts, &colors);
                 if !no_cache { cache.write_palette(&colors)? } //COLORS
                 postcolor(c, &mut colors);
                 if warn { spi.stop_warn(gen) } else { spi.stop() }
-                Ok(colors)
+                Ok(Generated { colors, backend, th, count: Some(orig.len()), mode, crops })
             },
         }
 
     }
 }
 
+/// Palette of a single image, usually in memory, like the bytes of an encoded image (`Vec<u8>`,
+/// `&[u8]`) or an [`image::DynamicImage`], see [`Source`]. Unlike [`gen_colors`], nothing is
+/// cached nor printed, and `backend = "auto"` tries the backends on its own.
+pub fn palette(img: impl Into<Source>, c: &crate::config::Config) -> Result<Colors> {
+    let img = img.into();
+
+    let mut colors = match &c.preset {
+        Some(p) => p.palette(p.cs(p.backend(&img, &c.loader())?)),
+        None => {
+            let rgb8s = c.weigh(c.parser().read(&img, &c.loader())?, &img)?;
+            let rgb8s = c.filter_pixels(&rgb8s)?;
+
+            let gen = &c.fallback_generator.unwrap_or_default();
+            let (top, orig, .., counts) = c.color_space.run(c.threshold_mode(false), &rgb8s, gen, &c.sort_ord(), &c.color_space_bounds, &c.scoring.unwrap_or_default())
+                .ok_or(NotEnoughColors)?;
+            c.run_palette(top, orig, &counts)
+        },
+    };
+
+    postcolor(c, &mut colors);
+    Ok(colors)
+}
+
 /// These steps are not cached, since they are variable and cheap operations. Keep the original
 /// scheme in which this is done and then apply these.
 pub fn postcolor(c: &crate::config::Config, colors: &mut crate::colors::Colors) {
@@ -6,7 +6,7 @@ use anyhow::Result;
 use owo_colors::OwoColorize;
 
 use wallust::{
-    args, cache, config::{self, WalStr}, gen_colors, themes
+    args, cache, config::{self, WalStr}, gen_colors, themes, palettes::Mode
 };
 
 // Check if the platform is windows and if so, include the billgates module
@@ -37,11 +37,11 @@ fn main() -> Result<()> {
     match cli.subcmds {
         args::Subcmds::Run(s) => {
             // use serde to read wallust.toml, this is mut only because the user could provide a `-C custom_config.toml`
-            run(&mut conf, &cache_path, &s, &cli.globals)?
+            run(&mut conf, &cache_path, &s.read_stdin()?, &cli.globals)?
         },
         args::Subcmds::Pywal(s) => {
             match s.file {
-                Some(_) => run(&mut conf, &cache_path, &s.into(), &cli.globals)?, // -i "...png"
+                Some(_) => run(&mut conf, &cache_path, &args::WallustArgs::from(s).read_stdin()?, &cli.globals)?, // -i "...png"
                 None => { //must be using a file or a theme name `-f file.jpg`
                     let theme = &s.theme.expect("SHOULD BE NON EMPTY, from clap");
                     if !quiet { println!("[{info}] {}: Using {theme}", "theme".magenta().bold(), theme = theme.italic()); }
@@ -50,7 +50,7 @@ fn main() -> Result<()> {
 
                     cli.globals.set_seq(&colors, &cache_path)?;
                     cli.globals.update_cur(&colors)?;
-                    if ! skip_templates { conf.write_entry(&WalStr::Theme(theme.to_owned()), &colors, quiet)?; }
+                    if ! skip_templates { conf.write_entry(&WalStr::Theme(theme.to_owned()), &colors, &[], &[], quiet)?; }
                 }
             }
         },
@@ -72,7 +72,7 @@ fn main() -> Result<()> {
             cli.globals.update_cur(&colors)?;
 
             //empty image_path cuz it's not used
-            if ! skip_templates { conf.write_entry(&WalStr::Theme(theme), &colors, quiet)?; }
+            if ! skip_templates { conf.write_entry(&WalStr::Theme(theme), &colors, &[], &[], quiet)?; }
             if ! quiet { colors.done() }
         },
         args::Subcmds::Cs { colorscheme, format } => {
@@ -91,7 +91,7 @@ fn main() -> Result<()> {
             cli.globals.update_cur(&colors)?;
 
             //empty image_path cuz it's not used
-            if ! skip_templates { conf.write_entry(&walstr, &colors, quiet)?;
+            if ! skip_templates { conf.write_entry(&walstr, &colors, &[], &[], quiet)?;
             }
             if ! quiet { colors.done() }
         },
@@ -118,6 +118,22 @@ fn run(conf: &mut config::Config, cache_path: &Path, cli: &args::WallustArgs, g:
 
     // apply --backend or --filter or --colorspace
     conf.customs_cli(cli);
+    conf.check_bounds()?;
+    conf.check_palette()?;
+
+    // without any FILE, these are the images of the outputs
+    let inputs = cli.inputs();
+    for (i, o) in cli.outputs.iter().enumerate() {
+        if cli.outputs[..i].iter().any(|x| x.name == o.name) {
+            anyhow::bail!("The output '{}' was given more than once.", o.name);
+        }
+    }
+
+    // each output chooses its own backend, if `auto`
+    let backend = conf.backend;
+
+    // `backend = "auto"`, choose it now so it's printed
+    conf.auto_backend(cli.file())?;
 
     // auto threshold
     conf.true_th = conf.threshold.unwrap_or_default();
@@ -127,23 +143,76 @@ fn run(conf: &mut config::Config, cache_path: &Path, cli: &args::WallustArgs, g:
 
     // print some info that's gonna be used
     if !g.quiet {
-        let f = match cli.file.file_name() {
-            Some(s) => s.to_string_lossy(),
-            None => cli.file.to_string_lossy(),
-        };
-        println!("[{info}] {img}: {f}", img = "image".magenta().bold());
+        for i in &inputs {
+            let f = match i.source.path() {
+                Some(p) => p.file_name().unwrap_or(p.as_os_str()).to_string_lossy(),
+                None => "stdin".into(),
+            };
+            let w = if inputs.len() > 1 { format!(" (weight {})", i.weight) } else { String::new() };
+            println!("[{info}] {img}: {f}{w}", img = "image".magenta().bold());
+        }
         conf.print();
     }
 
     // Whether to load data from cache or to generate one from scratch
     if !g.quiet && cli.overwrite_cache { println!("[{info}] {c}: Overwriting cache, if present, `-w` flag provided.", c = "cache".magenta().bold()); }
 
-    let colors = gen_colors(&cli.file, conf, cli.dynamic_threshold, cache_path, cli.no_cache, g.quiet, cli.overwrite_cache)?;
+    let gen = gen_colors(&inputs, conf, cli.dynamic_threshold, cache_path, cli.no_cache, g.quiet, cli.overwrite_cache)?;
+    let colors = gen.colors;
+    conf.backend = gen.backend;
+    conf.used_th = gen.th;
+    conf.mode = gen.mode;
+
+    if !g.quiet {
+        // the part read by the backend, without the bars (if trimming) and inside the region
+        for (i, c) in inputs.iter().zip(&gen.crops) {
+            let Some((x, y, w, h)) = c else { continue };
+            print!("\n[{info}] {b}: Reading {w}x{h}+{x}+{y} of {f}", b = "crop".magenta().bold(), f = i.source);
+        }
+        // kmeans makes that many clusters, without any threshold
+        if let Some(n) = gen.count.filter(|_| conf.color_count.is_some() && conf.color_space != wallust::colorspaces::ColorSpace::KMeans) { print!("\n[{info}] {t}: {th} gives {n} colors", t = "threshold".magenta().bold(), th = conf.used_th); }
+        // which one `auto` chose
+        if conf.palette == wallust::palettes::Palette::Auto.into() {
+            let p = conf.palette_for(conf.mode);
+            print!("\n[{info}] {a}: {m} image, using {p}", a = "auto palette".magenta().bold(), m = conf.mode, p = p.bold().color(p.col()));
+        }
+        colors.print();
+    }
+
+    // a palette for every output, with the same settings as the main one
+    let mut outputs = Vec::with_capacity(cli.outputs.len());
+    let chosen = (conf.backend, conf.backend_auto.take());
+    conf.backend = backend;
+    for o in &cli.outputs {
+        if !g.quiet { println!("[{info}] {out}: {n} from {f}", out = "output".magenta().bold(), n = o.name.bold(), f = o.input.source); }
+        let c = gen_colors(std::slice::from_ref(&o.input), conf, cli.dynamic_threshold, cache_path, cli.no_cache, g.quiet, cli.overwrite_cache)?;
+        if !g.quiet { c.colors.print(); }
+        outputs.push((o, c));
+    }
+    (conf.backend, conf.backend_auto) = chosen;
+
+    // the `dark` and `light` palettes, from the same colors as the main one
+    let mut variants = Vec::with_capacity(2);
+    if conf.wants_variants() {
+        let palette = conf.palette.clone();
+        for m in [Mode::Dark, Mode::Light] {
+            conf.palette = conf.palette_auto.pick(m).clone();
+            if !g.quiet { println!("[{info}] {v}: {m} from {p}", v = "variant".magenta().bold(), p = conf.palette.bold().color(conf.palette.col())); }
+            let c = gen_colors(&inputs, conf, cli.dynamic_threshold, cache_path, cli.no_cache, g.quiet, cli.overwrite_cache)?;
+            if !g.quiet { c.colors.print(); }
+            variants.push((m, c));
+        }
+        conf.palette = palette;
+    }
 
-    if !g.quiet { colors.print(); }
     g.set_seq(&colors, cache_path)?;
     g.update_cur(&colors)?;
-    if !g.skip_templates { conf.write_entry(&WalStr::Path(cli.file.clone()), &colors, g.quiet)?; }
+    // images from stdin don't have a path, `wallpaper` is `-`
+    let wallpaper = match cli.file().path() {
+        Some(p) => WalStr::Path(p.to_path_buf()),
+        None => WalStr::Theme(cli.file().to_string()),
+    };
+    if !g.skip_templates { conf.write_entry(&wallpaper, &colors, &outputs, &variants, g.quiet)?; }
 
     // Cache colors
     if !g.quiet && cli.no_cache { println!("[{info}] {}: Skipping caching the palette, `-n` flag provided.", "cache".magenta().bold()); }
@@ -152,8 +221,8 @@ fn run(conf: &mut config::Config, cache_path: &Path, cli: &args::WallustArgs, g:
     if !g.quiet { colors.done(); }
 
     #[cfg(target_os = "windows")]
-    {
-        let file_path = cli.file.canonicalize().unwrap_or_else(|_| cli.file.clone());
+    if let Some(file) = cli.file().path() {
+        let file_path = file.canonicalize().unwrap_or_else(|_| file.to_path_buf());
         
         match billgates::background::set_wallpaper(&file_path) {
 
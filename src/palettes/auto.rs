@@ -0,0 +1,92 @@
+//! # Auto palette
+//! Chooses between a dark and a light palette by how light the image is, so the same config
+//! works with bright and dark wallpapers. Which ones can be set in a `[palette]` table:
+//! ```toml
+//! [palette]
+//! name = "auto"
+//! dark = "dark16"
+//! light = "light16"
+//! ```
+use palette::{FromColor, Oklab};
+
+use super::*;
+
+/// Images lighter than this, in OKLab lightness (0 to 1), get the `light` palette.
+pub const LIGHT: f32 = 0.6;
+
+/// Whether the image, or a palette, is dark or light. Available to templates as `mode`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema" , derive(schemars::JsonSchema))]
+#[serde(rename_all = "lowercase")]
+pub enum Mode {
+    #[default]
+    Dark,
+    Light,
+}
+
+/// The palettes `auto` chooses from, the `dark` and `light` keys of the `[palette]` table.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default)]
+pub struct Auto {
+    /// Palette for dark images (default is dark)
+    pub dark: PaletteName,
+    /// Palette for light images (default is light)
+    pub light: PaletteName,
+}
+
+impl Default for Auto {
+    fn default() -> Self {
+        Self { dark: Palette::Dark.into(), light: Palette::Light.into() }
+    }
+}
+
+impl Auto {
+    /// The palette for an image in this [`Mode`]
+    pub fn pick(&self, m: Mode) -> &PaletteName {
+        match m {
+            Mode::Dark => &self.dark,
+            Mode::Light => &self.light,
+        }
+    }
+
+    /// Neither of them can be `auto`.
+    pub fn validate(&self) -> Result<(), String> {
+        match (&self.dark, &self.light) {
+            (PaletteName::Builtin(Palette::Auto), _) => Err("`dark` can't be auto".into()),
+            (_, PaletteName::Builtin(Palette::Auto)) => Err("`light` can't be auto".into()),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl Mode {
+    /// Of the image, by the average lightness of the colors found (`orig`), each one weighted by
+    /// how many pixels it has (`counts`).
+    pub fn of_image(orig: &[Srgb], counts: &[usize]) -> Self {
+        let (sum, weights) = orig.iter().zip(counts)
+            .map(|(&c, &n)| (Oklab::from_color(c.into_linear()).l, n as f32))
+            .fold((0.0, 0.0), |(s, w), (l, wi)| (s + l * wi, w + wi));
+
+        match weights > 0.0 && sum / weights > LIGHT {
+            true => Mode::Light,
+            false => Mode::Dark,
+        }
+    }
+
+    /// Of a palette, by its background
+    pub fn of_colors(c: &Colors) -> Self {
+        match Oklab::from_color(c.background.0.into_linear()).l > 0.5 {
+            true => Mode::Light,
+            false => Mode::Dark,
+        }
+    }
+}
+
+impl fmt::Display for Mode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Mode::Dark => write!(f, "dark"),
+            Mode::Light => write!(f, "light"),
+        }
+    }
+}
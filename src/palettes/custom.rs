@@ -0,0 +1,322 @@
+//! # Custom palettes
+//! Palettes declared in the config file, under `[palettes.<name>]`, without writing any Rust. Every
+//! slot is an [`Expr`], a color followed by the changes done to it, from left to right:
+//! ```toml
+//! [palettes.mine]
+//! base = "dark"           # the slots left out come from this palette (dark by default)
+//! order = "darkfirst"     # how `top` is sorted (the one of `base` by default)
+//! background = "darkest darken 0.8"
+//! color1 = "top[3] saturate 0.2"
+//! color8 = "color7 darken 0.3"
+//! cursor = "foreground blend #EEEEEE"
+//! ```
+//! Slots can refer to each other, as long as they don't go in circles.
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+
+use palette::Srgb;
+use serde::Deserialize;
+
+use super::Palette;
+use crate::colors::{Colors, Myrgb};
+use crate::colorspaces::ColorOrder;
+
+/// A `[palettes.<name>]` table
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[cfg_attr(feature = "schema" , derive(schemars::JsonSchema))]
+pub struct Custom {
+    /// Palette that fills the slots not set (default is dark)
+    pub base: Option<Palette>,
+    /// How the colors are sorted, `top[0]` being the first (default is the one of `base`)
+    pub order: Option<Order>,
+    /// What goes in every slot: background, foreground, cursor and color0 to color15
+    #[serde(flatten)]
+    #[cfg_attr(feature = "schema", schemars(with = "BTreeMap<Slot, String>"))]
+    pub slots: BTreeMap<Slot, Expr>,
+}
+
+/// Same as [`ColorOrder`], in the config file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[cfg_attr(feature = "schema" , derive(schemars::JsonSchema))]
+#[serde(rename_all = "lowercase")]
+pub enum Order {
+    /// `top[0]` is the lightest
+    #[serde(alias = "light-first")]
+    LightFirst,
+    /// `top[0]` is the darkest
+    #[serde(alias = "dark-first")]
+    DarkFirst,
+}
+
+/// The colors of a palette, like the fields of [`Colors`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Deserialize)]
+#[cfg_attr(feature = "schema" , derive(schemars::JsonSchema))]
+#[serde(rename_all = "lowercase")]
+pub enum Slot {
+    Background, Foreground, Cursor,
+    Color0, Color1, Color2,  Color3,  Color4,  Color5,  Color6,  Color7,
+    Color8, Color9, Color10, Color11, Color12, Color13, Color14, Color15,
+}
+
+/// A color: one of the colors found, a fixed one or another slot.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Atom {
+    /// `top[N]`, sorted by the [`Order`]. If there are less colors, it goes around from the start.
+    Top(usize),
+    /// `orig[N]`, the most used colors first
+    Orig(usize),
+    /// `darkest`
+    Darkest,
+    /// `lightest`
+    Lightest,
+    /// `#RRGGBB`
+    Hex(Myrgb),
+    /// The color of another slot, `color7`
+    Slot(Slot),
+}
+
+/// A change to a color, same as the ones the built in palettes use, see [`Myrgb`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Op {
+    /// `darken 0.2`
+    Darken(f32),
+    /// `lighten 0.2`
+    Lighten(f32),
+    /// `saturate 0.2`
+    Saturate(f32),
+    /// `blend #EEEEEE`, half and half with another color
+    Blend(Atom),
+    /// `complementary`
+    Complementary,
+}
+
+/// What goes in a slot: `top[3] darken 0.2 blend #EEEEEE`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Expr {
+    pub color: Atom,
+    pub ops: Vec<Op>,
+}
+
+impl Custom {
+    /// How `top` is sorted
+    pub fn sort_ord(&self) -> ColorOrder {
+        match self.order {
+            Some(Order::LightFirst) => ColorOrder::LightFirst,
+            Some(Order::DarkFirst) => ColorOrder::DarkFirst,
+            None => self.base.unwrap_or_default().sort_ord(),
+        }
+    }
+
+    /// Fills the palette, `c` sorted by [`Custom::sort_ord`] and `orig` by how much they are used.
+    /// `base` gets `c` reversed if it wants the other order.
+    pub fn run(&self, c: Vec<Srgb>, orig: Vec<Srgb>) -> Colors {
+        let base = self.base.unwrap_or_default();
+        let mut base_c = c.clone();
+        if self.sort_ord() != base.sort_ord() {
+            base_c.reverse();
+        }
+        let base = base.run(base_c, orig.clone());
+        let env = Env { custom: self, base, c: &c, orig: &orig };
+
+        let mut ret = base;
+        for (&slot, e) in &self.slots {
+            *slot.get_mut(&mut ret) = env.eval(e);
+        }
+        ret
+    }
+
+    /// Slots can't refer to each other in circles, and `base` can't be `auto`.
+    pub fn validate(&self) -> Result<(), String> {
+        if self.base == Some(Palette::Auto) {
+            return Err("`base` can't be auto".into());
+        }
+
+        fn visit(custom: &Custom, slot: Slot, path: &mut Vec<Slot>) -> Result<(), String> {
+            if let Some(i) = path.iter().position(|&s| s == slot) {
+                let names = path[i..].iter().chain([&slot]).map(|s| s.to_string()).collect::<Vec<_>>();
+                return Err(format!("the slots go in circles: {}", names.join(" -> ")));
+            }
+            let Some(e) = custom.slots.get(&slot) else { return Ok(()) };
+            path.push(slot);
+            for s in e.slots() { visit(custom, s, path)?; }
+            path.pop();
+            Ok(())
+        }
+
+        self.slots.keys().try_for_each(|&s| visit(self, s, &mut vec![]))
+    }
+}
+
+/// What the expressions are evaluated with
+struct Env<'a> {
+    custom: &'a Custom,
+    /// The palette of `base`, for the slots not set
+    base: Colors,
+    c: &'a [Srgb],
+    orig: &'a [Srgb],
+}
+
+impl Env<'_> {
+    fn eval(&self, e: &Expr) -> Myrgb {
+        e.ops.iter().fold(self.atom(e.color), |col, op| match *op {
+            Op::Darken(a) => col.darken(a),
+            Op::Lighten(a) => col.lighten(a),
+            Op::Saturate(a) => col.saturate(a),
+            Op::Blend(other) => col.blend(self.atom(other)),
+            Op::Complementary => col.complementary(),
+        })
+    }
+
+    fn atom(&self, a: Atom) -> Myrgb {
+        let (first, last) = (self.c[0], self.c[self.c.len() - 1]);
+        let (darkest, lightest) = match self.custom.sort_ord() {
+            ColorOrder::LightFirst => (last, first),
+            ColorOrder::DarkFirst => (first, last),
+        };
+
+        match a {
+            Atom::Top(n) => self.c[n % self.c.len()].into(),
+            Atom::Orig(n) => self.orig[n % self.orig.len()].into(),
+            Atom::Darkest => darkest.into(),
+            Atom::Lightest => lightest.into(),
+            Atom::Hex(h) => h,
+            // no circles, checked by [`Custom::validate`]
+            Atom::Slot(s) => match self.custom.slots.get(&s) {
+                Some(e) => self.eval(e),
+                None => *s.get(&self.base),
+            },
+        }
+    }
+}
+
+impl Expr {
+    /// The slots it refers to
+    fn slots(&self) -> impl Iterator<Item = Slot> + '_ {
+        let blends = self.ops.iter().filter_map(|op| match op { Op::Blend(a) => Some(a), _ => None });
+        std::iter::once(&self.color).chain(blends).filter_map(|a| match a { Atom::Slot(s) => Some(*s), _ => None })
+    }
+}
+
+impl FromStr for Expr {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut words = s.split_whitespace();
+        let color = words.next().ok_or("expected a color, found nothing")?.parse()?;
+        let mut ops = vec![];
+
+        while let Some(w) = words.next() {
+            let amount = |a: Option<&str>| match a.map(str::parse::<f32>) {
+                Some(Ok(a)) if (0.0..=1.0).contains(&a) => Ok(a),
+                _ => Err(format!("`{w}` needs an amount between 0 and 1")),
+            };
+
+            ops.push(match w {
+                "darken" => Op::Darken(amount(words.next())?),
+                "lighten" => Op::Lighten(amount(words.next())?),
+                "saturate" => Op::Saturate(amount(words.next())?),
+                "blend" => Op::Blend(words.next().ok_or("`blend` needs a color")?.parse()?),
+                "complementary" => Op::Complementary,
+                _ => return Err(format!("unknown change `{w}`, expected darken, lighten, saturate, blend or complementary")),
+            });
+        }
+
+        Ok(Self { color, ops })
+    }
+}
+
+impl FromStr for Atom {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let index = |prefix: &str| {
+            s.strip_prefix(prefix)
+                .and_then(|r| r.strip_prefix('['))
+                .and_then(|r| r.strip_suffix(']'))
+                .map(|n| n.parse::<usize>().map_err(|_| format!("`{s}` needs an index, like {prefix}[0]")))
+        };
+
+        if let Some(n) = index("top") { return Ok(Atom::Top(n?)) }
+        if let Some(n) = index("orig") { return Ok(Atom::Orig(n?)) }
+
+        match s {
+            "darkest" => Ok(Atom::Darkest),
+            "lightest" => Ok(Atom::Lightest),
+            _ if s.starts_with('#') => s.parse::<Srgb<u8>>()
+                .map(|c| Atom::Hex(c.into()))
+                .map_err(|e| format!("`{s}` isn't a color: {e}")),
+            _ => Slot::deserialize(serde::de::value::StrDeserializer::<serde::de::value::Error>::new(s))
+                .map(Atom::Slot)
+                .map_err(|_| format!("unknown color `{s}`, expected top[N], orig[N], darkest, lightest, a #RRGGBB color or another slot")),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Expr {
+    fn deserialize<D: serde::Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        String::deserialize(d)?.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+impl Slot {
+    fn get(self, c: &Colors) -> &Myrgb {
+        match self {
+            Slot::Background => &c.background,
+            Slot::Foreground => &c.foreground,
+            Slot::Cursor => &c.cursor,
+            Slot::Color0 => &c.color0,
+            Slot::Color1 => &c.color1,
+            Slot::Color2 => &c.color2,
+            Slot::Color3 => &c.color3,
+            Slot::Color4 => &c.color4,
+            Slot::Color5 => &c.color5,
+            Slot::Color6 => &c.color6,
+            Slot::Color7 => &c.color7,
+            Slot::Color8 => &c.color8,
+            Slot::Color9 => &c.color9,
+            Slot::Color10 => &c.color10,
+            Slot::Color11 => &c.color11,
+            Slot::Color12 => &c.color12,
+            Slot::Color13 => &c.color13,
+            Slot::Color14 => &c.color14,
+            Slot::Color15 => &c.color15,
+        }
+    }
+
+    fn get_mut(self, c: &mut Colors) -> &mut Myrgb {
+        match self {
+            Slot::Background => &mut c.background,
+            Slot::Foreground => &mut c.foreground,
+            Slot::Cursor => &mut c.cursor,
+            Slot::Color0 => &mut c.color0,
+            Slot::Color1 => &mut c.color1,
+            Slot::Color2 => &mut c.color2,
+            Slot::Color3 => &mut c.color3,
+            Slot::Color4 => &mut c.color4,
+            Slot::Color5 => &mut c.color5,
+            Slot::Color6 => &mut c.color6,
+            Slot::Color7 => &mut c.color7,
+            Slot::Color8 => &mut c.color8,
+            Slot::Color9 => &mut c.color9,
+            Slot::Color10 => &mut c.color10,
+            Slot::Color11 => &mut c.color11,
+            Slot::Color12 => &mut c.color12,
+            Slot::Color13 => &mut c.color13,
+            Slot::Color14 => &mut c.color14,
+            Slot::Color15 => &mut c.color15,
+        }
+    }
+}
+
+impl fmt::Display for Slot {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Slot::Background => write!(f, "background"),
+            Slot::Foreground => write!(f, "foreground"),
+            Slot::Cursor => write!(f, "cursor"),
+            // color0 to color15
+            s => write!(f, "color{}", *s as u8 - Slot::Color0 as u8),
+        }
+    }
+}
@@ -12,6 +12,8 @@
 //!     be in a doc comment of the function itself.
 //!  3. If it's a variation of an already existing palette, it should be indicated as a comment.
 //!
+//! Palettes can also be declared in the config file, without a new variant, see [`custom`].
+//!
 //! * XXX would other palettes need more than 6 (or even 8) colors? if so, change the return type to
 //!   `Result<Colors>` or just fallback to a scheme
 use std::fmt;
@@ -43,6 +45,8 @@ mod harddark;
 mod light;
 mod softdark;
 mod softlight;
+pub mod auto;
+pub mod custom;
 
 use ansidark::ansidark;
 use dark::dark;
@@ -50,6 +54,8 @@ use harddark::harddark;
 use light::light;
 use softdark::softdark;
 use softlight::softlight;
+pub use auto::{Auto, Mode};
+pub use custom::Custom;
 
 /// Corresponds to the modules inside this module and `palette` parameter in the config file.
 #[derive(Debug, PartialEq, Eq, Deserialize, Serialize, Clone, Copy, Default, clap::ValueEnum)]
@@ -149,6 +155,10 @@ pub enum Palette {
     #[clap(alias  = "soft-light-comp16", name = "softlightcomp16")]
     #[serde(alias = "soft-light-comp16")]
     SoftLightComp16,
+
+    /// Chooses `dark` or `light` by how light the image is, which ones can be changed in a
+    /// `[palette]` table
+    Auto,
 }
 
 impl F {
@@ -181,6 +191,10 @@ impl F {
             F::SoftLight16 => softlight(c, orig).to_16col(),
             F::SoftLightComp => softlight(c, orig).to_comp(),
             F::SoftLightComp16 => softlight(c, orig).to_comp().to_16col(),
+
+            // [`crate::config::Config::run_palette`] picks the `dark` or `light` one for the
+            // image, without it, it's the default `dark`
+            F::Auto => dark(c, orig),
         }
     }
     /// Use different sorting `sort_by` on different schemes palette, which creates even more schemes.
@@ -189,6 +203,7 @@ impl F {
               F::Dark  | F::Dark16 | F::DarkComp | F::DarkComp16
             | F::SoftDark | F::SoftDark16 | F::SoftDarkComp | F::SoftDarkComp16
             | F::SoftLight | F::SoftLight16 | F::SoftLightComp | F::SoftLightComp16
+            | F::Auto
                 => ColorOrder::LightFirst,
 
               F::Light | F::Light16 | F::LightComp | F::LightComp16
@@ -227,6 +242,8 @@ impl F {
             F::SoftLight16 => AnsiColors::BrightYellow,
             F::SoftLightComp => AnsiColors::BrightYellow,
             F::SoftLightComp16 => AnsiColors::BrightYellow,
+
+            F::Auto => AnsiColors::White,
         }
     }
 }
@@ -262,6 +279,92 @@ impl fmt::Display for F {
             F::SoftLight16     => write!(f, "SoftLight16"),
             F::SoftLightComp   => write!(f, "SoftLightComp"),
             F::SoftLightComp16 => write!(f, "SoftLightComp16"),
+
+            F::Auto => write!(f, "Auto"),
+        }
+    }
+}
+
+/// The `palette` key and `--palette`: one of the [`Palette`]s or the name of a `[palettes.<name>]`
+/// table, see [`Custom`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PaletteName {
+    Builtin(Palette),
+    Custom(String),
+}
+
+impl Default for PaletteName {
+    fn default() -> Self { Self::Builtin(Palette::default()) }
+}
+
+impl From<Palette> for PaletteName {
+    fn from(p: Palette) -> Self { Self::Builtin(p) }
+}
+
+impl PaletteName {
+    /// Assign a color when printing in `main()`
+    pub fn col(&self) -> AnsiColors {
+        match self {
+            Self::Builtin(p) => p.col(),
+            Self::Custom(_) => AnsiColors::BrightWhite,
+        }
+    }
+}
+
+/// Any name that isn't a built in palette is a custom one, which may not exist.
+impl std::str::FromStr for PaletteName {
+    type Err = std::convert::Infallible;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        use clap::ValueEnum;
+        Ok(match Palette::from_str(s, true) {
+            Ok(p) => Self::Builtin(p),
+            Err(_) => Self::Custom(s.to_string()),
+        })
+    }
+}
+
+impl<'de> Deserialize<'de> for PaletteName {
+    fn deserialize<D: serde::Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(d)?;
+        Ok(s.parse().unwrap_or_else(|e| match e {}))
+    }
+}
+
+impl Serialize for PaletteName {
+    fn serialize<S: serde::Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Self::Builtin(p) => p.serialize(s),
+            Self::Custom(n) => s.serialize_str(n),
         }
     }
 }
+
+impl fmt::Display for PaletteName {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Builtin(p) => p.fmt(f),
+            Self::Custom(n) => write!(f, "{n}"),
+        }
+    }
+}
+
+/// The `palette` key of the config file, which is either only the name, `palette = "dark"`, or a
+/// `[palette]` table with the name and the palettes `auto` chooses from, see [`Auto`].
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct PaletteTable {
+    /// Which palette to use
+    pub name: Option<PaletteName>,
+    /// The `dark` and `light` palettes of `auto`
+    pub auto: Auto,
+}
+
+impl<'de> Deserialize<'de> for PaletteTable {
+    fn deserialize<D: serde::Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        use serde::de::Error;
+
+        let (name, auto): (_, Auto) = crate::config::str_or_table(d, "a palette name or a [palette] table", &["name", "dark", "light"])?;
+        auto.validate().map_err(D::Error::custom)?;
+        Ok(PaletteTable { name, auto })
+    }
+}
@@ -8,9 +8,9 @@ use std::fmt;
 use palette::Srgb;
 use serde::Deserialize;
 use crate::colors::Colors;
-use crate::backends::Backend;
+use crate::backends::{ImageParser, Loader, Octree, Source, Wal};
+use crate::backends::wal::has_im;
 use anyhow::Result;
-use std::path::Path;
 
 
 /// Presets overwrite, ignore, any backend, colorspace or palette defined. They take preference,
@@ -33,9 +33,14 @@ impl Preset {
     //         // _ => None,
     //     }
     // }
-    pub fn backend(&self, p: &Path) -> Result<Vec<u8>> {
+    /// `pywal` prefers image magick, but falls back to the native [`Octree`] backend when it
+    /// isn't installed.
+    pub fn backend(&self, p: &Source, l: &Loader) -> Result<Vec<u8>> {
         match self {
-            Preset::Pywal => Backend::Wal.main()(p),
+            Preset::Pywal => match has_im() {
+                Ok(_) => Wal.parse(p, l),
+                Err(_) => Octree.parse(p, l),
+            },
         }
     }
 
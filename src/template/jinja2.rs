@@ -55,18 +55,23 @@ impl From<&TemplateFields<'_>> for minijinja::Value {
         let alpha_dec = f32::from(values.alpha) / 100.0;
         let alpha_dec = if values.alpha % 10 == 0 { format!("{alpha_dec:.1}") } else { format!("{alpha_dec:.2}") };
         let v = minijinja::Value::from_serialize(c);
+        let variants = values.variants.iter().map(|(m, f)| (m.to_string(), minijinja::Value::from(f))).collect::<minijinja::Value>();
 
         context! {
             ..v,
+            ..variants,
             ..context! {
                 alpha      => values.alpha,
                 alpha_dec  => alpha_dec,
                 cursor     => c.cursor,
                 palette    => values.palette,
+                mode       => values.mode,
                 wallpaper  => values.image_path,
                 backend    => values.backend,
                 colorspace => values.colorspace,
+                threshold  => values.threshold,
                 colors     => c.into_iter().map(|x| x.to_string()).collect::<Vec<String>>(),
+                outputs    => values.outputs.iter().map(|(name, o)| (*name, minijinja::Value::from(o))).collect::<minijinja::Value>(),
             }
         }
 
@@ -6,7 +6,7 @@ use std::collections::HashMap;
 use crate::{
     colors::Colors,
     config::Fields,
-    palettes::Palette,
+    palettes::{Mode, PaletteName},
     backends::Backend,
     colorspaces::ColorSpace,
 };
@@ -28,11 +28,20 @@ use jinja2::{
 
 pub struct TemplateFields<'a> {
     pub alpha: u8,
+    /// Threshold used to tell the colors apart, the one found when it's automatic
+    pub threshold: u8,
     pub backend: &'a Backend,
-    pub palette: &'a Palette,
+    pub palette: &'a PaletteName,
+    /// Whether the palette is dark or light, the one chosen with `palette = "auto"`
+    pub mode: Mode,
     pub colorspace: &'a ColorSpace,
     pub image_path: &'a str,
     pub colors: &'a Colors,
+    /// Palettes of each monitor by its name, see `--output`. Only available to jinja templates.
+    pub outputs: Vec<(&'a str, TemplateFields<'a>)>,
+    /// The `dark` and `light` palettes, see `variants`. Only available to jinja templates, or to
+    /// any template with `variant`.
+    pub variants: Vec<(Mode, TemplateFields<'a>)>,
 }
 
 impl TemplateFields<'_> {
@@ -105,6 +114,8 @@ impl TemplateFields<'_> {
         map.insert("backend", self.backend.to_string());
         map.insert("colorspace", self.colorspace.to_string());
         map.insert("palette", self.palette.to_string());
+        map.insert("mode", self.mode.to_string());
+        map.insert("threshold", self.threshold.to_string());
 
         // normal output `#EEEEEE`
         map.insert("color0" , col.color0 .to_string());
@@ -165,6 +176,18 @@ pub fn write_template(config_dir: &Path, templates_header: &HashMap<String, Fiel
 
         let pywal = fields.pywal.unwrap_or(false);
 
+        // the `dark` or `light` palette, only made from an image (not with `theme` or `cs`)
+        let values = match fields.variant {
+            Some(m) => match values.variants.iter().find(|(v, _)| *v == m) {
+                Some((_, f)) => f,
+                None => {
+                    eprintln!("[{warn}] {name}: There is no {m} variant without an image, skipping it.");
+                    continue;
+                },
+            },
+            None => values,
+        };
+
         if !path.is_dir() { // normal file
             let render = if pywal { values.render_pywal(&path, target_path) } else { values.render_jinja(&mut jinjaenv, &path, target_path) };
             if let Err(err) = render {
@@ -17,6 +17,14 @@ fn verify_cli() {
 #[allow(non_upper_case_globals)]
 const content: &str = include_str!("../wallust.toml");
 
+/// reads `toml` as the `--config-file`
+fn config_from(toml: &str) -> anyhow::Result<Config> {
+    let mut tmp = tempfile::NamedTempFile::new().expect("init new temporal named pipe");
+    write!(tmp, "{toml}").expect("should write to tmp correctly");
+    let g = Globals { config_file: Some(tmp.path().to_path_buf()), ..Globals::default() };
+    Config::new(&g)
+}
+
 /// test for a valid `--config_file` and for the provided file to be the new location
 #[test]
 fn config_file() {
@@ -64,3 +72,336 @@ fn config_dir() {
 
     tmp.close().expect("temporal directory should close successfully");
 }
+
+/// `backend` is either the name or a `[backend]` table with parameters
+#[test]
+fn backend_table() {
+    use wallust::backends::{Backend, ResizeFilter};
+
+    let c = config_from("backend = \"kmeans\"").expect("backend name");
+    assert_eq!(c.backend, Backend::Kmeans);
+    assert_eq!(c.backend_params, Default::default());
+
+    let c = config_from("
+[backend]
+name = \"thumb\"
+kmeans = { k = 8 }
+thumb.width = 256
+thumb.filter = \"lanczos3\"
+").expect("backend table");
+    assert_eq!(c.backend, Backend::Thumb);
+    assert_eq!(c.backend_params.kmeans.k, 8);
+    assert_eq!(c.backend_params.kmeans.max_iter, 300);
+    assert_eq!(c.backend_params.thumb.width, 256);
+    assert_eq!(c.backend_params.thumb.height, 512);
+    assert_eq!(c.backend_params.thumb.filter, Some(ResizeFilter::Lanczos3));
+
+    // only parameters, default backend
+    let c = config_from("[backend]\nfastresize.shrink = 2").expect("backend table without name");
+    assert_eq!(c.backend, Backend::default());
+    assert_eq!(c.backend_params.fastresize.shrink, 2);
+
+    assert!(config_from("backend = \"nope\"").is_err());
+    assert!(config_from("[backend]\nkmeans.k = \"eight\"").is_err());
+    // typos
+    assert!(config_from("[backend]\nnmae = \"thumb\"").is_err());
+    assert!(config_from("[backend]\nthumb.widht = 256").is_err());
+    assert!(config_from("[backend]\nfast-resize.shrink = 2").is_ok());
+}
+
+/// `ignore_colors` are hex colors
+#[test]
+fn ignore_colors() {
+    let c = config_from("ignore_colors = [\"#000000\", \"#FFFFFF\"]\nignore_tolerance = 20\ntrim_borders = true").expect("valid config");
+
+    let cols: Vec<String> = c.ignore_colors.as_ref().unwrap().iter().map(|c| c.to_string()).collect();
+    assert_eq!(cols, ["#000000", "#FFFFFF"]);
+    assert_eq!(c.ignore_tolerance, Some(20));
+    assert!(c.loader().trim_borders);
+}
+
+/// Images are `path` or `path:weight`
+#[test]
+fn inputs() {
+    use clap::Parser;
+    use wallust::args::{Input, Subcmds};
+
+    let tmp = tempfile::NamedTempFile::new().expect("init new temporal named pipe");
+    let existing = tmp.path().to_string_lossy().to_string();
+
+    let run = |args: &[&str]| Cli::try_parse_from(["wallust", "run"].iter().chain(args));
+    let Ok(Cli { subcmds: Subcmds::Run(r), .. }) = run(&["a.png", "b.jpg:2", "c.png:0.5", &existing]) else {
+        panic!("should parse every image");
+    };
+
+    let weights: Vec<f32> = r.files.iter().map(|i| i.weight).collect();
+    assert_eq!(weights, [1.0, 2.0, 0.5, 1.0]);
+    assert_eq!(r.files[1].source.path(), Some(std::path::Path::new("b.jpg")));
+    assert_eq!(r.file().path(), Some(std::path::Path::new("a.png")));
+
+    // not a number, so it's part of the name
+    assert_eq!("a:b.png".parse::<Input>().unwrap().source.path(), Some(std::path::Path::new("a:b.png")));
+
+    assert!(run(&["a.png:0"]).is_err());
+    assert!(run(&["a.png:-1"]).is_err());
+    assert!(run(&[]).is_err());
+}
+
+/// `--output NAME=FILE`, and the images of the outputs make the main palette without any FILE
+#[test]
+fn outputs() {
+    use clap::Parser;
+    use wallust::args::{Output, Subcmds};
+
+    let run = |args: &[&str]| Cli::try_parse_from(["wallust", "run"].iter().chain(args));
+    let Ok(Cli { subcmds: Subcmds::Run(r), .. }) = run(&["--output", "DP-1=a.png", "--output", "HDMI-A-1=b.png:2"]) else {
+        panic!("outputs alone are enough");
+    };
+
+    assert_eq!(r.outputs[0].name, "DP-1");
+    assert_eq!(r.outputs[1].input.weight, 2.0);
+    assert_eq!(r.inputs(), [r.outputs[0].input.clone(), r.outputs[1].input.clone()]);
+    assert_eq!(r.file().path(), Some(std::path::Path::new("a.png")));
+
+    assert!("a.png".parse::<Output>().is_err());
+    assert!("=a.png".parse::<Output>().is_err());
+    assert!("DP-1=".parse::<Output>().is_err());
+}
+
+/// `color_space` is either the name or a `[color_space]` table with the bounds, which the cli
+/// overrides one by one
+#[test]
+fn color_space_table() {
+    use clap::Parser;
+    use wallust::args::Subcmds;
+    use wallust::colorspaces::{Bounds, ColorSpace};
+
+    let c = config_from("color_space = \"labmixed\"").expect("colorspace name");
+    assert_eq!(c.color_space, ColorSpace::LabMixed);
+    assert_eq!(c.color_space_bounds, Bounds::default());
+
+    let mut c = config_from("[color_space]\nname = \"oklch\"\ndarkest = 1\nlightest = 99.5").expect("colorspace table");
+    assert_eq!(c.color_space, ColorSpace::OkLch);
+    assert_eq!(c.color_space_bounds, Bounds { darkest: Some(1.0), lightest: Some(99.5), min_chroma: None, ..Bounds::default() });
+
+    let Ok(Cli { subcmds: Subcmds::Run(r), .. }) = Cli::try_parse_from(["wallust", "run", "a.png", "--lightest", "90", "--min-chroma", "0", "--grid"]) else {
+        panic!("should parse the bounds");
+    };
+    c.customs_cli(&r);
+    assert_eq!(c.color_space_bounds, Bounds { darkest: Some(1.0), lightest: Some(90.0), min_chroma: Some(0.0), grid: true, ..Bounds::default() });
+    assert!(config_from("[color_space]
+grid = true").expect("grid").color_space_bounds.grid);
+
+    assert!(c.check_bounds().is_ok());
+
+    assert!(config_from("[color_space]\ndarkest = 50\nlightest = 40").is_err());
+    assert!(config_from("[color_space]\nmin_chroma = -1").is_err());
+    assert!(Cli::try_parse_from(["wallust", "run", "a.png", "--darkest", "101"]).is_err());
+    // typos
+    assert!(config_from("[color_space]\nname = \"lch\"\ndarkst = 10").is_err());
+    assert!(config_from("[color_space]\nbuckets.red = { hue = [0, 60], chrom = 50 }").is_err());
+
+    // each flag is fine on its own, but not with what the config file says
+    let mut c = config_from("[color_space]\nlightest = 10").expect("only lightest");
+    let Ok(Cli { subcmds: Subcmds::Run(r), .. }) = Cli::try_parse_from(["wallust", "run", "a.png", "--darkest", "90"]) else {
+        panic!("should parse the bounds");
+    };
+    c.customs_cli(&r);
+    assert!(c.check_bounds().is_err());
+}
+
+/// `color_count` replaces the threshold with a search for it, and can't be used along with it
+#[test]
+fn color_count() {
+    use clap::Parser;
+    use wallust::args::Subcmds;
+    use wallust::colorspaces::Threshold;
+
+    let mut c = config_from("color_count = 10").expect("color count in range");
+    assert_eq!(c.color_count, Some(10));
+    assert_eq!(c.threshold_mode(false), Threshold::Auto(Some(10)));
+
+    let Ok(Cli { subcmds: Subcmds::Run(r), .. }) = Cli::try_parse_from(["wallust", "run", "a.png", "--color-count", "12"]) else {
+        panic!("should parse the color count");
+    };
+    c.customs_cli(&r);
+    assert_eq!(c.threshold_mode(false), Threshold::Auto(Some(12)));
+
+    assert_eq!(config_from("threshold = 15").expect("plain threshold").threshold_mode(false), Threshold::Fixed(15));
+    assert_eq!(config_from("threshold = 15").expect("plain threshold").threshold_mode(true), Threshold::Auto(None));
+
+    assert!(config_from("color_count = 2").is_err());
+    assert!(config_from("color_count = 40").is_err());
+    assert!(Cli::try_parse_from(["wallust", "run", "a.png", "--color-count", "5"]).is_err());
+    assert!(Cli::try_parse_from(["wallust", "run", "a.png", "--color-count", "8", "-t", "10"]).is_err());
+}
+
+/// `frames` is checked the same in the config file as in the cli
+#[test]
+fn frames() {
+    use clap::Parser;
+    use wallust::backends::MAX_FRAMES;
+
+    assert_eq!(config_from("frames = 8").expect("frames in range").frames, Some(8));
+    assert_eq!(config_from(&format!("frames = {MAX_FRAMES}")).expect("frames in range").frames, Some(MAX_FRAMES));
+    assert!(config_from("frames = 0").is_err());
+    assert!(config_from(&format!("frames = {}", MAX_FRAMES + 1)).is_err());
+    assert!(Cli::try_parse_from(["wallust", "run", "a.gif", "--frames", "0"]).is_err());
+}
+
+/// The hue ranges of `lchansi`, which together should go around the whole hue circle
+#[test]
+fn lchansi_buckets() {
+    use wallust::colorspaces::Bucket;
+
+    let read = |buckets: &str| config_from(&format!("[color_space]\nname = \"lchansi\"\n[color_space.buckets]\n{buckets}"));
+
+    // warmer red, wrapping around 360
+    let c = read("red = { hue = [340, 60], chroma = 150 }\nmagenta = { hue = [280, 340] }").expect("buckets cover the circle");
+    assert_eq!(c.color_space_bounds.buckets.red, Some(Bucket { hue: [340.0, 60.0], lightness: None, chroma: Some(150.0) }));
+    assert_eq!(c.color_space_bounds.buckets.green, None);
+
+    // narrower cyan, with blue taking the rest
+    assert!(read("cyan = { hue = [180, 200] }\nblue = { hue = [200, 280] }").is_ok());
+
+    let err = |b| format!("{:#}", read(b).unwrap_err());
+    assert!(err("red = { hue = [0, 70] }").contains("overlap"));
+    assert!(err("cyan = { hue = [180, 200] }").contains("200 to 210"));
+    assert!(err("red = { hue = [10, 60] }").contains("0 to 10"));
+    assert!(read("red = { hue = [0, 400] }").is_err());
+    assert!(read("red = { hue = [0, 60], lightness = 120 }").is_err());
+    assert!(read("orange = { hue = [20, 40] }").is_err());
+}
+
+/// `[palettes.<name>]` tables, chosen with `palette` or `--palette`
+#[test]
+fn custom_palettes() {
+    use clap::Parser;
+    use palette::Srgb;
+    use wallust::args::Subcmds;
+    use wallust::colorspaces::ColorOrder;
+    use wallust::colors::Myrgb;
+    use wallust::palettes::{Palette, PaletteName};
+
+    let mut c = config_from(r##"
+palette = "mine"
+
+[palettes.mine]
+base = "light"
+order = "darkfirst"
+background = "darkest"
+color1 = "top[3] darken 0.5"
+color8 = "color1 blend #FFFFFF"
+color9 = "top[20]"
+"##).expect("valid palette");
+    assert_eq!(c.palette, PaletteName::Custom("mine".into()));
+    assert!(matches!(c.sort_ord(), ColorOrder::DarkFirst));
+
+    let top = (0..8).map(|i| Srgb::new(i as f32 / 10.0, 0.2, 0.3)).collect::<Vec<_>>();
+    let colors = c.run_palette(top.clone(), top.clone(), &[1; 8]);
+    let base = Palette::Light.run(top.clone(), top.clone());
+
+    assert_eq!(colors.background, Myrgb(top[0]));
+    assert_eq!(colors.color1, Myrgb(top[3]).darken(0.5));
+    assert_eq!(colors.color8, Myrgb(top[3]).darken(0.5).blend(Myrgb(Srgb::new(1.0, 1.0, 1.0))));
+    // goes around when there aren't that many
+    assert_eq!(colors.color9, Myrgb(top[4]));
+    // the rest are the ones of `base`
+    assert_eq!(colors.foreground, base.foreground);
+    assert_eq!(colors.color2, base.color2);
+
+    // `dark` wants the lightest first, so it gets `top` reversed
+    let other = config_from(r##"
+palette = "mine"
+
+[palettes.mine]
+base = "dark"
+order = "darkfirst"
+background = "top[0]"
+"##).expect("valid palette");
+    assert!(matches!(other.sort_ord(), ColorOrder::DarkFirst));
+    let colors = other.run_palette(top.clone(), top.clone(), &[1; 8]);
+    let base = Palette::Dark.run(top.iter().rev().copied().collect(), top.clone());
+    assert_eq!(colors.background, Myrgb(top[0]));
+    assert_eq!(colors.foreground, base.foreground);
+    assert_eq!(colors.color1, base.color1);
+    assert_eq!(colors.color9, base.color9);
+
+    // the cli chooses another one
+    let Ok(Cli { subcmds: Subcmds::Run(r), .. }) = Cli::try_parse_from(["wallust", "run", "a.png", "-p", "dark16"]) else {
+        panic!("should parse the palette");
+    };
+    c.customs_cli(&r);
+    assert_eq!(c.palette, PaletteName::Builtin(Palette::Dark16));
+
+    let Ok(Cli { subcmds: Subcmds::Run(r), .. }) = Cli::try_parse_from(["wallust", "run", "a.png", "-p", "nope"]) else {
+        panic!("any name is parsed");
+    };
+    c.customs_cli(&r);
+    assert!(c.check_palette().is_err());
+
+    let err = |toml: &str| format!("{:#}", config_from(toml).unwrap_err());
+    assert!(err("palette = \"nope\"").contains("nope"));
+    assert!(err("[palettes.a]\ncolor1 = \"color2\"\ncolor2 = \"color3 darken 0.1\"\ncolor3 = \"color1\"").contains("color1 -> color2 -> color3 -> color1"));
+    assert!(err("[palettes.dark]\ncolor1 = \"top[0]\"").contains("already"));
+    assert!(err("[palettes.a]\ncolor1 = \"top[0] darken 2\"").contains("between 0 and 1"));
+    assert!(err("[palettes.a]\ncolor1 = \"top[0] shift\"").contains("unknown change"));
+    assert!(err("[palettes.a]\ncolor1 = \"middle\"").contains("unknown color"));
+    assert!(config_from("[palettes.a]\ncolor16 = \"top[0]\"").is_err());
+    assert!(err("[palettes.a]\nbase = \"auto\"").contains("can't be auto"));
+}
+
+/// `palette = "auto"` chooses between the `dark` and `light` palettes of the `[palette]` table
+#[test]
+fn auto_palette() {
+    use palette::Srgb;
+    use wallust::colors::Colors;
+    use wallust::colorspaces::{ColorOrder, ColorSpace};
+    use wallust::palettes::{Mode, Palette, PaletteName};
+
+    let mut c = config_from("[palette]\nname = \"auto\"\ndark = \"dark16\"\nlight = \"light16\"").expect("valid palette");
+    assert_eq!(c.palette, PaletteName::Builtin(Palette::Auto));
+    assert_eq!(c.palette_auto.light, PaletteName::Builtin(Palette::Light16));
+    // the one of `dark16`
+    assert_eq!(c.sort_ord(), ColorOrder::LightFirst);
+
+    // sorted by `LightFirst`, the most used first in `orig`
+    let dark = (0..8).rev().map(|i| Srgb::new(i as f32 / 30.0, 0.05, 0.1)).collect::<Vec<_>>();
+    let same = |a: Colors, b: Colors| a.into_iter().eq(b);
+    let n = [1; 8];
+    let colors = c.run_palette(dark.clone(), dark.clone(), &n);
+    assert!(same(colors, Palette::Dark16.run(dark.clone(), dark.clone())));
+    assert_eq!(c.palette_mode(&dark, &n, &colors), Mode::Dark);
+
+    // `light16` wants them `DarkFirst`
+    let light = (0..8).rev().map(|i| Srgb::new(0.7 + i as f32 / 30.0, 0.8, 0.9)).collect::<Vec<_>>();
+    let rev = light.iter().rev().copied().collect::<Vec<_>>();
+    let colors = c.run_palette(light.clone(), light.clone(), &n);
+    assert!(same(colors, Palette::Light16.run(rev, light.clone())));
+    assert_eq!(c.palette_for(c.palette_mode(&light, &n, &colors)), &PaletteName::Builtin(Palette::Light16));
+
+    // by how many pixels have each color, not by which one comes first
+    let mixed = [light[0], dark[0]];
+    assert_eq!(Mode::of_image(&mixed, &[1, 100]), Mode::Dark);
+    assert_eq!(Mode::of_image(&mixed, &[100, 1]), Mode::Light);
+
+    // lchansi keeps its order
+    c.color_space = ColorSpace::LchAnsi;
+    assert!(same(c.run_palette(light.clone(), light.clone(), &n), Palette::Light16.run(light.clone(), light.clone())));
+
+    // without a table, `dark` and `light`
+    let c = config_from("palette = \"auto\"").expect("valid palette");
+    assert_eq!(c.palette_auto.dark, PaletteName::Builtin(Palette::Dark));
+
+    // `variants` generates both, also a template that wants one of them
+    assert!(!c.wants_variants());
+    assert!(config_from("variants = true").expect("valid").wants_variants());
+    assert!(config_from("[templates]\na = { template = 'a', target = 'b', variant = \"light\" }").expect("valid").wants_variants());
+
+    let err = |toml: &str| format!("{:#}", config_from(toml).unwrap_err());
+    assert!(err("[palette]\nname = \"auto\"\nlight = \"auto\"").contains("can't be auto"));
+    assert!(err("[palette]\nname = \"auto\"\nlight = \"nope\"").contains("nope"));
+    assert!(err("variants = true\n[palette]\ndark = \"nope\"").contains("nope"));
+    // a typo isn't ignored
+    assert!(err("[palette]\nname = \"auto\"\nlihgt = \"light16\"").contains("lihgt"));
+}
@@ -0,0 +1,442 @@
+use std::path::Path;
+
+use image::{Rgb, RgbImage, Rgba, RgbaImage};
+use palette::{FromColor, Hsv, Srgb};
+use palette::cast::ComponentsAs;
+use palette::color_difference::EuclideanDistance;
+
+use wallust::backends::{Backend, ImageParser, Loader, Params, Region, Source};
+use wallust::backends::octree;
+use wallust::presets::Preset;
+
+/// Hue sweep on the x axis and a dark to light ramp on the y axis, enough colors to require
+/// reducing them, like with any wallpaper.
+fn sample(dir: &Path) -> std::path::PathBuf {
+    let img = RgbImage::from_fn(320, 200, |x, y| {
+        let hsv = Hsv::new(x as f32 / 320.0 * 360.0, 0.8, y as f32 / 200.0);
+        let rgb: Srgb<u8> = Srgb::from_color(hsv).into_format();
+        Rgb(rgb.into())
+    });
+    let path = dir.join("sample.png");
+    img.save(&path).unwrap();
+    path
+}
+
+fn cols(bytes: &[u8]) -> Vec<Srgb> {
+    let c: &[Srgb<u8>] = bytes.components_as();
+    c.iter().map(|x| x.into_format()).collect()
+}
+
+/// Same contract as `wal`: at least 16 colors, sorted like `-unique-colors` prints them.
+#[test]
+fn octree_unique_colors() {
+    let tmp = tempfile::tempdir().unwrap();
+    let path = sample(tmp.path());
+
+    let bytes = Backend::Octree.parser(&Params::default()).parse(&Source::from(&path), &Loader::default()).unwrap();
+    let c: Vec<&[u8]> = bytes.chunks(3).collect();
+
+    assert!(c.len() >= octree::COLORS && c.len() < octree::COLORS + octree::RETRIES);
+
+    let key = |c: &[u8]| (0..8).rev().fold(0u32, |k, i| {
+        k << 3 | u32::from((c[2] >> i) & 1) << 2 | u32::from((c[1] >> i) & 1) << 1 | u32::from((c[0] >> i) & 1)
+    });
+    assert!(c.windows(2).all(|w| key(w[0]) < key(w[1])));
+}
+
+/// Not enough colors to fill the palette, image magick would also fail here.
+#[test]
+fn octree_flat_image() {
+    let tmp = tempfile::tempdir().unwrap();
+    let path = tmp.path().join("flat.png");
+    RgbImage::from_pixel(64, 64, Rgb([12, 34, 56])).save(&path).unwrap();
+
+    assert!(Backend::Octree.parser(&Params::default()).parse(&Source::from(&path), &Loader::default()).is_err());
+}
+
+/// The pywal preset shouldn't depend on image magick being installed.
+#[test]
+fn pywal_preset() {
+    let tmp = tempfile::tempdir().unwrap();
+    let path = sample(tmp.path());
+
+    let p = Preset::Pywal;
+    let bytes = p.backend(&Source::from(&path), &Loader::default()).unwrap();
+    p.palette(p.cs(bytes));
+}
+
+/// Same as [`sample`], saved once, so image magick reads the very same pixels.
+const WAL_SAMPLE: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/wal/sample.png");
+
+/// What image magick gave for [`WAL_SAMPLE`] with the `wal` backend, a `#RRGGBB` color per line,
+/// so the comparison runs without it installed. Recorded with `cargo run --example record_wal`.
+const RECORDED_WAL: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/wal/sample.txt");
+
+/// Largest rgb distance (0 to sqrt(3)) between a color image magick picked and the closest
+/// native one, about 1% of the diagonal of the rgb cube. Both follow `quantize.c`, so this only
+/// leaves room for the small differences between IM's Lanczos resize and the `image` one.
+const WAL_TOLERANCE: f32 = 0.02;
+
+/// Compare against the colors image magick outputs, recorded in [`RECORDED_WAL`].
+#[test]
+#[ignore = "tests/fixtures/wal/sample.txt isn't recorded yet, see `cargo run --example record_wal`"]
+fn octree_matches_wal() {
+    let path = Path::new(WAL_SAMPLE).to_path_buf();
+
+    let Ok(recorded) = std::fs::read_to_string(RECORDED_WAL) else {
+        panic!("{RECORDED_WAL} is missing, record it with image magick installed: cargo run --example record_wal");
+    };
+    let wal: Vec<Srgb> = recorded.lines()
+        .map(|l| l.parse::<Srgb<u8>>().expect("a #RRGGBB color per line").into_format())
+        .collect();
+    assert!(wal.len() >= octree::COLORS, "{RECORDED_WAL} should have at least {} colors", octree::COLORS);
+
+    let native = cols(&Backend::Octree.parser(&Params::default()).parse(&Source::from(&path), &Loader::default()).unwrap());
+
+    // same `-colors N` should be reached, with every color close to one of the other side
+    assert_eq!(native.len(), wal.len(), "{native:?} and {wal:?} have a different amount of colors");
+    let closest = |c: &Srgb, to: &[Srgb]| to.iter().map(|x| x.distance(*c)).fold(f32::INFINITY, f32::min);
+    for w in &wal {
+        assert!(closest(w, &native) < WAL_TOLERANCE, "{w:?} has no close color in {native:?}");
+    }
+    for n in &native {
+        assert!(closest(n, &wal) < WAL_TOLERANCE, "{n:?} has no close color in {wal:?}");
+    }
+}
+
+/// Transparent pixels shouldn't reach the colorspace as black, in any backend.
+#[test]
+fn alpha_cutoff() {
+    let tmp = tempfile::tempdir().unwrap();
+    let path = tmp.path().join("logo.png");
+    RgbaImage::from_fn(64, 64, |x, _| if x < 48 { Rgba([0, 0, 0, 0]) } else { Rgba([200, 30, 30, 255]) })
+        .save(&path).unwrap();
+
+    let half = Loader { alpha_cutoff: 50, ..Loader::default() };
+
+    for b in [Backend::Full, Backend::Resized, Backend::Thumb, Backend::FastResize, Backend::Kmeans] {
+        let bytes = b.parser(&Params::default()).parse(&Source::from(&path), &half).unwrap();
+        assert!(bytes.chunks(3).all(|c| c[0] > 100), "{b} kept transparent pixels");
+
+        // unless asked, every pixel is kept, like before there was a cutoff
+        let all = b.parser(&Params::default()).parse(&Source::from(&path), &Loader::default()).unwrap();
+        assert!(all.chunks(3).any(|c| c == [0, 0, 0]), "{b} should keep every pixel by default");
+    }
+
+    // nothing left
+    RgbaImage::from_pixel(8, 8, Rgba([0, 0, 0, 0])).save(&path).unwrap();
+    assert!(Backend::Full.parser(&Params::default()).parse(&Source::from(&path), &half).is_err());
+}
+
+/// Left half red, right half blue
+fn halves(dir: &Path) -> std::path::PathBuf {
+    let path = dir.join("halves.png");
+    RgbImage::from_fn(64, 32, |x, _| if x < 32 { Rgb([200, 0, 0]) } else { Rgb([0, 0, 200]) })
+        .save(&path).unwrap();
+    path
+}
+
+#[test]
+fn region() {
+    let tmp = tempfile::tempdir().unwrap();
+    let path = halves(tmp.path());
+
+    for r in ["0.5,0,0.5,1", "east:0.5x1", "southeast:0.25x0.5"] {
+        let l = Loader { region: Some(r.parse().unwrap()), ..Loader::default() };
+        let bytes = Backend::Full.parser(&Params::default()).parse(&Source::from(&path), &l).unwrap();
+        assert!(bytes.chunks(3).all(|c| c == [0, 0, 200]), "{r} should only read the right half");
+    }
+
+    let l = Loader { region: Some("west:0.5x1".parse().unwrap()), ..Loader::default() };
+    let bytes = Backend::Resized.parser(&Params::default()).parse(&Source::from(&path), &l).unwrap();
+    assert!(bytes.chunks(3).all(|c| c == [200, 0, 0]));
+}
+
+#[test]
+fn region_parse() {
+    let r: Region = "center:0.5x0.5".parse().unwrap();
+    assert_eq!(r.fractions(), (0.25, 0.25, 0.5, 0.5));
+    assert_eq!(r.pixels(100, 40), (25, 10, 50, 20));
+
+    for bad in ["", "0,0,1", "0.5,0,0.6,1", "0,0,0,1", "middle:1x1", "north:1", "0,0,2,1"] {
+        assert!(bad.parse::<Region>().is_err(), "'{bad}' should be invalid");
+    }
+}
+
+/// A red frame shown for 100ms, then a blue one for 300ms.
+#[test]
+fn animated_frames() {
+    use image::{Delay, Frame};
+    use image::codecs::gif::GifEncoder;
+
+    let tmp = tempfile::tempdir().unwrap();
+    let path = tmp.path().join("anim.gif");
+
+    let frame = |c: [u8; 4], ms| Frame::from_parts(RgbaImage::from_pixel(16, 16, Rgba(c)), 0, 0, Delay::from_numer_denom_ms(ms, 1));
+    let mut enc = GifEncoder::new(std::fs::File::create(&path).unwrap());
+    enc.encode_frames([frame([255, 0, 0, 255], 100), frame([0, 0, 255, 255], 300)]).unwrap();
+    drop(enc);
+
+    let count = |bytes: &[u8], c: [u8; 3]| bytes.chunks(3).filter(|x| *x == c).count();
+
+    // first frame only
+    let bytes = Backend::Full.parser(&Params::default()).parse(&Source::from(&path), &Loader::default()).unwrap();
+    assert_eq!(count(&bytes, [0, 0, 255]), 0);
+
+    // weighted by how long each frame is shown
+    let l = Loader { frames: 4, ..Loader::default() };
+    let read = Backend::Full.parser(&Params::default()).read(&Source::from(&path), &l).unwrap();
+    assert_eq!(count(&read.rgb, [255, 0, 0]), 16 * 16);
+    assert_eq!(count(&read.rgb, [0, 0, 255]), 3 * 16 * 16);
+    // stacked on top of each other
+    assert_eq!(read.size, Some((16, 4 * 16, 4)));
+
+    // and cropped, frame by frame
+    let l = Loader { frames: 4, region: Some("0,0,0.5,0.5".parse().unwrap()), ..Loader::default() };
+    let bytes = Backend::Full.parser(&Params::default()).parse(&Source::from(&path), &l).unwrap();
+    assert_eq!(bytes.len(), 4 * 8 * 8 * 3);
+}
+
+/// Parameters reach the backend and the cache file name
+#[test]
+fn backend_params() {
+    use wallust::backends::{Kmeans, Thumb};
+    use wallust::cache::Cache;
+    use wallust::config::Config;
+
+    let tmp = tempfile::tempdir().unwrap();
+    let path = sample(tmp.path());
+
+    let thumb = Thumb { width: 32, height: 32, ..Thumb::default() };
+    let bytes = thumb.parse(&Source::from(&path), &Loader::default()).unwrap();
+    assert_eq!(bytes.len(), 32 * 20 * 3); // keeps the 320x200 ratio
+
+    // same seed, same output
+    let k = Kmeans { k: 3, seed: 7, ..Kmeans::default() };
+    let a = k.parse(&Source::from(&path), &Loader::default()).unwrap();
+    assert_eq!(a, k.parse(&Source::from(&path), &Loader::default()).unwrap());
+    // with more runs too, their seeds come from this one
+    let runs = Kmeans { runs: 3, ..k };
+    assert_eq!(runs.parse(&Source::from(&path), &Loader::default()).unwrap(), runs.parse(&Source::from(&path), &Loader::default()).unwrap());
+    let mut cols: Vec<&[u8]> = a.chunks(3).collect();
+    cols.sort();
+    cols.dedup();
+    assert!(cols.len() <= 3);
+
+    let c = Config { backend: Backend::Kmeans, ..Config::default() };
+    let mut other = Config { backend: Backend::Kmeans, ..Config::default() };
+    other.backend_params.kmeans.k = 3;
+    let a = Cache::new(&path, &c, tmp.path()).unwrap();
+    let b = Cache::new(&path, &other, tmp.path()).unwrap();
+    assert_ne!(a.back, b.back);
+}
+
+/// 16 bit and HDR images are mapped into 8 bits, instead of being clipped or read as sRGB
+#[test]
+fn tone_map() {
+    use image::{DynamicImage, ImageBuffer, Rgb32FImage};
+    use wallust::backends::{Full, ToneMap};
+
+    let tmp = tempfile::tempdir().unwrap();
+
+    // linear light: mid gray (sRGB 128) on the left, a really bright orange highlight on the right
+    let exr = tmp.path().join("render.exr");
+    DynamicImage::ImageRgb32F(Rgb32FImage::from_fn(8, 4, |x, _| {
+        if x < 4 { image::Rgb([0.2158, 0.2158, 0.2158]) } else { image::Rgb([16.0, 4.0, 0.5]) }
+    })).save(&exr).unwrap();
+
+    let read = |t| Full.parse(&Source::from(&exr), &Loader { tone_map: t, ..Loader::default() }).unwrap();
+
+    let clip = read(ToneMap::Clip);
+    assert_eq!(&clip[..3], &[128, 128, 128]);
+    assert_eq!(&clip[12..14], &[255, 255]); // burned into yellow
+
+    for t in [ToneMap::Reinhard, ToneMap::Aces, ToneMap::Hable] {
+        let px = read(t);
+        let hl = &px[12..15];
+        assert!(hl[0] > hl[1] && hl[1] > hl[2], "{t} should keep the highlight hue: {hl:?}");
+        assert!(px[0] < 128, "{t} compresses the mid tones as well");
+    }
+
+    // 16 bit, already gamma encoded, only rescaled whatever the operator
+    let png = tmp.path().join("photo.png");
+    let img: ImageBuffer<image::Rgb<u16>, Vec<u16>> = ImageBuffer::from_pixel(4, 4, image::Rgb([128 * 257, 0, 65535]));
+    img.save(&png).unwrap();
+    for t in [ToneMap::Clip, ToneMap::Reinhard, ToneMap::Aces, ToneMap::Hable] {
+        let px = Full.parse(&Source::from(&png), &Loader { tone_map: t, ..Loader::default() }).unwrap();
+        assert_eq!(&px[..3], &[128, 0, 255], "{t} shouldn't darken 16 bit images");
+    }
+}
+
+/// Display P3 has a wider gamut, the same values are more saturated once in sRGB
+#[test]
+fn icc_profile() {
+    use image::ImageEncoder;
+    use image::codecs::png::PngEncoder;
+    use wallust::backends::Full;
+
+    let tmp = tempfile::tempdir().unwrap();
+    let path = tmp.path().join("p3.png");
+
+    let img = RgbImage::from_pixel(4, 4, Rgb([200, 100, 50]));
+    let mut enc = PngEncoder::new(std::fs::File::create(&path).unwrap());
+    enc.set_icc_profile(moxcms::ColorProfile::new_display_p3().encode().unwrap()).unwrap();
+    enc.write_image(&img, 4, 4, image::ExtendedColorType::Rgb8).unwrap();
+
+    let px = Full.parse(&Source::from(&path), &Loader::default()).unwrap();
+    assert!(px[0] > 200 && px[1] < 100 && px[2] < 50, "should be converted into sRGB: {:?}", &px[..3]);
+
+    let px = Full.parse(&Source::from(&path), &Loader { icc: false, ..Loader::default() }).unwrap();
+    assert_eq!(&px[..3], &[200, 100, 50]);
+}
+
+/// `auto` chooses by size, format and bit depth, keeping the rest as fallbacks
+#[test]
+fn auto_backend() {
+    use image::ImageBuffer;
+    use wallust::backends::auto::candidates;
+    use wallust::config::Config;
+
+    let tmp = tempfile::tempdir().unwrap();
+    let l = Loader::default();
+
+    let small = sample(tmp.path());
+    assert_eq!(candidates(&Source::from(&small), &l).unwrap(), [Backend::Full, Backend::FastResize, Backend::Resized, Backend::Thumb]);
+
+    let big = tmp.path().join("big.png");
+    RgbImage::from_pixel(640, 640, Rgb([10, 20, 30])).save(&big).unwrap();
+    assert_eq!(candidates(&Source::from(&big), &l).unwrap()[0], Backend::FastResize);
+    // only a quarter is read
+    let l2 = Loader { region: Some("center:0.5x0.5".parse().unwrap()), ..l };
+    assert_eq!(candidates(&Source::from(&big), &l2).unwrap()[0], Backend::Full);
+
+    let gif = tmp.path().join("big.gif");
+    RgbImage::from_pixel(640, 640, Rgb([10, 20, 30])).save(&gif).unwrap();
+    assert_eq!(candidates(&Source::from(&gif), &l).unwrap()[0], Backend::Full);
+
+    let deep = tmp.path().join("deep.png");
+    let img: ImageBuffer<Rgb<u16>, Vec<u16>> = ImageBuffer::from_pixel(640, 640, Rgb([0, 1000, 65535]));
+    img.save(&deep).unwrap();
+    assert_eq!(candidates(&Source::from(&deep), &l).unwrap()[0], Backend::Resized);
+
+    // a single color isn't enough, for every backend
+    assert!(Backend::Auto.parser(&Params::default()).parse(&Source::from(&big), &l).is_err());
+    assert!(Backend::Auto.parser(&Params::default()).parse(&Source::from(&small), &l).is_ok());
+
+    let mut c = Config { backend: Backend::Auto, ..Config::default() };
+    let all = c.backends(&Source::from(&small)).unwrap();
+    c.auto_backend(&Source::from(&small)).unwrap();
+    assert_eq!(c.backend, Backend::Full);
+    assert_eq!(c.backend_auto, Some(vec![Backend::FastResize, Backend::Resized, Backend::Thumb]));
+    assert_eq!(c.backends(&Source::from(&small)).unwrap(), all);
+
+    // only with auto
+    let mut c = Config { backend: Backend::Thumb, ..Config::default() };
+    c.auto_backend(&Source::from(&small)).unwrap();
+    assert_eq!(c.backends(&Source::from(&small)).unwrap(), [Backend::Thumb]);
+}
+
+/// `auto` only moves on to the next backend when the image can't be decoded, or there aren't
+/// enough colors
+#[test]
+fn auto_fallback() {
+    use wallust::{falls_back, NotEnoughColors};
+
+    let tmp = tempfile::tempdir().unwrap();
+    let broken = tmp.path().join("broken.png");
+    std::fs::write(&broken, b"\x89PNG\r\n\x1a\nnot really").unwrap();
+
+    let decode = Backend::Full.parser(&Params::default()).parse(&Source::from(&broken), &Loader::default()).unwrap_err();
+    assert!(falls_back(&decode));
+    assert!(falls_back(&anyhow::Error::from(NotEnoughColors).context("reading the image")));
+
+    let missing = Backend::Full.parser(&Params::default()).parse(&Source::from(&tmp.path().join("nope.png")), &Loader::default()).unwrap_err();
+    assert!(!falls_back(&missing));
+    assert!(!falls_back(&anyhow::anyhow!("Failed to create the cache")));
+}
+
+/// Letterbox bars (with some noise, like subtitles) are left out, a sky isn't
+#[test]
+fn trim_borders() {
+    let tmp = tempfile::tempdir().unwrap();
+    let path = tmp.path().join("film.png");
+    let still = halves(tmp.path());
+    let still = image::open(&still).unwrap().into_rgb8();
+
+    RgbImage::from_fn(64, 52, |x, y| match y {
+        // a subtitle, less than 2% of the line
+        47 if x == 30 => Rgb([255, 255, 255]),
+        0..10 | 42.. => Rgb([4, 4, 4]),
+        _ => *still.get_pixel(x, y - 10),
+    }).save(&path).unwrap();
+
+    let l = Loader { trim_borders: true, ..Loader::default() };
+    assert_eq!(l.content(&Source::from(&path)).unwrap(), Some((0, 10, 64, 32)));
+    assert_eq!(Loader::default().content(&Source::from(&path)).unwrap(), None);
+
+    let px = Backend::Full.parser(&Params::default()).read(&Source::from(&path), &l).unwrap();
+    assert_eq!(px.rgb.len(), 64 * 32 * 3);
+    assert!(px.rgb.chunks(3).all(|c| c != [4, 4, 4]));
+    // what was read comes along with the pixels
+    assert_eq!(px.crop, Some((0, 10, 64, 32)));
+
+    // the region is inside what's left
+    let l = Loader { region: Some("east:0.5x1".parse().unwrap()), ..l };
+    assert_eq!(l.rect(&Source::from(&path)).unwrap(), Some((32, 10, 32, 32)));
+    for b in [Backend::Full, Backend::Resized, Backend::Thumb, Backend::FastResize] {
+        assert_eq!(b.parser(&Params::default()).read(&Source::from(&path), &l).unwrap().crop, Some((32, 10, 32, 32)), "{b}");
+    }
+    assert_eq!(Backend::Full.parser(&Params::default()).read(&Source::from(&path), &Loader::default()).unwrap().crop, None);
+
+    let sky = tmp.path().join("sky.png");
+    RgbImage::from_fn(64, 32, |x, y| if y < 12 { Rgb([90, 140, 220]) } else { Rgb([x as u8 * 4, 80, 40]) })
+        .save(&sky).unwrap();
+    assert_eq!(Loader { trim_borders: true, ..Loader::default() }.content(&Source::from(&sky)).unwrap(), None);
+}
+
+/// Combined images count by their weight, not by how many pixels each backend returned.
+#[test]
+fn combine() {
+    use wallust::args::Input;
+
+    let red = Source::from(Path::new("red"));
+    let inputs = [
+        Input { source: red.clone(), weight: 2.0 },
+        Input { source: Source::from(Path::new("blue")), weight: 1.0 },
+    ];
+    // a big red image and a small blue one
+    let read = |s: &Source| Ok(if *s == red { [255, 0, 0].repeat(1000) } else { [0, 0, 255].repeat(200) });
+
+    let px = wallust::combine(&inputs, read).unwrap();
+    let red = px.chunks_exact(3).filter(|p| p[0] == 255).count();
+    let blue = px.chunks_exact(3).filter(|p| p[2] == 255).count();
+
+    assert_eq!(red + blue, 1200);
+    assert_eq!(red, 2 * blue);
+
+    // a single image is left as is
+    assert_eq!(wallust::combine(&inputs[1..], read).unwrap().len(), 600);
+}
+
+/// Images in memory, encoded (like stdin) or decoded, read the same as the file
+#[test]
+fn in_memory() {
+    let tmp = tempfile::tempdir().unwrap();
+    let path = sample(tmp.path());
+    let l = Loader { region: Some("east:0.5x1".parse().unwrap()), ..Loader::default() };
+
+    let file = Source::from(&path);
+    let bytes = Source::from(std::fs::read(&path).unwrap());
+    let decoded = Source::from(image::open(&path).unwrap());
+
+    let expected = Backend::Full.parser(&Params::default()).parse(&file, &l).unwrap();
+    for s in [&bytes, &decoded] {
+        assert_eq!(Backend::Full.parser(&Params::default()).parse(s, &l).unwrap(), expected);
+        assert_eq!(l.dimensions(s).unwrap(), (320, 200));
+    }
+    assert_eq!(Backend::Auto.parser(&Params::default()).parse(&decoded, &l).unwrap(), expected);
+
+    let c = wallust::config::Config::default();
+    let hex = |s: Source| wallust::palette(s, &c).unwrap().into_iter().map(|x| x.to_string()).collect::<Vec<_>>();
+    assert_eq!(hex(decoded), hex(bytes));
+}
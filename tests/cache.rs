@@ -1,9 +1,17 @@
 use tempfile::tempdir;
 use wallust::cache::Cache;
 use wallust::cache::IsCached;
+use wallust::config::Config;
 use std::fs::File;
 use std::io::Write;
+use std::path::{Path, PathBuf};
 
+/// The keys only hash the bytes of the image, so it doesn't need to be a real one
+fn fake_image(dir: &Path) -> PathBuf {
+    let img = dir.join("image");
+    File::create(&img).unwrap().write_all(b"not really an image").unwrap();
+    img
+}
 
 //cat avril-blue-and-blackwhite.jpg_1060213_1986482_1.0.json | sed 's/"/\\"/g' | xclip -i
 
@@ -37,3 +45,191 @@ fn is_cached() {
 
     tmp_dir.close().expect("temporal named pipe should close successfully");
 }
+
+/// Reading only a part of the image changes every cached step, so it must be in the file names.
+#[test]
+fn region_key() {
+    let tmp_dir = tempdir().unwrap();
+    let img = fake_image(tmp_dir.path());
+
+    let whole = Config::default();
+    let cropped = Config { region: Some("0,0,0.5,1".parse().unwrap()), ..Config::default() };
+    let gravity = Config { region: Some("west:0.5x1".parse().unwrap()), ..Config::default() };
+
+    let whole = Cache::new(&img, &whole, tmp_dir.path()).unwrap();
+    let cropped = Cache::new(&img, &cropped, tmp_dir.path()).unwrap();
+    let gravity = Cache::new(&img, &gravity, tmp_dir.path()).unwrap();
+
+    assert_ne!(whole.back, cropped.back);
+    assert_ne!(whole.palette, cropped.palette);
+    // same region, different notation
+    assert_eq!(cropped.back, gravity.back);
+}
+
+/// The pixels are weighted as they are read, so the backend file (and the ones after it) change.
+#[test]
+fn weighting_key() {
+    let tmp_dir = tempdir().unwrap();
+    let img = fake_image(tmp_dir.path());
+
+    let plain = Config::default();
+    let weighted = Config { weighting: Some(wallust::colorspaces::Weighting::Saliency), ..Config::default() };
+
+    let plain = Cache::new(&img, &plain, tmp_dir.path()).unwrap();
+    let weighted = Cache::new(&img, &weighted, tmp_dir.path()).unwrap();
+
+    assert_ne!(plain.back, weighted.back);
+    assert_ne!(plain.cs, weighted.cs);
+    assert_ne!(plain.palette, weighted.palette);
+}
+
+/// Combined images get their own cache, by the contents and weight of each one, while a single
+/// image keeps the same one as before.
+#[test]
+fn inputs_key() {
+    use wallust::args::Input;
+
+    let tmp_dir = tempdir().unwrap();
+    let (a, b) = (fake_image(tmp_dir.path()), tmp_dir.path().join("other"));
+    File::create(&b).unwrap().write_all(b"not an image either").unwrap();
+
+    let c = Config::default();
+    let input = |p: &Path, weight| Input { source: p.into(), weight };
+    let key = |i: &[Input]| Cache::with_inputs(i, &c, tmp_dir.path()).unwrap().back;
+
+    assert_eq!(key(&[input(&a, 1.0)]), Cache::new(&a, &c, tmp_dir.path()).unwrap().back);
+
+    let both = key(&[input(&a, 1.0), input(&b, 1.0)]);
+    assert_ne!(both, key(&[input(&a, 1.0)]));
+    assert_ne!(both, key(&[input(&a, 2.0), input(&b, 1.0)]));
+    assert_ne!(both, key(&[input(&b, 1.0), input(&a, 1.0)]));
+}
+
+/// The same image shares the cache, whether it's read from a file or stdin
+#[test]
+fn stdin_key() {
+    use wallust::args::Input;
+
+    let tmp_dir = tempdir().unwrap();
+    let img = fake_image(tmp_dir.path());
+
+    let c = Config::default();
+    let stdin = Input::from(wallust::backends::Source::from(b"not really an image".to_vec()));
+    let other = Input::from(wallust::backends::Source::from(b"another image".to_vec()));
+
+    let file = Cache::new(&img, &c, tmp_dir.path()).unwrap();
+    assert_eq!(Cache::with_inputs(&[stdin], &c, tmp_dir.path()).unwrap().back, file.back);
+    assert_ne!(Cache::with_inputs(&[other], &c, tmp_dir.path()).unwrap().back, file.back);
+
+    // a file already read hashes those bytes, without reading it again
+    let read = Input::from(wallust::backends::Source::from(&img).read().unwrap());
+    std::fs::remove_file(&img).unwrap();
+    assert_eq!(Cache::with_inputs(&[read], &c, tmp_dir.path()).unwrap().back, file.back);
+}
+
+/// Colorspace bounds change the colors found, not the backend output
+#[test]
+fn bounds_key() {
+    let tmp_dir = tempdir().unwrap();
+    let img = fake_image(tmp_dir.path());
+
+    let plain = Config::default();
+    let bounds = wallust::colorspaces::Bounds { darkest: Some(1.0), ..Default::default() };
+    let bounded = Config { color_space_bounds: bounds, ..Config::default() };
+
+    let plain = Cache::new(&img, &plain, tmp_dir.path()).unwrap();
+    let bounded = Cache::new(&img, &bounded, tmp_dir.path()).unwrap();
+
+    assert_eq!(plain.back, bounded.back);
+    assert_ne!(plain.cs, bounded.cs);
+    assert_ne!(plain.palette, bounded.palette);
+}
+
+/// Generated colors depend on the fallback generator, so it gets its own colorspace cache
+#[test]
+fn fallback_generator_key() {
+    use wallust::colorspaces::FallbackGenerator;
+
+    let tmp_dir = tempdir().unwrap();
+    let img = fake_image(tmp_dir.path());
+
+    let cache = |g| Cache::new(&img, &Config { fallback_generator: g, ..Config::default() }, tmp_dir.path()).unwrap();
+
+    let plain = cache(None);
+    assert_eq!(plain.cs, cache(Some(FallbackGenerator::Interpolate)).cs);
+
+    let triadic = cache(Some(FallbackGenerator::Triadic));
+    assert_eq!(plain.back, triadic.back);
+    assert_ne!(plain.cs, triadic.cs);
+    assert_ne!(triadic.cs, cache(Some(FallbackGenerator::SplitComplementary)).cs);
+}
+
+/// Scoring changes which colors are kept, but not the backend
+#[test]
+fn scoring_key() {
+    use wallust::colorspaces::Scoring;
+
+    let tmp_dir = tempdir().unwrap();
+    let img = fake_image(tmp_dir.path());
+
+    let cache = |s| Cache::new(&img, &Config { scoring: s, ..Config::default() }, tmp_dir.path()).unwrap();
+
+    let plain = cache(None);
+    assert_eq!(plain.cs, cache(Some(Scoring::Count)).cs);
+
+    let vibrant = cache(Some(Scoring::Vibrancy));
+    assert_eq!(plain.back, vibrant.back);
+    assert_ne!(plain.cs, vibrant.cs);
+}
+
+/// Custom palettes only change the palette file, also when their table changes
+#[test]
+fn custom_palette_key() {
+    use std::collections::HashMap;
+    use wallust::palettes::{Custom, PaletteName};
+
+    let tmp_dir = tempdir().unwrap();
+    let img = fake_image(tmp_dir.path());
+
+    let cache = |table: &str| {
+        let custom: Custom = toml::from_str(table).unwrap();
+        let c = Config {
+            palette: PaletteName::Custom("mine".into()),
+            palettes: Some(HashMap::from([("mine".to_string(), custom)])),
+            ..Config::default()
+        };
+        Cache::new(&img, &c, tmp_dir.path()).unwrap()
+    };
+
+    let plain = Cache::new(&img, &Config::default(), tmp_dir.path()).unwrap();
+    let mine = cache("color1 = \"top[0]\"");
+
+    assert_eq!(plain.cs, mine.cs);
+    assert_ne!(plain.palette, mine.palette);
+    assert_eq!(mine.palette, cache("color1 = \"top[0]\"").palette);
+    assert_ne!(mine.palette, cache("color1 = \"top[1]\"").palette);
+}
+
+#[test]
+fn auto_palette_key() {
+    use wallust::palettes::{Auto, Palette};
+
+    let tmp_dir = tempdir().unwrap();
+    let img = fake_image(tmp_dir.path());
+
+    let cache = |auto: Auto| {
+        let c = Config { palette: Palette::Auto.into(), palette_auto: auto, ..Config::default() };
+        Cache::new(&img, &c, tmp_dir.path()).unwrap()
+    };
+
+    let plain = cache(Auto::default());
+    let sixteen = cache(Auto { dark: Palette::Dark16.into(), light: Palette::Light16.into() });
+
+    assert_eq!(plain.cs, sixteen.cs);
+    assert_ne!(plain.palette, sixteen.palette);
+    assert_ne!(plain.palette, Cache::new(&img, &Config::default(), tmp_dir.path()).unwrap().palette);
+
+    // `light` wants the colors sorted the other way around, so they aren't the ones of `dark`
+    let light = Config { palette: Palette::Light.into(), ..Config::default() };
+    assert_ne!(plain.cs, Cache::new(&img, &light, tmp_dir.path()).unwrap().cs);
+}
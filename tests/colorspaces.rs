@@ -0,0 +1,406 @@
+use std::path::Path;
+
+use image::{Rgb, RgbImage};
+
+use wallust::backends::{Full, ImageParser, Loader, Pixels, Source};
+use wallust::colorspaces::Weighting;
+
+/// Flat sky with a small, detailed (checkered) red subject in the middle, 4% of the image
+fn subject(dir: &Path) -> std::path::PathBuf {
+    let img = RgbImage::from_fn(200, 200, |x, y| {
+        if !((80..120).contains(&x) && (80..120).contains(&y)) { return Rgb([120, 150, 190]) }
+        if (x / 2 + y / 2) % 2 == 0 { Rgb([200, 30, 30]) } else { Rgb([150, 20, 20]) }
+    });
+    let path = dir.join("subject.png");
+    img.save(&path).unwrap();
+    path
+}
+
+/// The subject counts more than the area it covers, with any weighting, by where its pixels are
+/// or, for backends that don't keep that, by its color
+#[test]
+fn weighting() {
+    let tmp = tempfile::tempdir().unwrap();
+    let path = subject(tmp.path());
+    let l = Loader::default();
+
+    let read = Full.read(&Source::from(&path), &l).unwrap();
+    assert_eq!(read.size, Some((200, 200, 1)));
+    let rgb = read.rgb.clone();
+    let red = |b: &[u8]| b.chunks(3).filter(|c| c[0] > c[2]).count() as f32 / (b.len() / 3) as f32;
+    assert_eq!(red(&rgb), 0.04);
+
+    let same = Weighting::None.apply(read.clone(), &Source::from(&path), &l).unwrap();
+    assert_eq!(same, rgb);
+
+    // the pixels are enough, the image isn't read again
+    let gone = Source::from(tmp.path().join("gone.png"));
+
+    for w in [Weighting::Center, Weighting::Contrast, Weighting::Saliency] {
+        let weighted = w.apply(read.clone(), &gone, &l).unwrap();
+        // about the same amount of pixels
+        assert!(weighted.len().abs_diff(rgb.len()) <= 3, "{w}: {} pixels", weighted.len() / 3);
+        assert!(red(&weighted) > 0.08, "{w}: subject is only {}", red(&weighted));
+
+        // without their position, the image is decoded to weight their colors
+        let colors = Pixels { size: None, ..read.clone() };
+        assert!(w.apply(colors.clone(), &gone, &l).is_err());
+        let weighted = w.apply(colors, &Source::from(&path), &l).unwrap();
+        assert!(weighted.len().abs_diff(rgb.len()) <= 3, "{w}: {} pixels", weighted.len() / 3);
+        assert!(red(&weighted) > 0.08, "{w}: subject is only {}", red(&weighted));
+    }
+}
+
+/// With their position, the same color counts more at the center than on the sides
+#[test]
+fn weighting_position() {
+    // red on the sides and in the middle, the same amount of each
+    let (w, h) = (90, 30);
+    let rgb: Vec<u8> = (0..h).flat_map(|_| (0..w).flat_map(|x| match x {
+        0..15 | 75.. => [200, 30, 30],
+        30..60 => [30, 30, 200],
+        _ => [120, 150, 190],
+    })).collect();
+    let p = Pixels { rgb, crop: None, size: Some((w, h, 1)) };
+
+    let weighted = Weighting::Center.apply(p, &Source::from(Vec::new()), &Loader::default()).unwrap();
+    let share = |c: u8| weighted.chunks(3).filter(|p| p[0] == c && p[1] == 30).count() as f32 / (weighted.len() / 3) as f32;
+    assert!(share(30) > 2.0 * share(200), "middle {}, sides {}", share(30), share(200));
+}
+
+/// Colors close to the ignored ones are left out
+#[test]
+fn ignore_colors() {
+    use wallust::colors::Myrgb;
+    use wallust::colorspaces::ignore::ignore;
+
+    let black = Myrgb(palette::Srgb::new(0.0, 0.0, 0.0));
+    let rgb = [0, 0, 0, 8, 6, 9, 200, 30, 30, 12, 0, 0];
+
+    assert_eq!(*ignore(&rgb, &[], 10).unwrap(), rgb);
+    assert_eq!(*ignore(&rgb, &[black], 10).unwrap(), [200, 30, 30, 12, 0, 0]);
+    assert_eq!(*ignore(&rgb, &[black], 12).unwrap(), [200, 30, 30]);
+    assert!(ignore(&[1, 1, 1], &[black], 10).is_err());
+}
+
+/// The OK colorspaces gather a full palette like their CIE counterparts
+#[test]
+fn oklab() {
+    use wallust::colorspaces::{Bounds, ColorOrder, ColorSpace, FallbackGenerator, Scoring, Threshold};
+
+    // a few distinct hues, in different amounts
+    let img = RgbImage::from_fn(120, 60, |x, _| match x / 20 {
+        0 => Rgb([200, 30, 30]),
+        1 => Rgb([30, 160, 40]),
+        2 => Rgb([40, 60, 200]),
+        3 => Rgb([220, 200, 40]),
+        4 => Rgb([160, 40, 170]),
+        _ => Rgb([40, 180, 190]),
+    });
+    let rgb = img.into_raw();
+
+    for cs in [ColorSpace::OkLab, ColorSpace::OkLabMixed, ColorSpace::OkLch, ColorSpace::OkLchMixed] {
+        let (top, orig, warn, ..) = cs.run(Threshold::Fixed(10), &rgb, &FallbackGenerator::default(), &ColorOrder::DarkFirst, &Bounds::default(), &Scoring::default()).unwrap();
+        assert_eq!(top.len(), 6, "{cs}");
+        assert_eq!(orig.len(), 6, "{cs}");
+        assert!(!warn, "{cs}");
+    }
+
+    // too close together to tell apart
+    let gray = [100, 100, 100, 102, 101, 100].repeat(50);
+    assert!(ColorSpace::OkLab.run(Threshold::Fixed(20), &gray, &FallbackGenerator::default(), &ColorOrder::DarkFirst, &Bounds::default(), &Scoring::default()).is_none());
+}
+
+/// Two saturated blues that CIEDE2000 merges are kept apart by CAM16-UCS
+#[test]
+fn cam16() {
+    use wallust::colorspaces::{Bounds, ColorOrder, ColorSpace, FallbackGenerator, Scoring, Threshold};
+
+    let blues = [[0, 0, 255], [0, 90, 255]].repeat(50).concat();
+    let run = |cs: ColorSpace| cs.run(Threshold::Fixed(10), &blues, &FallbackGenerator::default(), &ColorOrder::DarkFirst, &Bounds::default(), &Scoring::default());
+
+    assert!(run(ColorSpace::Lab).is_none());
+
+    let (_, orig, warn, ..) = run(ColorSpace::Cam16).unwrap();
+    assert!(warn); // only two colors, the rest are generated
+    let orig = orig.iter().map(|c| c.into_format::<u8>()).collect::<Vec<_>>();
+    // and they round trip back to sRGB
+    for c in [palette::Srgb::new(0, 0, 255), palette::Srgb::new(0, 90, 255)] {
+        assert!(orig.iter().any(|o| o.red.abs_diff(c.red) <= 1 && o.green.abs_diff(c.green) <= 1 && o.blue.abs_diff(c.blue) <= 1), "{c:?} in {orig:?}");
+    }
+}
+
+/// A near black image has nothing above the default darkest lightness, unless it's lowered
+#[test]
+fn bounds() {
+    use wallust::colorspaces::{Bounds, ColorOrder, ColorSpace, FallbackGenerator, Scoring, Threshold};
+
+    let noir = [[10, 2, 2], [2, 10, 2], [2, 2, 12], [10, 10, 2], [10, 2, 10], [2, 10, 10]].repeat(50).concat();
+    let dark = Bounds { darkest: Some(0.0), ..Bounds::default() };
+
+    for cs in [ColorSpace::Lab, ColorSpace::Lch, ColorSpace::OkLab, ColorSpace::OkLch] {
+        let run = |b: &Bounds| cs.run(Threshold::Fixed(1), &noir, &FallbackGenerator::default(), &ColorOrder::DarkFirst, b, &Scoring::default());
+        assert!(run(&Bounds::default()).is_none(), "{cs}");
+        assert!(run(&dark).is_some(), "{cs}");
+    }
+
+    // only the colorful ones
+    let rgb = [[200, 30, 30], [30, 160, 40], [120, 110, 115], [90, 95, 100]].repeat(50).concat();
+    // same scale in every colorspace, cam16 keeps the same ones
+    let vivid = Bounds { min_chroma: Some(50.0), ..Bounds::default() };
+    for cs in [ColorSpace::Lab, ColorSpace::Cam16] {
+        let (_, orig, ..) = cs.run(Threshold::Fixed(5), &rgb, &FallbackGenerator::default(), &ColorOrder::DarkFirst, &vivid, &Scoring::default()).unwrap();
+        let orig = orig.iter().map(|c| c.into_format::<u8>()).collect::<Vec<_>>();
+        let has = |r: u8, g: u8, b: u8| orig.iter().any(|c| c.red.abs_diff(r) <= 1 && c.green.abs_diff(g) <= 1 && c.blue.abs_diff(b) <= 1);
+        assert!(has(200, 30, 30) && has(30, 160, 40), "{cs}: {orig:?}");
+        assert!(!has(120, 110, 115) && !has(90, 95, 100), "{cs}: {orig:?}");
+    }
+
+    assert!(Bounds { darkest: Some(60.0), lightest: Some(40.0), ..Bounds::default() }.validate().is_err());
+}
+
+/// Asking for a number of colors finds the threshold that gives them
+#[test]
+fn color_count() {
+    use palette::{FromColor, Hsv, Srgb};
+    use wallust::colorspaces::{Bounds, ColorOrder, ColorSpace, FallbackGenerator, Scoring, Threshold, MAX_COLS, MIN_COLS};
+
+    // around the hue wheel, the higher the threshold the more neighbours are merged
+    let rgb = (0..36).map(|i| {
+        let c: Srgb<u8> = Srgb::from_color(Hsv::new(i as f32 * 10.0, 0.7, 0.8)).into_format();
+        [c.red, c.green, c.blue]
+    }).collect::<Vec<_>>().repeat(20).concat();
+    let run = |th| ColorSpace::Lab.run(th, &rgb, &FallbackGenerator::default(), &ColorOrder::DarkFirst, &Bounds::default(), &Scoring::default());
+
+    for n in [7, 8, 9, 12, 13] {
+        let (_, orig, warn, th, _) = run(Threshold::Auto(Some(n))).unwrap();
+        assert_eq!(orig.len(), usize::from(n), "threshold {th}");
+        assert!(!warn);
+        // the same as running with that threshold
+        assert_eq!(run(Threshold::Fixed(th)).unwrap().1, orig);
+    }
+
+    // no threshold gives 10 here, the closest one is used
+    let (_, orig, ..) = run(Threshold::Auto(Some(10))).unwrap();
+    assert!(orig.len().abs_diff(10) == 1, "{}", orig.len());
+
+    // without one, the first threshold of the table, from the middle outwards, in range is kept
+    let table = [14, 16, 13, 17, 12, 18, 11, 19, 10, 20, 9, 21, 8, 22, 7, 23, 6, 24, 5, 25, 4, 26, 3, 27, 2];
+    let range = usize::from(MIN_COLS)..=usize::from(MAX_COLS);
+    let first = table.into_iter().find(|&th| range.contains(&run(Threshold::Fixed(th)).unwrap().1.len())).unwrap();
+    let (_, orig, warn, th, _) = run(Threshold::Auto(None)).unwrap();
+    assert_eq!(th, first);
+    assert!(range.contains(&orig.len()));
+    assert!(!warn);
+}
+
+/// Harmonies rotate the hue in OKLch, keeping the lightness
+#[test]
+fn harmonies() {
+    use palette::{FromColor, Oklch, Srgb};
+    use wallust::colorspaces::FallbackGenerator as G;
+
+    let a = Srgb::new(0.6, 0.3, 0.2);
+    let b = Srgb::new(0.2, 0.4, 0.6);
+
+    for (g, angles) in [
+        (G::Triadic, vec![120.0, 240.0]),
+        (G::Tetradic, vec![90.0, 180.0, 270.0]),
+        (G::Analogous, vec![-30.0, 30.0]),
+        (G::SplitComplementary, vec![150.0, 210.0]),
+    ] {
+        let new = g.gen()(a, b, 16);
+        assert_eq!(new.len(), angles.len() * 2, "{g}");
+
+        for (c, (orig, angle)) in new.iter().zip([a, b].iter().flat_map(|o| angles.iter().map(move |x| (o, x)))) {
+            let (c, orig) = (Oklch::from_color(*c), Oklch::from_color(*orig));
+            assert!((c.l - orig.l).abs() < 0.01, "{g}: {c:?} {orig:?}");
+            let turn = (c.hue - orig.hue).into_positive_degrees();
+            let want = palette::OklabHue::from_degrees(*angle).into_positive_degrees();
+            assert!((turn - want).abs() < 1.0, "{g}: turned {turn}, not {want}");
+        }
+    }
+}
+
+/// A small vivid accent is left out by count, but not when scoring by chroma or vibrancy
+#[test]
+fn scoring() {
+    use wallust::colorspaces::{Bounds, ColorOrder, ColorSpace, FallbackGenerator, Scoring, Threshold};
+
+    // plenty of grays, more than fit in the palette, and a few red pixels
+    let red = [200, 30, 30];
+    let mut rgb = (0..20u8).flat_map(|i| [60 + i * 8; 3].repeat(50)).collect::<Vec<_>>();
+    rgb.extend(red.repeat(10));
+
+    let run = |s| ColorSpace::Lab.run(Threshold::Fixed(2), &rgb, &FallbackGenerator::default(), &ColorOrder::DarkFirst, &Bounds::default(), &s).unwrap();
+    let has_red = |orig: &[palette::Srgb]| orig.iter().any(|c| c.into_format::<u8>() == palette::Srgb::new(200, 30, 30));
+
+    let (_, orig, ..) = run(Scoring::Count);
+    assert_eq!(orig.len(), 16);
+    assert!(!has_red(&orig));
+
+    let (_, orig, ..) = run(Scoring::Chroma);
+    assert!(has_red(&orig));
+
+    // the most vibrant one first
+    let (_, orig, ..) = run(Scoring::Vibrancy);
+    assert_eq!(orig[0].into_format::<u8>(), palette::Srgb::new(200, 30, 30));
+}
+
+/// With the default ranges, every color found stays in its `lchansi` bucket
+#[test]
+fn lchansi_default_buckets() {
+    use palette::{FromColor, Lch, Srgb};
+    use wallust::colorspaces::{Bounds, ColorOrder, ColorSpace, FallbackGenerator, Scoring, Threshold};
+
+    // away from the middle of their bucket, where the ones made up are
+    let hues = [10.0, 70.0, 130.0, 190.0, 260.0, 300.0];
+    let rgb = hues.iter().flat_map(|&h| {
+        let c: Srgb<u8> = Srgb::from_color(Lch::new(60.0, 30.0, h)).into_format();
+        [c.red, c.green, c.blue]
+    }).collect::<Vec<_>>().repeat(50);
+    let hue = |c: Srgb| Lch::from_color(c).hue.into_positive_degrees();
+
+    let orig = ColorSpace::LchAnsi.run(Threshold::Fixed(1), &rgb, &FallbackGenerator::default(), &ColorOrder::DarkFirst, &Bounds::default(), &Scoring::default()).unwrap().1;
+
+    // black, red, green, yellow, blue, magenta, cyan, gray
+    for (i, h) in [1, 3, 2, 6, 4, 5].into_iter().zip(hues) {
+        let found = hue(orig[i]);
+        // pulled towards the lightness and chroma of its bucket, blue goes out of sRGB and turns
+        let near = if i == 4 { (210.0..280.0).contains(&found) } else { (found - h).abs() < 2.0 };
+        assert!(near, "{h} came out as {found}");
+    }
+}
+
+/// A color goes to the `lchansi` bucket its hue falls in, which can be changed
+#[test]
+fn lchansi_buckets() {
+    use palette::{FromColor, Lch, Srgb};
+    use wallust::colorspaces::{Bounds, Bucket, Buckets, ColorOrder, ColorSpace, FallbackGenerator, Scoring, Threshold};
+
+    // between the default red (0 to 60) and a narrower one
+    let orange: Srgb<u8> = Srgb::from_color(Lch::new(60.0, 40.0, 45.0)).into_format();
+    let rgb = [orange.red, orange.green, orange.blue].repeat(100);
+    let hue = |c: Srgb| Lch::from_color(c).hue.into_positive_degrees();
+
+    let run = |b: &Bounds| ColorSpace::LchAnsi.run(Threshold::Fixed(1), &rgb, &FallbackGenerator::default(), &ColorOrder::DarkFirst, b, &Scoring::default()).unwrap().1;
+
+    // black, red, green, yellow, blue, magenta, cyan, gray
+    let plain = run(&Bounds::default());
+
+    let buckets = Buckets {
+        red: Some(Bucket { hue: [340.0, 30.0], lightness: None, chroma: None }),
+        yellow: Some(Bucket { hue: [30.0, 120.0], lightness: None, chroma: None }),
+        magenta: Some(Bucket { hue: [280.0, 340.0], lightness: None, chroma: None }),
+        ..Buckets::default()
+    };
+    let narrow = Bounds { buckets, ..Bounds::default() };
+    assert!(narrow.validate().is_ok());
+    let orig = run(&narrow);
+
+    // it's the orange, otherwise it's made up in its range
+    assert!((30.0..60.0).contains(&hue(plain[1])), "red {}", hue(plain[1]));
+    assert!((hue(orig[1]) - hue(plain[1])).abs() > 5.0, "red {}", hue(orig[1]));
+
+    // an empty bucket is made up in the middle of its range, (in sRGB with a low chroma)
+    let buckets = Buckets {
+        yellow: Some(Bucket { hue: [60.0, 120.0], lightness: Some(70.0), chroma: Some(20.0) }),
+        ..Buckets::default()
+    };
+    let yellow = run(&Bounds { buckets, ..Bounds::default() })[3];
+    assert!((hue(yellow) - 90.0).abs() < 2.0, "yellow {}", hue(yellow));
+}
+
+/// Every `lchansi` bucket gets the colors in its range, not only the first one
+#[test]
+fn lchansi_every_bucket() {
+    use palette::{FromColor, Lch, Srgb};
+    use wallust::colorspaces::{Bounds, Bucket, Buckets, ColorOrder, ColorSpace, FallbackGenerator, Scoring, Threshold};
+
+    // away from the middle of their bucket, where the ones made up are
+    let hues = [10.0, 70.0, 130.0, 190.0, 260.0, 300.0];
+    let rgb = hues.iter().flat_map(|&h| {
+        let c: Srgb<u8> = Srgb::from_color(Lch::new(60.0, 30.0, h)).into_format();
+        [c.red, c.green, c.blue]
+    }).collect::<Vec<_>>().repeat(50);
+    let hue = |c: Srgb| Lch::from_color(c).hue.into_positive_degrees();
+
+    // the default ranges, with the lightness and chroma of the colors above, so none goes out of sRGB
+    let b = |s, e| Some(Bucket { hue: [s, e], lightness: Some(60.0), chroma: Some(30.0) });
+    let buckets = Buckets {
+        red: b(0.0, 60.0), yellow: b(60.0, 120.0), green: b(120.0, 180.0),
+        cyan: b(180.0, 210.0), blue: b(210.0, 280.0), magenta: b(280.0, 360.0),
+    };
+    let bounds = Bounds { buckets, ..Bounds::default() };
+    let orig = ColorSpace::LchAnsi.run(Threshold::Fixed(1), &rgb, &FallbackGenerator::default(), &ColorOrder::DarkFirst, &bounds, &Scoring::default()).unwrap().1;
+
+    // black, red, green, yellow, blue, magenta, cyan, gray
+    for (i, h) in [1, 3, 2, 6, 4, 5].into_iter().zip(hues) {
+        assert!((hue(orig[i]) - h).abs() < 2.0, "{h} came out as {}", hue(orig[i]));
+    }
+}
+
+/// `kmeans` makes `color_count` clusters, the same every time and whatever the threshold
+#[test]
+fn kmeans() {
+    use palette::Srgb;
+    use wallust::colorspaces::{Bounds, ColorOrder, ColorSpace, FallbackGenerator, Scoring, Threshold};
+
+    // six colors, each with a bit of noise around it, the first one the most used
+    let base = [[200, 40, 40], [40, 160, 60], [50, 70, 200], [220, 200, 60], [150, 60, 170], [40, 170, 180]];
+    let rgb = base.iter().enumerate().flat_map(|(i, c)| {
+        (0..60 - i * 5).map(move |j| c.map(|v| v + (j % 7) as u8))
+    }).collect::<Vec<_>>().concat();
+
+    let run = |th| ColorSpace::KMeans.run(th, &rgb, &FallbackGenerator::default(), &ColorOrder::DarkFirst, &Bounds::default(), &Scoring::default()).unwrap();
+
+    let (_, orig, warn, th, _) = run(Threshold::Auto(Some(6)));
+    assert_eq!(orig.len(), 6);
+    assert!(!warn);
+    assert_eq!(th, 0);
+    // every cluster is around one of the colors, by how many pixels it has
+    for (c, b) in orig.iter().zip(base) {
+        let c = c.into_format::<u8>();
+        let b = Srgb::new(b[0], b[1], b[2]);
+        assert!(c.red.abs_diff(b.red) <= 6 && c.green.abs_diff(b.green) <= 6 && c.blue.abs_diff(b.blue) <= 6, "{c:?} {b:?}");
+    }
+
+    assert_eq!(run(Threshold::Auto(Some(6))), run(Threshold::Auto(Some(6))));
+    assert_eq!(run(Threshold::Fixed(2)), run(Threshold::Fixed(40)));
+    assert_eq!(run(Threshold::Auto(Some(9))).1.len(), 9);
+
+    // more clusters than colors, still none of them is the same color
+    let orig = run(Threshold::Auto(Some(16))).1.iter().map(|c| c.into_format::<u8>()).collect::<Vec<Srgb<u8>>>();
+    assert!(orig.iter().enumerate().all(|(i, c)| !orig[i + 1..].contains(c)), "{orig:?}");
+}
+
+/// Near colors are read together, a lot less than what a threshold merges
+#[test]
+fn grid() {
+    use wallust::colorspaces::{Bounds, ColorSpace, GRID_BITS};
+
+    // two colors, with a bit of grain
+    let rgb = (0..4_u8).flat_map(|i| [[200 + i, 40, 40 + i], [40, 60 + i, 200]]).collect::<Vec<_>>().concat().repeat(20);
+
+    for cs in [ColorSpace::Lab, ColorSpace::Lch, ColorSpace::OkLab, ColorSpace::Cam16] {
+        let run = |th, bits| cs.histogram(&rgb, th, bits, &Bounds::default());
+        // with no threshold, only the same colors are merged
+        assert_eq!(run(0, Some(8)), 8, "{cs}");
+        assert_eq!(run(0, Some(GRID_BITS)), 2, "{cs}");
+        // every color apart gathers the same as every pixel on its own
+        assert_eq!(run(10, Some(8)), run(10, None), "{cs}");
+    }
+    // but once there is one, it's the same
+    for cs in [ColorSpace::Lab, ColorSpace::Lch, ColorSpace::OkLab] {
+        assert_eq!(cs.histogram(&rgb, 10, Some(8), &Bounds::default()), cs.histogram(&rgb, 10, Some(GRID_BITS), &Bounds::default()), "{cs}");
+    }
+
+    // only when asked, otherwise the colors found are the ones of the image
+    use wallust::colorspaces::{ColorOrder, FallbackGenerator, Scoring, Threshold};
+    let grid = Bounds { grid: true, ..Bounds::default() };
+    let run = |b: &Bounds| ColorSpace::Lab.run(Threshold::Fixed(10), &rgb, &FallbackGenerator::default(), &ColorOrder::DarkFirst, b, &Scoring::default()).unwrap().1;
+    let first = palette::Srgb::new(200, 40, 40);
+    assert!(run(&Bounds::default()).iter().any(|c| c.into_format::<u8>() == first));
+    assert!(run(&grid).iter().all(|c| c.into_format::<u8>() != first));
+    assert_ne!(Bounds::default().key(), grid.key());
+}
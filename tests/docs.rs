@@ -10,6 +10,9 @@ fn main() {
     use wallust::colorspaces::ColorSpace as ColorSpaces;
     // use wallust::colorspaces::FallbackGenerator as Generate;
     use wallust::palettes::Palette as Filters;
+    use wallust::backends::ToneMap;
+    use wallust::colorspaces::Weighting;
+    use wallust::colorspaces::Scoring;
     use strum::IntoEnumIterator;
 
     fn ul_comment<T: documented::DocumentedFields + std::fmt::Display + IntoEnumIterator>() -> String {
@@ -32,6 +35,9 @@ fn main() {
     let backends    = ul_comment::<Backend>();
     let colorspaces = ul_comment::<ColorSpaces>();
     let filters     = ul_comment::<Filters>();
+    let tonemaps    = ul_comment::<ToneMap>();
+    let weightings  = ul_comment::<Weighting>();
+    let scorings    = ul_comment::<Scoring>();
 
     let backend = format!("\
 | Backends  | Description |
@@ -48,6 +54,21 @@ fn main() {
 |---------|-------------|
 {filters}");
 
+    let tone_map = format!("\
+| Tone Map | Description |
+|----------|-------------|
+{tonemaps}");
+
+    let weighting = format!("\
+| Weighting | Description |
+|-----------|-------------|
+{weightings}");
+
+    let scoring = format!("\
+| Scoring | Description |
+|---------|-------------|
+{scorings}");
+
     std::fs::File::create("docs/parameters/backend-table.md").unwrap()
         .write_all(backend.as_bytes()).unwrap();
 
@@ -56,4 +77,13 @@ fn main() {
 
     std::fs::File::create("docs/parameters/palette-table.md").unwrap()
         .write_all(palette.as_bytes()).unwrap();
+
+    std::fs::File::create("docs/parameters/tone_map-table.md").unwrap()
+        .write_all(tone_map.as_bytes()).unwrap();
+
+    std::fs::File::create("docs/parameters/weighting-table.md").unwrap()
+        .write_all(weighting.as_bytes()).unwrap();
+
+    std::fs::File::create("docs/parameters/scoring-table.md").unwrap()
+        .write_all(scoring.as_bytes()).unwrap();
 }
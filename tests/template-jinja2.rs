@@ -1,9 +1,11 @@
 #![allow(non_upper_case_globals)]
 #![allow(non_snake_case)]
 use wallust::backends::Backend;
-use wallust::palettes::Palette;
+use wallust::palettes::{Mode, Palette, PaletteName};
 use wallust::template::jinja2::jinja_env;
 use wallust::template::TemplateFields;
+use wallust::colors::Myrgb;
+use palette::Srgb;
 
 mod template;
 use template::wall_str;
@@ -15,11 +17,15 @@ use template::mycols;
 fn jinja(content: &str) -> String {
     let Tfields: &TemplateFields = &TemplateFields {
         alpha: 100,
+        threshold: 20,
         backend: &Backend::Thumb,
-        palette: &Palette::Dark,
+        palette: &PaletteName::Builtin(Palette::Dark),
+        mode: Mode::Light,
         colorspace: &wallust::colorspaces::ColorSpace::Lab,
         image_path: wall_str,
         colors: &mycols(),
+        outputs: vec![],
+        variants: vec![],
     };
     let v = minijinja::Value::from(Tfields);
     jinja_env().render_named_str("sample", content, v).unwrap()
@@ -91,3 +97,134 @@ fn jinja_filters() {
         assert_eq!(expected[i].rgb(), rgb);
     }
 }
+
+/// Each `--output` palette is under its name, with its own colors and wallpaper
+#[test]
+fn jinja_outputs() {
+    let mut other = mycols();
+    other.color4 = Myrgb(Srgb::new(0_u8, 0, 255).into_format());
+
+    let output = |image_path, colors| TemplateFields {
+        alpha: 100,
+        threshold: 20,
+        backend: &Backend::Thumb,
+        palette: &PaletteName::Builtin(Palette::Dark),
+        mode: Mode::Light,
+        colorspace: &wallust::colorspaces::ColorSpace::Lab,
+        image_path,
+        colors,
+        outputs: vec![],
+        variants: vec![],
+    };
+    let base = mycols();
+    let Tfields = TemplateFields {
+        outputs: vec![("DP-1", output("/dp.png", &other)), ("HDMI-A-1", output("/hdmi.png", &base))],
+        ..output(wall_str, &base)
+    };
+    let v = minijinja::Value::from(&Tfields);
+    let render = |s: &str| jinja_env().render_named_str("sample", s, &v).unwrap();
+
+    assert_eq!(render(r#"{{ outputs["DP-1"].color4 }}"#), "#0000FF");
+    assert_eq!(render(r#"{{ outputs["HDMI-A-1"].color4 }} {{ color4 }}"#), "#040000 #040000");
+    assert_eq!(render(r#"{{ outputs["DP-1"].wallpaper }}"#), "/dp.png");
+    assert_eq!(render("{% for name in outputs %}{{ name }} {% endfor %}"), "DP-1 HDMI-A-1 ");
+}
+
+/// Each output gets the backend and threshold used for its own image, not the ones of the main one
+#[test]
+fn write_entry_outputs() {
+    use std::collections::HashMap;
+    use wallust::args::{Input, Output};
+    use wallust::backends::Source;
+    use wallust::config::{Config, Fields, WalStr};
+    use wallust::Generated;
+
+    let tmp = tempfile::tempdir().unwrap();
+    let target = tmp.path().join("out");
+    let image = tmp.path().join("dp.png");
+    std::fs::write(&image, b"only needs to exist").unwrap();
+    std::fs::write(tmp.path().join("t"), r#"{{ backend }} {{ threshold }} {{ outputs["DP-1"].backend }} {{ outputs["DP-1"].threshold }}"#).unwrap();
+
+    let fields = Fields { template: "t".into(), target: target.display().to_string(), pywal: None, max_depth: None, variant: None };
+    let c = Config {
+        templates: Some(HashMap::from([("t".to_string(), fields)])),
+        templates_dir: tmp.path().to_path_buf(),
+        backend: Backend::FastResize,
+        used_th: 20,
+        ..Config::default()
+    };
+
+    let o = Output { name: "DP-1".into(), input: Input::from(Source::from(image.clone())) };
+    let gen = Generated { colors: mycols(), backend: Backend::Full, th: 8, count: Some(16), mode: Mode::Dark, crops: vec![] };
+    c.write_entry(&WalStr::Path(image), &mycols(), &[(&o, gen)], &[], true).unwrap();
+
+    assert_eq!(std::fs::read_to_string(target).unwrap(), "fastresize 20 full 8");
+}
+
+/// The threshold that was used, found or not
+#[test]
+fn jinja_threshold() {
+    assert_eq!(jinja("{{ threshold }}"), "20");
+    assert_eq!(jinja("{% if threshold > 10 %}loose{% endif %}"), "loose");
+}
+
+/// Whether the palette is dark or light, what `palette = "auto"` chose
+#[test]
+fn jinja_mode() {
+    assert_eq!(jinja("{{ mode }}"), "light");
+    assert_eq!(jinja(r#"{% if mode == "light" %}day{% else %}night{% endif %}"#), "day");
+}
+
+/// With `variants`, the `dark` and `light` palettes are there too, and a template with `variant`
+/// gets one of them instead
+#[test]
+fn jinja_variants() {
+    use std::collections::HashMap;
+    use wallust::config::Fields;
+    use wallust::template::write_template;
+
+    let mut light = mycols();
+    light.background = Myrgb(Srgb::new(255_u8, 255, 255).into_format());
+    let (dark_name, light_name) = (PaletteName::Builtin(Palette::Dark16), PaletteName::Builtin(Palette::Light16));
+
+    let fields = |palette, mode, colors| TemplateFields {
+        alpha: 100,
+        threshold: 20,
+        backend: &Backend::Thumb,
+        palette,
+        mode,
+        colorspace: &wallust::colorspaces::ColorSpace::Lab,
+        image_path: wall_str,
+        colors,
+        outputs: vec![],
+        variants: vec![],
+    };
+    let base = mycols();
+    let Tfields = TemplateFields {
+        variants: vec![(Mode::Dark, fields(&dark_name, Mode::Dark, &base)), (Mode::Light, fields(&light_name, Mode::Light, &light))],
+        ..fields(&PaletteName::Builtin(Palette::Auto), Mode::Dark, &base)
+    };
+
+    let v = minijinja::Value::from(&Tfields);
+    let render = |s: &str| jinja_env().render_named_str("sample", s, &v).unwrap();
+    assert_eq!(render("{{ dark.background }} {{ light.background }}"), "#EEEEEE #FFFFFF");
+    assert_eq!(render("{{ light.palette }} {{ light.mode }} {{ palette }}"), "light16 light auto");
+
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::write(dir.path().join("bg"), "{{ background }} {{ mode }}").unwrap();
+    let target = |name: &str| dir.path().join(name).display().to_string();
+    let templates: HashMap<String, Fields> = toml::from_str(&format!(r#"
+main = {{ template = "bg", target = '{}' }}
+day = {{ template = "bg", target = '{}', variant = "light" }}
+"#, target("main"), target("day"))).unwrap();
+
+    write_template(dir.path(), &templates, &Tfields, true, false).unwrap();
+    assert_eq!(std::fs::read_to_string(target("main")).unwrap(), "#EEEEEE dark");
+    assert_eq!(std::fs::read_to_string(target("day")).unwrap(), "#FFFFFF light");
+
+    // `theme` and `cs` have no variants, the template is skipped instead of getting the main one
+    let night = target("night");
+    let templates: HashMap<String, Fields> = toml::from_str(&format!(r#"night = {{ template = "bg", target = '{night}', variant = "dark" }}"#)).unwrap();
+    write_template(dir.path(), &templates, &fields(&dark_name, Mode::Dark, &base), true, false).unwrap();
+    assert!(!std::path::Path::new(&night).exists());
+}
@@ -4,7 +4,7 @@
 #![allow(non_upper_case_globals)]
 #![allow(non_snake_case)]
 use wallust::backends::Backend;
-use wallust::palettes::Palette;
+use wallust::palettes::{Mode, Palette, PaletteName};
 use wallust::template::TemplateFields;
 use wallust::template::pywal;
 
@@ -18,11 +18,15 @@ macro_rules! waltest {
         fn $func_name() {
             let Tfields: &TemplateFields = &TemplateFields {
                 alpha: 100,
+                threshold: 20,
                 backend: &Backend::Thumb,
-                palette: &Palette::Dark,
+                palette: &PaletteName::Builtin(Palette::Dark),
+                mode: Mode::Dark,
                 colorspace: &wallust::colorspaces::ColorSpace::Lab,
                 image_path: wall_str,
                 colors: &mycols(),
+                outputs: vec![],
+                variants: vec![],
             };
 
             let template = include_str!($template);